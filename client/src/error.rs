@@ -0,0 +1,99 @@
+use std::fmt;
+use std::io;
+
+/// Class of a SCPI error, from the range of its code.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ErrorClass {
+    /// -199..=-100, the command was not understood.
+    Command,
+    /// -299..=-200, the command could not be executed, e.g. a parameter
+    /// out of range or the emergency stop latched.
+    Execution,
+    /// -399..=-300, device failure such as an unreachable servo controller
+    /// or a serial error.
+    DeviceSpecific,
+    /// -499..=-400, query problems.
+    Query,
+    /// Anything else, including device defined positive codes.
+    Other,
+}
+
+/// An error reported by the carrier.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScpiError {
+    pub code: i16,
+    pub message: String,
+}
+
+impl ScpiError {
+    pub fn class(&self) -> ErrorClass {
+        match self.code {
+            -199..=-100 => ErrorClass::Command,
+            -299..=-200 => ErrorClass::Execution,
+            -399..=-300 => ErrorClass::DeviceSpecific,
+            -499..=-400 => ErrorClass::Query,
+            _ => ErrorClass::Other,
+        }
+    }
+
+    /// Parse a `<code>,"<message>"` error line.
+    pub fn parse(line: &str) -> Option<Self> {
+        let (code, message) = line.split_once(',')?;
+        let message = message.trim().strip_prefix('"')?.strip_suffix('"')?;
+        Some(ScpiError {
+            code: code.trim().parse().ok()?,
+            message: message.replace("\"\"", "\""),
+        })
+    }
+}
+
+impl fmt::Display for ScpiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, \"{}\"", self.code, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The carrier rejected the command.
+    Scpi(ScpiError),
+    /// A response could not be parsed.
+    Response(String),
+    /// An argument can not be expressed in the command set.
+    Argument(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            Error::Scpi(err) => write!(f, "carrier error {}", err),
+            Error::Response(line) => write!(f, "unexpected response {:?}", line),
+            Error::Argument(what) => write!(f, "invalid argument: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<serialport::Error> for Error {
+    fn from(err: serialport::Error) -> Self {
+        Error::Io(err.into())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,293 @@
+//! Host client for the ash-carrier.
+//!
+//! [Carrier] builds the SCPI command lines accepted by the firmware and
+//! parses the responses. Verbose errors are enabled when connecting and
+//! every line is followed by `*OPC?`, so each line gets exactly one reply:
+//! the response data and `1`, or the error which aborted it.
+//!
+//! ```no_run
+//! use ash_carrier_client::Carrier;
+//!
+//! let mut carrier = Carrier::open("/dev/ttyACM0", 115200)?;
+//! carrier.set_body_pose([0.0, 0.1, 0.0], [0.0, 0.0, 0.05])?;
+//! carrier.walk(0.1, 0.0, 0.0)?;
+//! # Ok::<(), ash_carrier_client::Error>(())
+//! ```
+
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use ash_carrier_core::servo_commands::ServoControl;
+
+mod error;
+pub mod telemetry;
+
+pub use error::{Error, ErrorClass, Result, ScpiError};
+use telemetry::Record;
+
+pub use ash_carrier_core::tree::SERVOS;
+pub const PWIDTH_MIN: u16 = ServoControl::PWIDTH_MIN;
+pub const PWIDTH_MAX: u16 = ServoControl::PWIDTH_MAX;
+
+/// Typed client over any byte stream connected to a carrier interface.
+pub struct Carrier<T> {
+    port: T,
+    buffer: Vec<u8>,
+    /// Telemetry lines received while waiting for replies.
+    records: VecDeque<String>,
+    telemetry_mask: u8,
+}
+
+impl Carrier<Box<dyn serialport::SerialPort>> {
+    /// Open a serial port or the USB interface.
+    pub fn open(path: &str, baud: u32) -> Result<Self> {
+        let port = serialport::new(path, baud)
+            .timeout(Duration::from_secs(1))
+            .open()?;
+        Carrier::new(port)
+    }
+}
+
+impl<T: Read + Write> Carrier<T> {
+    /// Take over `port` and enable verbose errors on its interface.
+    pub fn new(port: T) -> Result<Self> {
+        let mut carrier = Carrier {
+            port,
+            buffer: Vec::new(),
+            records: VecDeque::new(),
+            telemetry_mask: telemetry::ALL,
+        };
+        // Terminate anything left over from a previous user
+        carrier.port.write_all(b"\n")?;
+        carrier.command("SYST:COMM:VERB ON")?;
+        Ok(carrier)
+    }
+
+    /// Execute a command line.
+    pub fn command(&mut self, line: &str) -> Result<()> {
+        self.query(line).map(|_| ())
+    }
+
+    /// Execute a line and return its response, empty for commands.
+    pub fn query(&mut self, line: &str) -> Result<String> {
+        self.send(line.as_bytes())
+    }
+
+    fn send(&mut self, line: &[u8]) -> Result<String> {
+        self.port.write_all(line)?;
+        self.port.write_all(b";*OPC?\n")?;
+        self.port.flush()?;
+        let reply = self.read_line()?;
+        if reply == "1" {
+            return Ok(String::new());
+        }
+        if let Some(data) = reply.strip_suffix(";1") {
+            return Ok(data.to_string());
+        }
+        match ScpiError::parse(&reply) {
+            Some(err) => Err(Error::Scpi(err)),
+            None => Err(Error::Response(reply)),
+        }
+    }
+
+    /// Next reply line, telemetry lines are put aside.
+    fn read_line(&mut self) -> Result<String> {
+        loop {
+            let mut byte = [0u8];
+            self.port.read_exact(&mut byte)?;
+            match byte[0] {
+                b'\r' => {}
+                b'\n' => {
+                    let line = String::from_utf8_lossy(&self.buffer).into_owned();
+                    self.buffer.clear();
+                    if line.starts_with("TLM ") {
+                        self.records.push_back(line);
+                    } else if !line.is_empty() {
+                        return Ok(line);
+                    }
+                }
+                b => self.buffer.push(b),
+            }
+        }
+    }
+
+    fn numbers<N: std::str::FromStr>(reply: &str) -> Result<Vec<N>> {
+        reply
+            .split(',')
+            .map(|v| v.trim().parse())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| Error::Response(reply.to_string()))
+    }
+
+    /// `*IDN?`
+    pub fn identify(&mut self) -> Result<String> {
+        self.query("*IDN?")
+    }
+
+    /// Set all pulse widths, sent as a binary block.
+    pub fn set_pulse_widths(&mut self, widths: &[u16; SERVOS]) -> Result<()> {
+        if widths.iter().any(|w| *w > PWIDTH_MAX) {
+            return Err(Error::Argument("pulse width out of range"));
+        }
+        let mut line = b"BODY:SERV:PWID:BLOC #248".to_vec();
+        for w in widths.iter() {
+            line.extend_from_slice(&w.to_le_bytes());
+        }
+        self.send(&line).map(|_| ())
+    }
+
+    /// Set the pulse width of servo `index`, 1 to 24.
+    pub fn set_pulse_width(&mut self, index: usize, width: u16) -> Result<()> {
+        if !(1..=SERVOS).contains(&index) {
+            return Err(Error::Argument("servo index out of range"));
+        }
+        if width > PWIDTH_MAX {
+            return Err(Error::Argument("pulse width out of range"));
+        }
+        self.command(&format!("BODY:SERV:PWID:SET {},{}", index, width))
+    }
+
+    pub fn pulse_widths(&mut self) -> Result<[u16; SERVOS]> {
+        let reply = self.query("BODY:SERV:PWID:ALL?")?;
+        let values: Vec<u16> = Self::numbers(&reply)?;
+        values
+            .as_slice()
+            .try_into()
+            .map_err(|_| Error::Response(reply))
+    }
+
+    /// Enable or disable all servos.
+    pub fn set_servos_enabled(&mut self, enable: bool) -> Result<()> {
+        self.command(if enable {
+            "BODY:SERV:STAT:ALL ON"
+        } else {
+            "BODY:SERV:STAT:ALL OFF"
+        })
+    }
+
+    /// Set body rotation (roll, pitch, yaw in rad) and translation (m).
+    pub fn set_body_pose(&mut self, rotation: [f32; 3], translation: [f32; 3]) -> Result<()> {
+        if !rotation
+            .iter()
+            .chain(translation.iter())
+            .all(|v| v.is_finite())
+        {
+            return Err(Error::Argument("pose is not finite"));
+        }
+        self.command(&format!(
+            "BODY:ATT:ROT {},{},{};:BODY:ATT:TRAN {},{},{}",
+            rotation[0], rotation[1], rotation[2], translation[0], translation[1], translation[2]
+        ))
+    }
+
+    /// Body rotation (roll, pitch, yaw in rad) and translation (m).
+    pub fn body_pose(&mut self) -> Result<([f32; 3], [f32; 3])> {
+        let reply = self.query("BODY:ATT:ROT?;:BODY:ATT:TRAN?")?;
+        let values: Vec<f32> = Self::numbers(&reply.replace(';', ","))?;
+        match values.as_slice() {
+            [r, p, y, tx, ty, tz] => Ok(([*r, *p, *y], [*tx, *ty, *tz])),
+            _ => Err(Error::Response(reply)),
+        }
+    }
+
+    /// Walk at `x`, `y` m/s turning at `yaw` rad/s.
+    pub fn walk(&mut self, x: f32, y: f32, yaw: f32) -> Result<()> {
+        if !(x.is_finite() && y.is_finite() && yaw.is_finite()) {
+            return Err(Error::Argument("velocity is not finite"));
+        }
+        self.command(&format!("BODY:VEL {},{},{}", x, y, yaw))
+    }
+
+    pub fn stop(&mut self) -> Result<()> {
+        self.walk(0.0, 0.0, 0.0)
+    }
+
+    /// Trip the emergency stop.
+    pub fn emergency_stop(&mut self) -> Result<()> {
+        self.command("SYST:EST")
+    }
+
+    /// Release the emergency stop, fails while the input is active.
+    pub fn reset_emergency_stop(&mut self) -> Result<()> {
+        self.command("SYST:EST:RES")
+    }
+
+    pub fn emergency_stopped(&mut self) -> Result<bool> {
+        Ok(self.query("SYST:EST?")? == "1")
+    }
+
+    /// Drain the error queue of the interface.
+    pub fn errors(&mut self) -> Result<Vec<ScpiError>> {
+        let mut errors = Vec::new();
+        loop {
+            let reply = self.query("SYST:ERR:NEXT?")?;
+            match ScpiError::parse(&reply) {
+                Some(err) if err.code == 0 => return Ok(errors),
+                Some(err) => errors.push(err),
+                None => return Err(Error::Response(reply)),
+            }
+        }
+    }
+
+    /// Start text telemetry with the fields in `mask`, see [telemetry].
+    pub fn start_telemetry(&mut self, rate: u16, mask: u8) -> Result<()> {
+        let mut line = String::new();
+        write!(
+            line,
+            "SYST:TEL:FORM CSV;MASK {};RATE {}",
+            mask & telemetry::ALL,
+            rate
+        )
+        .unwrap();
+        self.command(&line)?;
+        self.telemetry_mask = mask & telemetry::ALL;
+        Ok(())
+    }
+
+    pub fn stop_telemetry(&mut self) -> Result<()> {
+        self.command("SYST:TEL:RATE 0")?;
+        self.records.clear();
+        Ok(())
+    }
+
+    /// Telemetry records as they arrive.
+    pub fn telemetry(&mut self) -> Telemetry<'_, T> {
+        Telemetry { carrier: self }
+    }
+
+    fn next_record(&mut self) -> Result<Record> {
+        let line = match self.records.pop_front() {
+            Some(line) => line,
+            None => loop {
+                let mut byte = [0u8];
+                self.port.read_exact(&mut byte)?;
+                if byte[0] != b'\n' {
+                    self.buffer.push(byte[0]);
+                    continue;
+                }
+                let line = String::from_utf8_lossy(&self.buffer).trim().to_string();
+                self.buffer.clear();
+                if line.starts_with("TLM ") {
+                    break line;
+                }
+            },
+        };
+        Record::parse(&line, self.telemetry_mask).ok_or(Error::Response(line))
+    }
+}
+
+/// Blocking iterator over telemetry records.
+pub struct Telemetry<'a, T> {
+    carrier: &'a mut Carrier<T>,
+}
+
+impl<'a, T: Read + Write> Iterator for Telemetry<'a, T> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.carrier.next_record())
+    }
+}
@@ -0,0 +1,86 @@
+//! Text telemetry records, see `SYSTem:TELemetry` in the firmware.
+
+use crate::SERVOS;
+
+/// Commanded servo pulse widths.
+pub const TARGETS: u8 = 0x01;
+/// Pulse widths last written to the controllers, zero while off.
+pub const OUTPUTS: u8 = 0x02;
+/// Battery voltage.
+pub const BATTERY: u8 = 0x04;
+/// Main loop timing.
+pub const TIMING: u8 = 0x08;
+/// Error counters and the emergency stop latch.
+pub const ERRORS: u8 = 0x10;
+/// Gait phase.
+pub const GAIT: u8 = 0x20;
+pub const ALL: u8 = 0x3F;
+
+/// A telemetry record, fields not selected by the mask are `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Record {
+    pub time_ms: u32,
+    pub targets: Option<[u16; SERVOS]>,
+    pub outputs: Option<[u16; SERVOS]>,
+    pub battery_mv: Option<u32>,
+    /// Last and longest main loop time in µs.
+    pub loop_us: Option<(u32, u32)>,
+    pub i2c_errors: Option<u32>,
+    pub serial_errors: Option<u32>,
+    pub estop: Option<bool>,
+    /// Thousandths of the gait cycle, `Some(None)` while no gait runs.
+    pub gait_phase: Option<Option<u16>>,
+}
+
+impl Record {
+    /// Parse a `TLM <time>,<fields...>` line with the fields in `mask`.
+    pub fn parse(line: &str, mask: u8) -> Option<Self> {
+        let mut line = line.strip_prefix("TLM ")?;
+        // The only signed field comes last
+        let mut gait_phase = None;
+        if mask & GAIT != 0 {
+            let (rest, phase) = line.rsplit_once(',')?;
+            line = rest;
+            gait_phase = match phase.trim() {
+                "-1" => Some(None),
+                phase => Some(Some(phase.parse().ok()?)),
+            };
+        }
+        let mut values = line.split(',').map(|v| v.trim());
+        let mut next = || values.next()?.parse::<u32>().ok();
+        let widths = |next: &mut dyn FnMut() -> Option<u32>| {
+            let mut widths = [0u16; SERVOS];
+            for w in widths.iter_mut() {
+                *w = next()? as u16;
+            }
+            Some(widths)
+        };
+
+        let mut record = Record {
+            time_ms: next()?,
+            gait_phase,
+            ..Default::default()
+        };
+        if mask & TARGETS != 0 {
+            record.targets = Some(widths(&mut next)?);
+        }
+        if mask & OUTPUTS != 0 {
+            record.outputs = Some(widths(&mut next)?);
+        }
+        if mask & BATTERY != 0 {
+            record.battery_mv = Some(next()?);
+        }
+        if mask & TIMING != 0 {
+            record.loop_us = Some((next()?, next()?));
+        }
+        if mask & ERRORS != 0 {
+            record.i2c_errors = Some(next()?);
+            record.serial_errors = Some(next()?);
+            record.estop = Some(next()? != 0);
+        }
+        if next().is_some() {
+            return None;
+        }
+        Some(record)
+    }
+}
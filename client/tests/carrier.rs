@@ -0,0 +1,214 @@
+//! Client against a scripted firmware stand-in on a pseudo-terminal, for
+//! exact command lines and replies the simulator never sends. See
+//! `tests/sim.rs` for the client against the simulated firmware.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use ash_carrier_client::{telemetry, Carrier, Error, ErrorClass};
+use nix::pty::openpty;
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg};
+
+/// Lines received by the stand-in, with the `;*OPC?` suffix removed.
+type Received = Arc<Mutex<Vec<Vec<u8>>>>;
+
+/// Start a stand-in answering each line with `reply`, returns the client
+/// connected to it over a pty.
+fn connect<F>(reply: F) -> (Carrier<Box<dyn serialport::SerialPort>>, Received)
+where
+    F: Fn(&[u8]) -> Vec<u8> + Send + 'static,
+{
+    let pty = openpty(None, None).unwrap();
+    let mut termios = tcgetattr(&pty.slave).unwrap();
+    cfmakeraw(&mut termios);
+    tcsetattr(&pty.slave, SetArg::TCSANOW, &termios).unwrap();
+    let path = nix::unistd::ttyname(&pty.slave).unwrap();
+
+    let received = Received::default();
+    let log = received.clone();
+    let master = File::from(pty.master);
+    thread::spawn(move || {
+        let mut writer = master.try_clone().unwrap();
+        let mut reader = BufReader::new(master);
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line).unwrap_or(0) > 0 {
+            line.pop();
+            if let Some(command) = line.strip_suffix(b";*OPC?") {
+                log.lock().unwrap().push(command.to_vec());
+                writer.write_all(&reply(command)).unwrap();
+            }
+            line.clear();
+        }
+    });
+
+    let carrier = Carrier::open(path.to_str().unwrap(), 115200).unwrap();
+    // Keep the slave open until the client has it
+    drop(pty.slave);
+    (carrier, received)
+}
+
+fn ok(_: &[u8]) -> Vec<u8> {
+    b"1\n".to_vec()
+}
+
+fn last(received: &Received) -> Vec<u8> {
+    received.lock().unwrap().last().unwrap().clone()
+}
+
+#[test]
+fn enables_verbose_errors() {
+    let (_carrier, received) = connect(ok);
+    assert_eq!(received.lock().unwrap()[0], b"SYST:COMM:VERB ON");
+}
+
+#[test]
+fn pulse_widths_are_sent_as_block() {
+    let (mut carrier, received) = connect(ok);
+    let mut widths = [1500u16; 24];
+    widths[23] = 3000;
+    carrier.set_pulse_widths(&widths).unwrap();
+
+    let mut expected = b"BODY:SERV:PWID:BLOC #248".to_vec();
+    for w in widths.iter() {
+        expected.extend_from_slice(&w.to_le_bytes());
+    }
+    assert_eq!(last(&received), expected);
+}
+
+#[test]
+fn pulse_width_is_range_checked() {
+    let (mut carrier, received) = connect(ok);
+    assert!(matches!(
+        carrier.set_pulse_width(25, 1500),
+        Err(Error::Argument(_))
+    ));
+    assert!(matches!(
+        carrier.set_pulse_widths(&[3001; 24]),
+        Err(Error::Argument(_))
+    ));
+    assert!(matches!(
+        carrier.set_pulse_width(24, 3001),
+        Err(Error::Argument(_))
+    ));
+    carrier.set_pulse_width(24, 1200).unwrap();
+    assert_eq!(last(&received), b"BODY:SERV:PWID:SET 24,1200");
+}
+
+#[test]
+fn pulse_widths_are_parsed() {
+    let (mut carrier, _) = connect(|line| {
+        if line == b"BODY:SERV:PWID:ALL?" {
+            let widths: Vec<String> = (0..24).map(|i| (1000 + i).to_string()).collect();
+            format!("{};1\n", widths.join(",")).into_bytes()
+        } else {
+            ok(line)
+        }
+    });
+    let widths = carrier.pulse_widths().unwrap();
+    assert_eq!(widths[0], 1000);
+    assert_eq!(widths[23], 1023);
+}
+
+#[test]
+fn body_pose_and_walk() {
+    let (mut carrier, received) = connect(|line| {
+        if line == b"BODY:ATT:ROT?;:BODY:ATT:TRAN?" {
+            b"0.1,0.2,0.3;0,0,0.05;1\n".to_vec()
+        } else {
+            ok(line)
+        }
+    });
+    carrier
+        .set_body_pose([0.0, 0.5, -0.25], [0.0, 0.0, 0.1])
+        .unwrap();
+    assert_eq!(
+        last(&received),
+        b"BODY:ATT:ROT 0,0.5,-0.25;:BODY:ATT:TRAN 0,0,0.1"
+    );
+    assert_eq!(
+        carrier.body_pose().unwrap(),
+        ([0.1, 0.2, 0.3], [0.0, 0.0, 0.05])
+    );
+
+    carrier.walk(0.2, 0.0, -0.5).unwrap();
+    assert_eq!(last(&received), b"BODY:VEL 0.2,0,-0.5");
+    assert!(matches!(
+        carrier.walk(f32::NAN, 0.0, 0.0),
+        Err(Error::Argument(_))
+    ));
+}
+
+#[test]
+fn errors_are_mapped() {
+    let (mut carrier, _) = connect(|line| {
+        if line == b"SYST:EST:RES" {
+            b"-200,\"Execution error;Emergency stop input active\"\n".to_vec()
+        } else if line == b"SYST:ERR:NEXT?" {
+            b"0,\"No error\";1\n".to_vec()
+        } else {
+            ok(line)
+        }
+    });
+    match carrier.reset_emergency_stop() {
+        Err(Error::Scpi(err)) => {
+            assert_eq!(err.code, -200);
+            assert_eq!(err.class(), ErrorClass::Execution);
+            assert_eq!(err.message, "Execution error;Emergency stop input active");
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(carrier.errors().unwrap().is_empty());
+}
+
+#[test]
+fn garbage_is_a_response_error() {
+    let (mut carrier, _) = connect(|line| {
+        if line == b"*IDN?" {
+            b"what\n".to_vec()
+        } else {
+            ok(line)
+        }
+    });
+    assert!(matches!(carrier.identify(), Err(Error::Response(_))));
+}
+
+#[test]
+fn telemetry_is_set_aside_and_parsed() {
+    let (mut carrier, received) = connect(|line| {
+        if line == b"SYST:EST?" {
+            b"TLM 100,7400\nTLM 200,7390\n0;1\n".to_vec()
+        } else {
+            ok(line)
+        }
+    });
+    carrier.start_telemetry(20, telemetry::BATTERY).unwrap();
+    assert_eq!(last(&received), b"SYST:TEL:FORM CSV;MASK 4;RATE 20");
+
+    assert!(!carrier.emergency_stopped().unwrap());
+    let records: Vec<_> = carrier.telemetry().take(2).map(|r| r.unwrap()).collect();
+    assert_eq!(records[0].time_ms, 100);
+    assert_eq!(records[0].battery_mv, Some(7400));
+    assert_eq!(records[1].battery_mv, Some(7390));
+    assert_eq!(records[1].targets, None);
+}
+
+#[test]
+fn telemetry_record_with_all_fields() {
+    let mut line = String::from("TLM 5");
+    for i in 0..48 {
+        line += &format!(",{}", i);
+    }
+    line += ",7400,800,950,1,2,1,-1";
+    let record = telemetry::Record::parse(&line, telemetry::ALL).unwrap();
+    assert_eq!(record.targets.unwrap()[23], 23);
+    assert_eq!(record.outputs.unwrap()[0], 24);
+    assert_eq!(record.loop_us, Some((800, 950)));
+    assert_eq!(record.estop, Some(true));
+    assert_eq!(record.gait_phase, Some(None));
+    assert!(telemetry::Record::parse(&line, telemetry::TARGETS).is_none());
+
+    let record = telemetry::Record::parse("TLM 5,7400,250", telemetry::BATTERY | telemetry::GAIT);
+    assert_eq!(record.unwrap().gait_phase, Some(Some(250)));
+}
@@ -0,0 +1,157 @@
+//! Client against the simulated firmware on a pseudo-terminal, so the
+//! command lines it builds go through the real command tree.
+
+use std::fs::File;
+use std::io::Read;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+
+use ash_carrier_client::{telemetry, Carrier, Error, ErrorClass};
+use ash_carrier_sim::{run, Hardware, BANK_ADDRESSES};
+use nix::pty::openpty;
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg};
+
+/// The emergency stop latch is global, tests must not overlap.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// Run the simulator on `hardware` with a client connected to it over a
+/// pty, until `test` returns.
+fn with_carrier<F>(hardware: &Hardware, test: F)
+where
+    F: FnOnce(&mut Carrier<Box<dyn serialport::SerialPort>>),
+{
+    let _lock = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    let pty = openpty(None, None).unwrap();
+    let mut termios = tcgetattr(&pty.slave).unwrap();
+    cfmakeraw(&mut termios);
+    tcsetattr(&pty.slave, SetArg::TCSANOW, &termios).unwrap();
+    let path = nix::unistd::ttyname(&pty.slave).unwrap();
+
+    let mut master = File::from(pty.master);
+    let mut output = master.try_clone().unwrap();
+    let (tx, rx) = mpsc::channel();
+    // Reading fails once the client closed the slave, which stops the
+    // simulator
+    thread::spawn(move || {
+        let mut buf = [0u8; 256];
+        while let Ok(n) = master.read(&mut buf) {
+            if n == 0 || buf[..n].iter().any(|b| tx.send(*b).is_err()) {
+                break;
+            }
+        }
+    });
+    let hw = hardware.clone();
+    let sim = thread::spawn(move || {
+        run(&hw, rx, |bytes| {
+            let _ = std::io::Write::write_all(&mut output, bytes);
+        })
+    });
+
+    let mut carrier = Carrier::open(path.to_str().unwrap(), 115200).unwrap();
+    drop(pty.slave);
+    test(&mut carrier);
+    drop(carrier);
+    sim.join().unwrap();
+}
+
+#[test]
+fn identifies() {
+    with_carrier(&Hardware::new(), |carrier| {
+        assert!(carrier
+            .identify()
+            .unwrap()
+            .starts_with("GPA-Robotics,ash-carrier,0,sim-"));
+        assert!(carrier.errors().unwrap().is_empty());
+    });
+}
+
+#[test]
+fn pulse_widths_reach_the_controllers() {
+    let hardware = Hardware::new();
+    with_carrier(&hardware, |carrier| {
+        let mut widths = [1500u16; 24];
+        widths[0] = 1200;
+        widths[23] = 3000;
+        carrier.set_pulse_widths(&widths).unwrap();
+        carrier.set_pulse_width(4, 1800).unwrap();
+        widths[3] = 1800;
+        assert_eq!(carrier.pulse_widths().unwrap(), widths);
+
+        // Once the block and the query went through, the next line has
+        // seen an update of the controllers
+        carrier.identify().unwrap();
+        assert_eq!(hardware.i2c.outputs(BANK_ADDRESSES[0])[0], 246);
+        assert_eq!(hardware.i2c.outputs(BANK_ADDRESSES[1])[1], 369);
+
+        assert!(matches!(
+            carrier.set_pulse_width(1, 3001),
+            Err(Error::Argument(_))
+        ));
+        // The firmware range check, bypassing the one of the client
+        match carrier.command("BODY:SERV:PWID:SET 1,3001") {
+            Err(Error::Scpi(err)) => assert_eq!(err.code, -222),
+            other => panic!("unexpected {:?}", other),
+        }
+        // The error stays queued, once
+        let errors = carrier.errors().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, -222);
+    });
+}
+
+#[test]
+fn body_pose_and_walk() {
+    with_carrier(&Hardware::new(), |carrier| {
+        carrier
+            .set_body_pose([0.0, 0.25, -0.5], [0.0, 0.0, 0.05])
+            .unwrap();
+        let (rotation, translation) = carrier.body_pose().unwrap();
+        let expected = [0.0, 0.25, -0.5, 0.0, 0.0, 0.05];
+        for (value, expected) in rotation.iter().chain(translation.iter()).zip(&expected) {
+            assert!((value - expected).abs() < 1e-5, "{} {}", value, expected);
+        }
+        carrier.walk(0.1, 0.0, -0.2).unwrap();
+        carrier.stop().unwrap();
+    });
+}
+
+#[test]
+fn emergency_stop_errors_are_mapped() {
+    let hardware = Hardware::new();
+    with_carrier(&hardware, |carrier| {
+        hardware.estop.set_high(false);
+        carrier.identify().unwrap();
+        assert!(carrier.emergency_stopped().unwrap());
+        match carrier.reset_emergency_stop() {
+            Err(Error::Scpi(err)) => assert_eq!(err.class(), ErrorClass::Execution),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        hardware.estop.set_high(true);
+        carrier.identify().unwrap();
+        carrier.reset_emergency_stop().unwrap();
+        assert!(!carrier.emergency_stopped().unwrap());
+    });
+}
+
+#[test]
+fn telemetry_records_are_parsed() {
+    let hardware = Hardware::new();
+    hardware.set_battery_mv(7400);
+    with_carrier(&hardware, |carrier| {
+        carrier.start_telemetry(50, telemetry::ALL).unwrap();
+        let record = carrier.telemetry().next().unwrap().unwrap();
+        assert_eq!(record.targets, Some([1500; 24]));
+        assert!(record.outputs.is_some());
+        assert!((7300..7500).contains(&record.battery_mv.unwrap()));
+        assert_eq!(record.estop, Some(false));
+        assert_eq!(record.gait_phase, Some(None));
+
+        carrier.start_telemetry(50, telemetry::BATTERY).unwrap();
+        // Records still queued from before carry every field
+        let record = carrier.telemetry().find_map(|r| r.ok()).unwrap();
+        assert!(record.battery_mv.is_some());
+        carrier.stop_telemetry().unwrap();
+    });
+}
@@ -0,0 +1,132 @@
+use libm::{asinf, atan2f, sqrtf};
+use nalgebra::Vector3;
+
+/// Measured body attitude in radians, positive roll lowers the right side and
+/// positive pitch lowers the front.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Attitude {
+    pub roll: f32,
+    pub pitch: f32,
+}
+
+/// One reading of the IMU in body axes, x forward, y left and z up.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ImuSample {
+    /// Specific force in g, (0, 0, 1) when level and at rest.
+    pub accel: Vector3<f32>,
+    /// Angular rate in rad/s.
+    pub gyro: Vector3<f32>,
+}
+
+/// Mahony complementary filter without magnetometer.
+///
+/// The gyro is integrated and its drift corrected towards the gravity
+/// direction measured by the accelerometer. Yaw is not observable and only
+/// kept to integrate the other two correctly.
+pub struct Mahony {
+    /// Proportional gain, the inverse of the time constant in s.
+    pub kp: f32,
+    /// Integral gain, learns the gyro bias.
+    pub ki: f32,
+    /// Orientation, body to world, w x y z.
+    q: [f32; 4],
+    bias: Vector3<f32>,
+    initialized: bool,
+}
+
+impl Mahony {
+    pub const KP: f32 = 1.0;
+    pub const KI: f32 = 0.05;
+    /// Accelerometer readings further than this from 1 g are not used for
+    /// correction, the body is accelerating or hit something.
+    pub const ACCEL_TOLERANCE: f32 = 0.2;
+
+    pub fn new(kp: f32, ki: f32) -> Self {
+        Mahony {
+            kp,
+            ki,
+            q: [1.0, 0.0, 0.0, 0.0],
+            bias: Vector3::zeros(),
+            initialized: false,
+        }
+    }
+
+    /// Start over, the next usable sample sets the attitude directly.
+    pub fn reset(&mut self) {
+        *self = Mahony::new(self.kp, self.ki);
+    }
+
+    /// Integrate `sample`, taken `dt` seconds after the previous one.
+    pub fn update(&mut self, sample: &ImuSample, dt: f32) {
+        let norm = sample.accel.norm();
+        let usable = (norm - 1.0).abs() <= Self::ACCEL_TOLERANCE;
+        if !self.initialized {
+            if usable {
+                self.level_from(&(sample.accel / norm));
+                self.initialized = true;
+            }
+            return;
+        }
+
+        let mut gyro = sample.gyro;
+        if usable {
+            let a = sample.accel / norm;
+            let [q0, q1, q2, q3] = self.q;
+            // Up as seen from the body according to the current estimate
+            let v = Vector3::new(
+                2.0 * (q1 * q3 - q0 * q2),
+                2.0 * (q0 * q1 + q2 * q3),
+                q0 * q0 - q1 * q1 - q2 * q2 + q3 * q3,
+            );
+            let error = a.cross(&v);
+            if self.ki > 0.0 {
+                self.bias += error * (self.ki * dt);
+            }
+            gyro += error * self.kp + self.bias;
+        }
+
+        let [q0, q1, q2, q3] = self.q;
+        let (gx, gy, gz) = (gyro.x * 0.5 * dt, gyro.y * 0.5 * dt, gyro.z * 0.5 * dt);
+        self.q = [
+            q0 - q1 * gx - q2 * gy - q3 * gz,
+            q1 + q0 * gx + q2 * gz - q3 * gy,
+            q2 + q0 * gy - q1 * gz + q3 * gx,
+            q3 + q0 * gz + q1 * gy - q2 * gx,
+        ];
+        self.normalize();
+    }
+
+    /// Orientation with zero yaw matching the unit up vector `a`.
+    fn level_from(&mut self, a: &Vector3<f32>) {
+        let roll = atan2f(a.y, a.z);
+        let pitch = atan2f(-a.x, sqrtf(a.y * a.y + a.z * a.z));
+        let (sr, cr) = libm::sincosf(roll * 0.5);
+        let (sp, cp) = libm::sincosf(pitch * 0.5);
+        self.q = [cr * cp, sr * cp, cr * sp, -sr * sp];
+        self.bias = Vector3::zeros();
+    }
+
+    fn normalize(&mut self) {
+        let norm = sqrtf(self.q.iter().map(|q| q * q).sum());
+        for q in self.q.iter_mut() {
+            *q /= norm;
+        }
+    }
+
+    /// Current estimate, None until the first usable sample.
+    pub fn attitude(&self) -> Option<Attitude> {
+        if !self.initialized {
+            return None;
+        }
+        let [q0, q1, q2, q3] = self.q;
+        Some(Attitude {
+            roll: atan2f(2.0 * (q0 * q1 + q2 * q3), 1.0 - 2.0 * (q1 * q1 + q2 * q2)),
+            pitch: asinf((2.0 * (q0 * q2 - q3 * q1)).clamp(-1.0, 1.0)),
+        })
+    }
+
+    /// Learned gyro bias in rad/s, with the opposite sign.
+    pub fn bias(&self) -> Vector3<f32> {
+        self.bias
+    }
+}
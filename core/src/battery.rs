@@ -0,0 +1,42 @@
+use embedded_hal::adc::{Channel, OneShot};
+
+/// Pack voltage to ADC input divider, 100k over 10k.
+const DIVIDER: u32 = 11;
+/// ADC reference and full scale reading.
+const VREF_MV: u32 = 3300;
+const FULL_SCALE: u32 = 4095;
+
+/// Battery voltage sensing through a divider on an ADC input.
+pub struct Battery<PIN> {
+    pin: PIN,
+    millivolts: u32,
+}
+
+impl<PIN> Battery<PIN> {
+    pub fn new(pin: PIN) -> Self {
+        Battery { pin, millivolts: 0 }
+    }
+
+    /// Take a sample, the result is low pass filtered. Failed conversions
+    /// are skipped.
+    pub fn sample<ADC, A>(&mut self, adc: &mut A)
+    where
+        PIN: Channel<ADC>,
+        A: OneShot<ADC, u16, PIN>,
+    {
+        let sample = match adc.read(&mut self.pin) {
+            Ok(sample) => sample as u32,
+            Err(_) => return,
+        };
+        let millivolts = sample.min(FULL_SCALE) * VREF_MV / FULL_SCALE * DIVIDER;
+        self.millivolts = if self.millivolts == 0 {
+            millivolts
+        } else {
+            (self.millivolts * 7 + millivolts) / 8
+        };
+    }
+
+    pub fn millivolts(&self) -> u32 {
+        self.millivolts
+    }
+}
@@ -0,0 +1,63 @@
+use libm::{asinf, sinf};
+use nalgebra::Vector3;
+
+use crate::attitude::Attitude;
+use crate::servo_commands::ServoControl;
+use crate::tree::{LEGS, SERVOS};
+
+/// Hip positions in the body frame in m, x forward and y left. Legs 1 to 4
+/// are on the left from front to back, 5 to 8 on the right.
+pub const HIPS: [[f32; 2]; LEGS] = [
+    [0.15, 0.1],
+    [0.05, 0.1],
+    [-0.05, 0.1],
+    [-0.15, 0.1],
+    [0.15, -0.1],
+    [0.05, -0.1],
+    [-0.05, -0.1],
+    [-0.15, -0.1],
+];
+/// Hip to knee, the femur servo is the second servo of a leg.
+const FEMUR_M: f32 = 0.08;
+/// Servo travel, 1000 µs for 90°.
+const US_PER_RAD: f32 = 1000.0 / core::f32::consts::FRAC_PI_2;
+
+/// Commanded body velocity.
+///
+/// Nothing walks yet, the velocity is only stored for a gait to pick up.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Velocity {
+    /// m/s in the body frame.
+    pub linear: Vector3<f32>,
+    /// rad/s about the body axes.
+    pub angular: Vector3<f32>,
+}
+
+impl Velocity {
+    pub fn zero() -> Self {
+        Velocity {
+            linear: Vector3::zeros(),
+            angular: Vector3::zeros(),
+        }
+    }
+}
+
+/// Tilt the body by `attitude` through the foot heights of the legs
+/// standing at `targets`.
+///
+/// Each hip is raised or lowered by the change of its height and the femur
+/// turned to lengthen or shorten the leg by as much. A longer pulse lowers
+/// the foot of a left leg, the right legs are mirrored. Servos without a
+/// pulse are left alone.
+pub fn tilt(targets: &mut [u16; SERVOS], attitude: &Attitude) {
+    for (leg, [x, y]) in HIPS.iter().enumerate() {
+        let dz = y * sinf(attitude.roll) - x * sinf(attitude.pitch);
+        let angle = asinf((dz / FEMUR_M).clamp(-1.0, 1.0));
+        let side = if *y > 0.0 { 1.0 } else { -1.0 };
+        let femur = &mut targets[leg * SERVOS / LEGS + 1];
+        if *femur != 0 {
+            let width = *femur as f32 + side * angle * US_PER_RAD;
+            *femur = (width + 0.5).clamp(1.0, ServoControl::PWIDTH_MAX as f32) as u16;
+        }
+    }
+}
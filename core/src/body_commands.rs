@@ -0,0 +1,357 @@
+use core::cell::RefCell;
+use core::convert::{TryFrom, TryInto};
+use nalgebra::{Rotation3, Translation3, Vector3};
+use scpi::error::Result;
+use scpi::prelude::*;
+use scpi::qonly;
+use uom::si::angle::radian;
+use uom::si::f32;
+use uom::si::length::meter;
+
+use crate::attitude::Attitude;
+use crate::body::Velocity;
+use crate::contact::DescentSettings;
+use crate::level::{LevelConfig, LevelSettings};
+
+/// # `[:BODY]:ATTitude:ROTation <roll>,<pitch>,<yaw>`
+/// Set the body rotation, in radians unless a unit is given.
+///
+/// # `[:BODY]:ATTitude:ROTation?`
+/// Query the body rotation in radians.
+///
+pub struct BodyAttRotCommand<'a> {
+    rotation: &'a RefCell<Rotation3<f32>>,
+}
+
+impl<'a> BodyAttRotCommand<'a> {
+    pub fn new(rotation: &'a RefCell<Rotation3<f32>>) -> Self {
+        BodyAttRotCommand { rotation }
+    }
+}
+
+impl<'a> Command for BodyAttRotCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let rotation = Rotation3::<f32>::from_euler_angles(
+            f32::Angle::try_from(args.next_data(false)?.unwrap())?.get::<radian>(),
+            f32::Angle::try_from(args.next_data(false)?.unwrap())?.get::<radian>(),
+            f32::Angle::try_from(args.next_data(false)?.unwrap())?.get::<radian>(),
+        );
+        self.rotation.replace(rotation);
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let (roll, pitch, yaw) = self.rotation.borrow().euler_angles();
+        response.data(roll).data(pitch).data(yaw).finish()
+    }
+}
+
+/// # `[:BODY]:ATTitude:TRANslation <x>,<y>,<z>`
+/// Set the body translation, in meters unless a unit is given.
+///
+/// # `[:BODY]:ATTitude:TRANslation?`
+/// Query the body translation in meters.
+///
+pub struct BodyAttTranCommand<'a> {
+    translation: &'a RefCell<Translation3<f32>>,
+}
+
+impl<'a> BodyAttTranCommand<'a> {
+    pub fn new(translation: &'a RefCell<Translation3<f32>>) -> Self {
+        BodyAttTranCommand { translation }
+    }
+}
+
+impl<'a> Command for BodyAttTranCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let translation = Translation3::<f32>::new(
+            f32::Length::try_from(args.next_data(false)?.unwrap())?.get::<meter>(),
+            f32::Length::try_from(args.next_data(false)?.unwrap())?.get::<meter>(),
+            f32::Length::try_from(args.next_data(false)?.unwrap())?.get::<meter>(),
+        );
+        self.translation.replace(translation);
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let vec = self.translation.borrow();
+        response.data(vec.x).data(vec.y).data(vec.z).finish()
+    }
+}
+
+/// # `[:BODY]:VELocity <x>,<y>,<yaw>`
+/// Set the walking velocity in m/s and rad/s.
+///
+/// # `[:BODY]:VELocity?`
+/// Query the walking velocity.
+///
+pub struct BodyVelCommand<'a> {
+    velocity: &'a RefCell<Velocity>,
+}
+
+impl<'a> BodyVelCommand<'a> {
+    pub fn new(velocity: &'a RefCell<Velocity>) -> Self {
+        BodyVelCommand { velocity }
+    }
+}
+
+impl<'a> Command for BodyVelCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let x: f32 = args.next_data(false)?.unwrap().try_into()?;
+        let y: f32 = args.next_data(false)?.unwrap().try_into()?;
+        let yaw: f32 = args.next_data(false)?.unwrap().try_into()?;
+        if !(x.is_finite() && y.is_finite() && yaw.is_finite()) {
+            return Err(ErrorCode::IllegalParameterValue.into());
+        }
+        let mut velocity = self.velocity.borrow_mut();
+        velocity.linear = Vector3::new(x, y, 0.0);
+        velocity.angular = Vector3::new(0.0, 0.0, yaw);
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let velocity = self.velocity.borrow();
+        response
+            .data(velocity.linear.x)
+            .data(velocity.linear.y)
+            .data(velocity.angular.z)
+            .finish()
+    }
+}
+
+/// # `[:BODY]:LEVel <bool>`
+/// Keep the body level with the measured attitude. The commanded rotation
+/// is then relative to gravity instead of to the ground.
+///
+/// # `[:BODY]:LEVel?`
+/// Query if levelling is on.
+///
+pub struct BodyLevCommand<'a> {
+    level: &'a RefCell<LevelSettings>,
+}
+
+impl<'a> BodyLevCommand<'a> {
+    pub fn new(level: &'a RefCell<LevelSettings>) -> Self {
+        BodyLevCommand { level }
+    }
+}
+
+impl<'a> Command for BodyLevCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        self.level.borrow_mut().enabled = args.next_data(false)?.unwrap().try_into()?;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(self.level.borrow().enabled).finish()
+    }
+}
+
+/// # `[:BODY]:LEVel:GAIN <kp>,<ki>`
+/// Set the proportional and integral (1/s) gain of the levelling, at most
+/// 10 each.
+///
+/// # `[:BODY]:LEVel:GAIN?`
+/// Query the levelling gains.
+///
+pub struct BodyLevGainCommand<'a> {
+    level: &'a RefCell<LevelSettings>,
+}
+
+impl<'a> BodyLevGainCommand<'a> {
+    pub fn new(level: &'a RefCell<LevelSettings>) -> Self {
+        BodyLevGainCommand { level }
+    }
+}
+
+impl<'a> Command for BodyLevGainCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let kp: f32 = args.next_data(false)?.unwrap().try_into()?;
+        let ki: f32 = args.next_data(false)?.unwrap().try_into()?;
+        if !(0.0..=LevelConfig::KP_MAX).contains(&kp) || !(0.0..=LevelConfig::KI_MAX).contains(&ki)
+        {
+            return Err(ErrorCode::DataOutOfRange.into());
+        }
+        let config = &mut self.level.borrow_mut().config;
+        config.kp = kp;
+        config.ki = ki;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let config = self.level.borrow().config;
+        response.data(config.kp).data(config.ki).finish()
+    }
+}
+
+/// # `[:BODY]:LEVel:LIMit <angle>,<rate>`
+/// Set the largest levelling correction, at most 0.5 rad, and how fast it
+/// may change, at most 2 rad/s. Radians unless a unit is given.
+///
+/// # `[:BODY]:LEVel:LIMit?`
+/// Query the levelling limits.
+///
+pub struct BodyLevLimCommand<'a> {
+    level: &'a RefCell<LevelSettings>,
+}
+
+impl<'a> BodyLevLimCommand<'a> {
+    pub fn new(level: &'a RefCell<LevelSettings>) -> Self {
+        BodyLevLimCommand { level }
+    }
+}
+
+impl<'a> Command for BodyLevLimCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let angle = f32::Angle::try_from(args.next_data(false)?.unwrap())?.get::<radian>();
+        let rate: f32 = args.next_data(false)?.unwrap().try_into()?;
+        if !(0.0..=LevelConfig::MAX_ANGLE).contains(&angle)
+            || !(0.0..=LevelConfig::MAX_RATE).contains(&rate)
+        {
+            return Err(ErrorCode::DataOutOfRange.into());
+        }
+        let config = &mut self.level.borrow_mut().config;
+        config.max_angle = angle;
+        config.max_rate = rate;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let config = self.level.borrow().config;
+        response
+            .data(config.max_angle)
+            .data(config.max_rate)
+            .finish()
+    }
+}
+
+/// # `[:BODY]:LEVel:CORRection?`
+/// Query the roll and pitch in radians currently added to the commanded
+/// rotation by the levelling.
+///
+pub struct BodyLevCorrCommand<'a> {
+    correction: &'a RefCell<Attitude>,
+}
+
+impl<'a> BodyLevCorrCommand<'a> {
+    pub fn new(correction: &'a RefCell<Attitude>) -> Self {
+        BodyLevCorrCommand { correction }
+    }
+}
+
+impl<'a> Command for BodyLevCorrCommand<'a> {
+    qonly!();
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let correction = self.correction.borrow();
+        response
+            .data(correction.roll)
+            .data(correction.pitch)
+            .finish()
+    }
+}
+
+/// # `[:BODY]:GAIT:ADAPtive <bool>`
+/// Let swinging legs keep descending until foot contact instead of stopping
+/// at the planned height. Legs without a contact sensor are not affected.
+///
+/// # `[:BODY]:GAIT:ADAPtive?`
+/// Query if adaptive touchdown is on.
+///
+pub struct BodyGaitAdapCommand<'a> {
+    descent: &'a RefCell<DescentSettings>,
+}
+
+impl<'a> BodyGaitAdapCommand<'a> {
+    pub fn new(descent: &'a RefCell<DescentSettings>) -> Self {
+        BodyGaitAdapCommand { descent }
+    }
+}
+
+impl<'a> Command for BodyGaitAdapCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        self.descent.borrow_mut().adaptive = args.next_data(false)?.unwrap().try_into()?;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(self.descent.borrow().adaptive).finish()
+    }
+}
+
+/// # `[:BODY]:GAIT:ADAPtive:DEPTh <length>`
+/// Set how far below the planned height a foot may search for the ground,
+/// at most 0.1 m. Meters unless a unit is given.
+///
+/// # `[:BODY]:GAIT:ADAPtive:DEPTh?`
+/// Query the search depth in meters.
+///
+pub struct BodyGaitAdapDepthCommand<'a> {
+    descent: &'a RefCell<DescentSettings>,
+}
+
+impl<'a> BodyGaitAdapDepthCommand<'a> {
+    pub fn new(descent: &'a RefCell<DescentSettings>) -> Self {
+        BodyGaitAdapDepthCommand { descent }
+    }
+}
+
+impl<'a> Command for BodyGaitAdapDepthCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let depth = f32::Length::try_from(args.next_data(false)?.unwrap())?.get::<meter>();
+        if !(0.0..=DescentSettings::MAX_DEPTH).contains(&depth) {
+            return Err(ErrorCode::DataOutOfRange.into());
+        }
+        self.descent.borrow_mut().max_depth = depth;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(self.descent.borrow().max_depth).finish()
+    }
+}
@@ -0,0 +1,163 @@
+use embedded_hal::digital::v2::InputPin;
+
+use crate::tree::LEGS;
+
+/// Senses if a foot touches the ground.
+pub trait ContactSensor {
+    /// Current contact, None when the sensor can not be read.
+    fn contact(&mut self) -> Option<bool>;
+}
+
+/// Read the sensors of the legs in `fitted`, bit per leg. The others are
+/// unknown, an open switch can't be told from a missing one.
+pub fn read<S: ContactSensor>(sensors: &mut [S; LEGS], fitted: u8) -> [Option<bool>; LEGS] {
+    let mut contacts = [None; LEGS];
+    for (leg, (contact, sensor)) in contacts.iter_mut().zip(sensors.iter_mut()).enumerate() {
+        if fitted & 1 << leg != 0 {
+            *contact = sensor.contact();
+        }
+    }
+    contacts
+}
+
+/// Contact switch on a GPIO, closing to ground when the foot is loaded.
+pub struct Switch<PIN> {
+    pin: PIN,
+}
+
+impl<PIN: InputPin> Switch<PIN> {
+    pub fn new(pin: PIN) -> Self {
+        Switch { pin }
+    }
+}
+
+impl<PIN: InputPin> ContactSensor for Switch<PIN> {
+    fn contact(&mut self) -> Option<bool> {
+        self.pin.is_low().ok()
+    }
+}
+
+/// Contact from an analog load reading, a force sensor under the foot or
+/// an estimate from the servo current.
+///
+/// `read` returns the load in sensor units, None when it fails.
+pub struct ForceSensor<R> {
+    read: R,
+    /// Load at which the foot is considered touching.
+    pub touch: u16,
+    /// Load below which the foot is considered lifted again, below `touch`
+    /// so a noisy reading does not chatter.
+    pub release: u16,
+    contact: bool,
+}
+
+impl<R> ForceSensor<R>
+where
+    R: FnMut() -> Option<u16>,
+{
+    pub fn new(read: R, touch: u16, release: u16) -> Self {
+        ForceSensor {
+            read,
+            touch,
+            release,
+            contact: false,
+        }
+    }
+}
+
+impl<R> ContactSensor for ForceSensor<R>
+where
+    R: FnMut() -> Option<u16>,
+{
+    fn contact(&mut self) -> Option<bool> {
+        let load = (self.read)()?;
+        if load >= self.touch {
+            self.contact = true;
+        } else if load < self.release {
+            self.contact = false;
+        }
+        Some(self.contact)
+    }
+}
+
+/// `BODY:GAIT:ADAPtive` settings.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DescentSettings {
+    /// Keep descending until contact instead of stopping at the planned
+    /// height.
+    pub adaptive: bool,
+    /// Furthest a foot goes below the planned height, in m.
+    pub max_depth: f32,
+    /// Descent speed below the planned height, in m/s.
+    pub rate: f32,
+}
+
+impl DescentSettings {
+    pub const MAX_DEPTH: f32 = 0.1;
+
+    pub const fn new() -> Self {
+        DescentSettings {
+            adaptive: false,
+            max_depth: 0.04,
+            rate: 0.05,
+        }
+    }
+}
+
+/// Touchdown of one swinging leg.
+///
+/// Follows the height planned by the gait. Once the plan reaches the ground
+/// an adaptive touchdown keeps lowering the foot until contact, up to the
+/// maximum depth, and the foot stays where contact was made. Contact early
+/// in the swing, on a step up, ends it there too. Without a working contact
+/// sensor the plan is followed as is.
+pub struct Touchdown {
+    depth: f32,
+    landed: Option<f32>,
+}
+
+impl Touchdown {
+    /// Start of a swing.
+    pub const fn new() -> Self {
+        Touchdown {
+            depth: 0.0,
+            landed: None,
+        }
+    }
+
+    /// Foot height `dt` seconds after the previous update.
+    ///
+    /// `planned` is the height the gait wants and `ground` the height the
+    /// swing ends at on flat ground.
+    pub fn update(
+        &mut self,
+        settings: &DescentSettings,
+        planned: f32,
+        ground: f32,
+        contact: Option<bool>,
+        dt: f32,
+    ) -> f32 {
+        if let Some(height) = self.landed {
+            return height;
+        }
+        let contact = match contact {
+            Some(contact) if settings.adaptive => contact,
+            _ => return planned,
+        };
+        let height = if planned <= ground {
+            self.depth = (self.depth + settings.rate * dt).min(settings.max_depth);
+            ground - self.depth
+        } else {
+            planned
+        };
+        if contact {
+            self.landed = Some(height);
+        }
+        height
+    }
+
+    /// Height contact was made at.
+    pub fn landed(&self) -> Option<f32> {
+        self.landed
+    }
+}
@@ -0,0 +1,100 @@
+use core::fmt::{self, Write};
+
+const MAGIC: u32 = 0xDEAD_C0DE;
+const MESSAGE_LEN: usize = 128;
+
+/// What caused the last reset.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u32)]
+pub enum CrashKind {
+    Panic = 1,
+    HardFault = 2,
+}
+
+/// Post mortem record of a panic or fault. The firmware keeps it in RAM that
+/// is not initialized by the runtime, so it survives the reset issued after
+/// the crash.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct CrashRecord {
+    magic: u32,
+    kind: u32,
+    message_len: u32,
+    message: [u8; MESSAGE_LEN],
+    /// Stacked `r0-r3, r12, lr, pc, xpsr` of a HardFault.
+    pub frame: [u32; 8],
+    pub cfsr: u32,
+    pub hfsr: u32,
+    pub mmfar: u32,
+    pub bfar: u32,
+}
+
+impl CrashRecord {
+    /// Start a new record in place, the previous one is lost.
+    pub fn begin(&mut self, kind: CrashKind) {
+        *self = CrashRecord {
+            magic: 0,
+            kind: kind as u32,
+            message_len: 0,
+            message: [0; MESSAGE_LEN],
+            frame: [0; 8],
+            cfsr: 0,
+            hfsr: 0,
+            mmfar: 0,
+            bfar: 0,
+        };
+    }
+
+    /// Mark the record complete.
+    pub fn commit(&mut self) {
+        self.magic = MAGIC;
+    }
+
+    pub fn invalidate(&mut self) {
+        self.magic = 0;
+    }
+
+    /// True for a complete record, false for whatever was in RAM at power on.
+    pub fn is_valid(&self) -> bool {
+        self.magic == MAGIC
+    }
+
+    pub fn kind(&self) -> CrashKind {
+        if self.kind == CrashKind::HardFault as u32 {
+            CrashKind::HardFault
+        } else {
+            CrashKind::Panic
+        }
+    }
+
+    pub fn message(&self) -> &[u8] {
+        let len = (self.message_len as usize).min(MESSAGE_LEN);
+        &self.message[..len]
+    }
+}
+
+/// Appends to the message, silently truncating. Bytes other than printable
+/// ASCII are stored as `?`, so the message can always be sent as string data.
+impl Write for CrashRecord {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = (self.message_len as usize).min(MESSAGE_LEN);
+        let n = s.len().min(MESSAGE_LEN - len);
+        for (stored, byte) in self.message[len..len + n].iter_mut().zip(s.bytes()) {
+            *stored = match byte {
+                b' '..=b'~' => byte,
+                _ => b'?',
+            };
+        }
+        self.message_len = (len + n) as u32;
+        Ok(())
+    }
+}
+
+/// Where the record of the last crash is kept.
+pub trait CrashLog {
+    /// Get the record left by the previous run, if any.
+    fn last(&self) -> Option<CrashRecord>;
+
+    /// Forget the record left by the previous run.
+    fn clear(&self);
+}
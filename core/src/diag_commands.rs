@@ -0,0 +1,128 @@
+use core::cell::RefCell;
+use core::convert::TryInto;
+use scpi::error::Result;
+use scpi::prelude::*;
+use scpi::qonly;
+
+use crate::pwm::PwmSettings;
+use crate::servo_bus::BusHealth;
+
+/// # `DIAGnostic:I2C:ERRors? <bank>`
+/// Query the I2C error counters of servo controller 1 or 2.
+///
+/// Returns `<errors>,<retries>,<recoveries>,<unreachable>`.
+///
+pub struct DiagI2cErrorsCommand<'a> {
+    health: &'a RefCell<[BusHealth]>,
+}
+
+impl<'a> DiagI2cErrorsCommand<'a> {
+    pub fn new(health: &'a RefCell<[BusHealth]>) -> Self {
+        Self { health }
+    }
+}
+
+impl<'a> Command for DiagI2cErrorsCommand<'a> {
+    qonly!();
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let health = self.health.borrow();
+        let index: usize =
+            args.next_data(false)?
+                .unwrap()
+                .numeric_range(1, health.len(), |_| {
+                    Err(ErrorCode::IllegalParameterValue.into())
+                })?
+                - 1;
+        let h = &health[index];
+        response
+            .data(h.errors)
+            .data(h.retries)
+            .data(h.recoveries)
+            .data(h.unreachable)
+            .finish()
+    }
+}
+
+/// # `DIAGnostic:PWM:FREQuency <Hz>`
+/// Set the servo update rate, 40 to 333 Hz. Pulse widths stay the same, the
+/// controllers run at the closest rate their prescaler allows.
+///
+/// # `DIAGnostic:PWM:FREQuency?`
+/// Query the servo update rate.
+///
+pub struct DiagPwmFreqCommand<'a> {
+    pwm: &'a RefCell<PwmSettings>,
+}
+
+impl<'a> DiagPwmFreqCommand<'a> {
+    pub fn new(pwm: &'a RefCell<PwmSettings>) -> Self {
+        Self { pwm }
+    }
+}
+
+impl<'a> Command for DiagPwmFreqCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let frequency = args.next_data(false)?.unwrap().numeric_range(
+            PwmSettings::FREQUENCY_MIN,
+            PwmSettings::FREQUENCY_MAX,
+            |_| Err(ErrorCode::IllegalParameterValue.into()),
+        )?;
+        self.pwm.borrow_mut().frequency = frequency;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(self.pwm.borrow().frequency).finish()
+    }
+}
+
+/// # `DIAGnostic:PWM:TRIM <factor>`
+/// Set the oscillator calibration, the actual over the nominal 25 MHz
+/// oscillator frequency of the controllers, 0.9 to 1.1.
+///
+/// To calibrate, command a pulse width, measure it and multiply the trim by
+/// the commanded over the measured width.
+///
+/// # `DIAGnostic:PWM:TRIM?`
+/// Query the oscillator calibration.
+///
+pub struct DiagPwmTrimCommand<'a> {
+    pwm: &'a RefCell<PwmSettings>,
+}
+
+impl<'a> DiagPwmTrimCommand<'a> {
+    pub fn new(pwm: &'a RefCell<PwmSettings>) -> Self {
+        Self { pwm }
+    }
+}
+
+impl<'a> Command for DiagPwmTrimCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let trim: f32 = args.next_data(false)?.unwrap().try_into()?;
+        if !(PwmSettings::TRIM_MIN..=PwmSettings::TRIM_MAX).contains(&trim) {
+            return Err(ErrorCode::DataOutOfRange.into());
+        }
+        self.pwm.borrow_mut().trim = trim;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(self.pwm.borrow().trim).finish()
+    }
+}
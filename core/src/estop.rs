@@ -0,0 +1,46 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::status::{self, STB_ESTOP};
+
+static LATCHED: AtomicBool = AtomicBool::new(false);
+static INPUT: AtomicBool = AtomicBool::new(false);
+/// Tripped since the last [take_trip].
+static TRIPPED: AtomicBool = AtomicBool::new(false);
+
+/// Latch the emergency stop.
+pub fn trip() {
+    LATCHED.store(true, Ordering::SeqCst);
+    TRIPPED.store(true, Ordering::SeqCst);
+    status::set_stb(STB_ESTOP);
+}
+
+/// True once after every trip, for the owner of the commanded state to
+/// cancel motion.
+pub fn take_trip() -> bool {
+    TRIPPED.swap(false, Ordering::SeqCst)
+}
+
+/// Update the state of the emergency stop input, trips if active.
+pub fn set_input(active: bool) {
+    INPUT.store(active, Ordering::SeqCst);
+    if active {
+        trip();
+    }
+}
+
+/// Release the latch.
+///
+/// Returns false and stays latched if the input is still active.
+pub fn reset() -> bool {
+    if INPUT.load(Ordering::SeqCst) {
+        false
+    } else {
+        LATCHED.store(false, Ordering::SeqCst);
+        status::clear_stb(STB_ESTOP);
+        true
+    }
+}
+
+pub fn is_latched() -> bool {
+    LATCHED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,86 @@
+use core::cell::RefCell;
+use nalgebra::{Rotation3, Translation3};
+
+use crate::estop;
+use crate::protocol::{Message, NackReason, SERVOS};
+use crate::servo_commands::ServoControl;
+
+/// Executes requests received over the binary transport, on the same state
+/// as the SCPI commands.
+pub struct FramedHandler<'a> {
+    servos: &'a RefCell<[ServoControl]>,
+    rotation: &'a RefCell<Rotation3<f32>>,
+    translation: &'a RefCell<Translation3<f32>>,
+}
+
+impl<'a> FramedHandler<'a> {
+    pub fn new(
+        servos: &'a RefCell<[ServoControl]>,
+        rotation: &'a RefCell<Rotation3<f32>>,
+        translation: &'a RefCell<Translation3<f32>>,
+    ) -> Self {
+        FramedHandler {
+            servos,
+            rotation,
+            translation,
+        }
+    }
+
+    /// Execute a request and return the reply.
+    pub fn handle(&self, message: Message) -> Message {
+        match message {
+            Message::ServoTargets(targets) => {
+                if estop::is_latched() {
+                    return Message::Nack(NackReason::NotAllowed);
+                }
+                let valid = targets
+                    .iter()
+                    .all(|t| (ServoControl::PWIDTH_MIN..=ServoControl::PWIDTH_MAX).contains(t));
+                if !valid {
+                    return Message::Nack(NackReason::OutOfRange);
+                }
+                let mut servos = self.servos.borrow_mut();
+                for (servo, t) in servos.iter_mut().zip(targets.iter()) {
+                    servo.pulse_width = *t;
+                }
+                Message::Ack
+            }
+            Message::BodyPose {
+                rotation,
+                translation,
+            } => {
+                if !rotation
+                    .iter()
+                    .chain(translation.iter())
+                    .all(|v| v.is_finite())
+                {
+                    return Message::Nack(NackReason::OutOfRange);
+                }
+                self.rotation.replace(Rotation3::from_euler_angles(
+                    rotation[0],
+                    rotation[1],
+                    rotation[2],
+                ));
+                self.translation.replace(Translation3::new(
+                    translation[0],
+                    translation[1],
+                    translation[2],
+                ));
+                Message::Ack
+            }
+            Message::ReadTelemetry => {
+                let mut targets = [0u16; SERVOS];
+                for (t, servo) in targets.iter_mut().zip(self.servos.borrow().iter()) {
+                    *t = servo.pulse_width;
+                }
+                Message::Telemetry {
+                    targets,
+                    estop: estop::is_latched(),
+                }
+            }
+            Message::Scpi => Message::Ack,
+            // Replies are never sent to us
+            _ => Message::Nack(NackReason::UnknownKind),
+        }
+    }
+}
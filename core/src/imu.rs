@@ -0,0 +1,148 @@
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use nalgebra::Vector3;
+
+use crate::attitude::{Attitude, ImuSample, Mahony};
+use crate::log;
+
+/// Address with AD0 low.
+pub const MPU6050_ADDRESS: u8 = 0x68;
+
+const SMPLRT_DIV: u8 = 0x19;
+const CONFIG: u8 = 0x1A;
+const GYRO_CONFIG: u8 = 0x1B;
+const ACCEL_CONFIG: u8 = 0x1C;
+const ACCEL_XOUT_H: u8 = 0x3B;
+const PWR_MGMT_1: u8 = 0x6B;
+const WHO_AM_I: u8 = 0x75;
+const WHO_AM_I_VALUE: u8 = 0x68;
+
+/// 1 kHz sample rate with a 44 Hz low pass, filters out servo vibration.
+const DLPF_44HZ: u8 = 3;
+const GYRO_500DPS: u8 = 1 << 3;
+const ACCEL_4G: u8 = 1 << 3;
+/// Wake up, clocked from the X gyro PLL.
+const CLKSEL_PLL_X: u8 = 1;
+
+const ACCEL_LSB_PER_G: f32 = 8192.0;
+const GYRO_LSB_PER_RAD_S: f32 = 65.5 * 180.0 / core::f32::consts::PI;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Error<E> {
+    Bus(E),
+    /// Something else answered at the address, `WHO_AM_I` is given.
+    WrongDevice(u8),
+}
+
+/// InvenSense MPU-6050, mounted with its axes along the body axes.
+pub struct Mpu6050<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C, E> Mpu6050<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Mpu6050 { i2c, address }
+    }
+
+    /// Check the device and configure ranges and filtering.
+    pub fn init(&mut self) -> Result<(), Error<E>> {
+        let mut id = [0u8];
+        self.i2c
+            .write_read(self.address, &[WHO_AM_I], &mut id)
+            .map_err(Error::Bus)?;
+        if id[0] != WHO_AM_I_VALUE {
+            return Err(Error::WrongDevice(id[0]));
+        }
+        for (register, value) in [
+            (PWR_MGMT_1, CLKSEL_PLL_X),
+            (SMPLRT_DIV, 0),
+            (CONFIG, DLPF_44HZ),
+            (GYRO_CONFIG, GYRO_500DPS),
+            (ACCEL_CONFIG, ACCEL_4G),
+        ]
+        .iter()
+        {
+            self.i2c
+                .write(self.address, &[*register, *value])
+                .map_err(Error::Bus)?;
+        }
+        Ok(())
+    }
+
+    /// Read accelerometer and gyro.
+    pub fn read(&mut self) -> Result<ImuSample, Error<E>> {
+        let mut data = [0u8; 14];
+        self.i2c
+            .write_read(self.address, &[ACCEL_XOUT_H], &mut data)
+            .map_err(Error::Bus)?;
+        let value = |i: usize| i16::from_be_bytes([data[2 * i], data[2 * i + 1]]) as f32;
+        // Temperature sits between accelerometer and gyro
+        Ok(ImuSample {
+            accel: Vector3::new(value(0), value(1), value(2)) / ACCEL_LSB_PER_G,
+            gyro: Vector3::new(value(4), value(5), value(6)) / GYRO_LSB_PER_RAD_S,
+        })
+    }
+}
+
+/// Body attitude from an [Mpu6050].
+///
+/// The IMU is (re)initialized lazily on the first update and after every
+/// failure, the filter starts over after a failure.
+pub struct AttitudeEstimator<I2C> {
+    imu: Mpu6050<I2C>,
+    pub filter: Mahony,
+    needs_init: bool,
+    /// Failed reads or initializations.
+    pub errors: u32,
+}
+
+impl<I2C, E> AttitudeEstimator<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    pub fn new(imu: Mpu6050<I2C>) -> Self {
+        AttitudeEstimator {
+            imu,
+            filter: Mahony::new(Mahony::KP, Mahony::KI),
+            needs_init: true,
+            errors: 0,
+        }
+    }
+
+    /// Read the IMU, `dt` seconds after the previous update.
+    ///
+    /// Returns the new estimate, None while the IMU can not be read.
+    pub fn update(&mut self, dt: f32) -> Option<Attitude> {
+        let result = if self.needs_init {
+            self.imu.init().map(|_| None)
+        } else {
+            self.imu.read().map(Some)
+        };
+        match result {
+            Ok(Some(sample)) => {
+                self.filter.update(&sample, dt);
+                self.filter.attitude()
+            }
+            Ok(None) => {
+                self.needs_init = false;
+                None
+            }
+            Err(err) => {
+                // Not on every retry while the IMU stays absent
+                if !self.needs_init || self.errors == 0 {
+                    match err {
+                        Error::WrongDevice(id) => log::error("Unknown IMU", id as u32),
+                        Error::Bus(_) => log::warning("IMU not responding", self.errors),
+                    }
+                }
+                self.errors += 1;
+                self.needs_init = true;
+                self.filter.reset();
+                None
+            }
+        }
+    }
+}
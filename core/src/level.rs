@@ -0,0 +1,123 @@
+use crate::attitude::Attitude;
+
+/// Gains and limits of the [Leveller].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LevelConfig {
+    /// Proportional gain.
+    pub kp: f32,
+    /// Integral gain in 1/s, holds the correction for a constant slope.
+    pub ki: f32,
+    /// Largest correction in radians, per axis. Stands in for the edge of
+    /// the leg workspace, a steeper slope is only partly compensated.
+    pub max_angle: f32,
+    /// Fastest change of the correction in rad/s.
+    pub max_rate: f32,
+}
+
+impl LevelConfig {
+    pub const KP_MAX: f32 = 10.0;
+    pub const KI_MAX: f32 = 10.0;
+    pub const MAX_ANGLE: f32 = 0.5;
+    pub const MAX_RATE: f32 = 2.0;
+
+    pub const fn new() -> Self {
+        LevelConfig {
+            kp: 0.2,
+            ki: 1.0,
+            max_angle: 0.35,
+            max_rate: 0.5,
+        }
+    }
+}
+
+/// `BODY:LEVel` state and configuration.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LevelSettings {
+    pub enabled: bool,
+    pub config: LevelConfig,
+}
+
+impl LevelSettings {
+    pub const fn new() -> Self {
+        LevelSettings {
+            enabled: false,
+            config: LevelConfig::new(),
+        }
+    }
+}
+
+/// Closed loop body levelling.
+///
+/// Drives the measured attitude towards the commanded roll and pitch, so the
+/// commanded rotation becomes relative to gravity instead of to the ground.
+/// The correction is added to the commanded rotation, for the legs to
+/// realize through their foot heights.
+///
+/// While disabled or without a measurement the correction returns to zero at
+/// the rate limit, so switching never makes the body jump.
+pub struct Leveller {
+    integral: [f32; 2],
+    correction: [f32; 2],
+}
+
+impl Leveller {
+    pub const fn new() -> Self {
+        Leveller {
+            integral: [0.0; 2],
+            correction: [0.0; 2],
+        }
+    }
+
+    /// Run the controller `dt` seconds after the previous update, returns
+    /// the correction.
+    pub fn update(
+        &mut self,
+        settings: &LevelSettings,
+        commanded: &Attitude,
+        measured: Option<Attitude>,
+        dt: f32,
+    ) -> Attitude {
+        let config = &settings.config;
+        let target = match measured {
+            Some(measured) if settings.enabled => {
+                let error = [
+                    commanded.roll - measured.roll,
+                    commanded.pitch - measured.pitch,
+                ];
+                let mut target = [0.0; 2];
+                for axis in 0..2 {
+                    let integral = self.integral[axis] + config.ki * error[axis] * dt;
+                    let output = config.kp * error[axis] + integral;
+                    target[axis] = clamp(output, config.max_angle);
+                    // Stop integrating into the limit
+                    if target[axis] == output || output.signum() != error[axis].signum() {
+                        self.integral[axis] = clamp(integral, config.max_angle);
+                    }
+                }
+                target
+            }
+            _ => {
+                self.integral = [0.0; 2];
+                [0.0; 2]
+            }
+        };
+
+        let step = config.max_rate * dt;
+        for (correction, target) in self.correction.iter_mut().zip(target.iter()) {
+            *correction += clamp(target - *correction, step);
+        }
+        self.correction()
+    }
+
+    pub fn correction(&self) -> Attitude {
+        Attitude {
+            roll: self.correction[0],
+            pitch: self.correction[1],
+        }
+    }
+}
+
+/// Limit `value` to ±`limit`.
+fn clamp(value: f32, limit: f32) -> f32 {
+    value.clamp(-limit, limit)
+}
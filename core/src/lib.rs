@@ -0,0 +1,40 @@
+//! Hardware independent parts of the ash-carrier firmware: the SCPI
+//! commands, servo model, transports and the protocols spoken on them.
+//!
+//! Hardware is reached through `embedded-hal` traits, so everything here
+//! also builds and is tested on the host.
+#![no_std]
+// Constructors are const so the state can live in statics
+#![allow(clippy::new_without_default)]
+
+pub mod attitude;
+pub mod battery;
+pub mod body;
+pub mod body_commands;
+pub mod contact;
+pub mod crash;
+pub mod diag_commands;
+pub mod estop;
+pub mod framed;
+pub mod imu;
+pub mod level;
+pub mod linereader;
+pub mod log;
+pub mod measure_commands;
+pub mod overload;
+pub mod port;
+pub mod power;
+pub mod protocol;
+pub mod pwm;
+pub mod ros_bridge;
+pub mod rosserial;
+pub mod serial;
+pub mod servo_bus;
+pub mod servo_commands;
+pub mod settings;
+pub mod status;
+pub mod system_commands;
+pub mod telemetry;
+pub mod tree;
+
+pub use scpi;
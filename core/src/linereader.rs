@@ -0,0 +1,129 @@
+use nb::{Error, Result};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineError {
+    /// Line did not fit in the buffer, the rest of it is discarded.
+    Overflow,
+}
+
+/// Where in the line the reader is, block data is passed through untouched.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Scan {
+    Text,
+    /// Inside a string, terminated by the same quote.
+    Quoted(u8),
+    /// After a `#`, a nonzero digit starts a definite length block header.
+    Hash,
+    /// Remaining length digits and the length so far.
+    Length(u8, usize),
+    /// Remaining block data bytes.
+    Block(usize),
+}
+
+/// Splits a byte stream into lines terminated by CR, LF or CRLF.
+///
+/// A line longer than `N` bytes is reported once as [LineError::Overflow],
+/// everything up to the next terminator is then discarded so the reader
+/// resynchronizes on the following line.
+///
+/// The data of IEEE 488.2 definite length blocks (`#<n><length><data>`) may
+/// contain terminators and is kept as is, unless the block is longer than
+/// `N`.
+pub struct LineReader<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+    /// Previous byte was a CR, a LF directly after it ends the same line.
+    after_cr: bool,
+    /// Dropping the remains of an overlong line.
+    discarding: bool,
+    scan: Scan,
+}
+
+impl<const N: usize> LineReader<N> {
+    pub const fn new() -> Self {
+        LineReader {
+            buffer: [0; N],
+            len: 0,
+            after_cr: false,
+            discarding: false,
+            scan: Scan::Text,
+        }
+    }
+
+    /// Push a byte, returns the line without terminator once complete.
+    ///
+    /// The line must be consumed and [LineReader::clear]ed before pushing
+    /// more bytes.
+    pub fn push(&mut self, byte: u8) -> Result<&[u8], LineError> {
+        if self.block_data(byte) {
+            self.after_cr = false;
+            return self.store(byte);
+        }
+        let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+        match byte {
+            b'\n' if after_cr => Err(Error::WouldBlock),
+            b'\r' | b'\n' if self.discarding => {
+                self.discarding = false;
+                self.len = 0;
+                self.scan = Scan::Text;
+                Err(Error::WouldBlock)
+            }
+            b'\r' | b'\n' => {
+                self.scan = Scan::Text;
+                Ok(&self.buffer[..self.len])
+            }
+            _ => self.store(byte),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn store(&mut self, byte: u8) -> Result<&[u8], LineError> {
+        if self.discarding {
+            Err(Error::WouldBlock)
+        } else if self.len == N {
+            // A terminator ends the discard, even inside block data
+            self.discarding = true;
+            self.scan = Scan::Text;
+            self.len = 0;
+            Err(Error::Other(LineError::Overflow))
+        } else {
+            self.buffer[self.len] = byte;
+            self.len += 1;
+            Err(Error::WouldBlock)
+        }
+    }
+
+    /// Track block headers, true if `byte` is block data.
+    fn block_data(&mut self, byte: u8) -> bool {
+        let (scan, data) = match self.scan {
+            Scan::Block(n) if n > 1 => (Scan::Block(n - 1), true),
+            Scan::Block(_) => (Scan::Text, true),
+            Scan::Quoted(quote) if byte == quote => (Scan::Text, false),
+            Scan::Quoted(quote) => (Scan::Quoted(quote), false),
+            Scan::Hash => match byte {
+                b'1'..=b'9' => (Scan::Length(byte - b'0', 0), false),
+                _ => (Scan::Text, false),
+            },
+            Scan::Length(digits, len) if byte.is_ascii_digit() => {
+                let len = len.saturating_mul(10).saturating_add((byte - b'0') as usize);
+                match (digits, len) {
+                    // Can't fit, the line overflows and terminators end it
+                    (1, len) if len == 0 || len > N => (Scan::Text, false),
+                    (1, len) => (Scan::Block(len), false),
+                    (digits, len) => (Scan::Length(digits - 1, len), false),
+                }
+            }
+            Scan::Length(..) => (Scan::Text, false),
+            Scan::Text => match byte {
+                b'"' | b'\'' => (Scan::Quoted(byte), false),
+                b'#' => (Scan::Hash, false),
+                _ => (Scan::Text, false),
+            },
+        };
+        self.scan = scan;
+        data
+    }
+}
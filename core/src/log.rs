@@ -0,0 +1,85 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+use heapless::mpmc::Q32;
+
+/// Severity of a log entry.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub enum Level {
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    pub fn name(self) -> &'static [u8] {
+        match self {
+            Level::Debug => b"DEBUG",
+            Level::Info => b"INFO",
+            Level::Warning => b"WARNING",
+            Level::Error => b"ERROR",
+        }
+    }
+}
+
+/// A log entry, a static message and an optional value such as an index or
+/// error code.
+#[derive(Copy, Clone, Debug)]
+pub struct Entry {
+    pub level: Level,
+    pub message: &'static str,
+    pub value: u32,
+}
+
+/// Lock-free ring buffer, safe to write to from interrupts.
+static LOG: Q32<Entry> = Q32::new();
+/// Number of entries overwritten before being read.
+static DROPPED: AtomicU32 = AtomicU32::new(0);
+
+/// Append an entry, overwriting the oldest one if full.
+pub fn log(level: Level, message: &'static str, value: u32) {
+    #[cfg(feature = "semihosting")]
+    cortex_m_semihosting::heprintln!("[{:?}] {} ({})", level, message, value).ok();
+
+    let mut entry = Entry {
+        level,
+        message,
+        value,
+    };
+    while let Err(e) = LOG.enqueue(entry) {
+        if LOG.dequeue().is_some() {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+        entry = e;
+    }
+}
+
+pub fn debug(message: &'static str, value: u32) {
+    log(Level::Debug, message, value)
+}
+
+pub fn info(message: &'static str, value: u32) {
+    log(Level::Info, message, value)
+}
+
+pub fn warning(message: &'static str, value: u32) {
+    log(Level::Warning, message, value)
+}
+
+pub fn error(message: &'static str, value: u32) {
+    log(Level::Error, message, value)
+}
+
+/// Remove and return the oldest entry.
+pub fn next() -> Option<Entry> {
+    LOG.dequeue()
+}
+
+/// Remove all entries.
+pub fn clear() {
+    while LOG.dequeue().is_some() {}
+    DROPPED.store(0, Ordering::Relaxed);
+}
+
+pub fn dropped() -> u32 {
+    DROPPED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,140 @@
+use core::cell::RefCell;
+use scpi::error::Result;
+use scpi::prelude::*;
+use scpi::qonly;
+
+use crate::attitude::Attitude;
+use crate::overload::ServoLoad;
+use crate::tree::LEGS;
+
+/// # `MEASure:ATTitude?`
+/// Query the measured body attitude.
+///
+/// Returns `<roll>,<pitch>` in radians. Fails with `-241,"Hardware missing"`
+/// while no IMU is responding.
+///
+pub struct MeasAttCommand<'a> {
+    attitude: &'a RefCell<Option<Attitude>>,
+}
+
+impl<'a> MeasAttCommand<'a> {
+    pub fn new(attitude: &'a RefCell<Option<Attitude>>) -> Self {
+        Self { attitude }
+    }
+}
+
+impl<'a> Command for MeasAttCommand<'a> {
+    qonly!();
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let attitude = self
+            .attitude
+            .borrow()
+            .ok_or_else(|| Error::from(ErrorCode::HardwareMissing))?;
+        response.data(attitude.roll).data(attitude.pitch).finish()
+    }
+}
+
+/// # `MEASure:LEG:CONTact?`
+/// Query foot contact of every leg.
+///
+/// Returns one value per leg, `1` in contact, `0` lifted and `-1` without a
+/// working contact sensor, see `SYSTem:CONTact:FITTed`.
+///
+pub struct MeasLegContCommand<'a> {
+    contacts: &'a RefCell<[Option<bool>; LEGS]>,
+}
+
+impl<'a> MeasLegContCommand<'a> {
+    pub fn new(contacts: &'a RefCell<[Option<bool>; LEGS]>) -> Self {
+        Self { contacts }
+    }
+}
+
+impl<'a> Command for MeasLegContCommand<'a> {
+    qonly!();
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        for contact in self.contacts.borrow().iter() {
+            let value: i8 = match contact {
+                Some(true) => 1,
+                Some(false) => 0,
+                None => -1,
+            };
+            response.data(value);
+        }
+        response.finish()
+    }
+}
+
+/// # `MEASure:SERVos:CURRent?`
+/// Query the servo rail current of each bank in amperes.
+///
+pub struct MeasServCurrCommand<'a> {
+    load: &'a RefCell<ServoLoad>,
+}
+
+impl<'a> MeasServCurrCommand<'a> {
+    pub fn new(load: &'a RefCell<ServoLoad>) -> Self {
+        Self { load }
+    }
+}
+
+impl<'a> Command for MeasServCurrCommand<'a> {
+    qonly!();
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        for milliamps in self.load.borrow().milliamps.iter() {
+            response.data(*milliamps as f32 / 1000.0);
+        }
+        response.finish()
+    }
+}
+
+/// # `MEASure:TEMPerature?`
+/// Query the board temperature in °C.
+///
+/// Fails with `-241,"Hardware missing"` until the sensor has been read.
+///
+pub struct MeasTempCommand<'a> {
+    load: &'a RefCell<ServoLoad>,
+}
+
+impl<'a> MeasTempCommand<'a> {
+    pub fn new(load: &'a RefCell<ServoLoad>) -> Self {
+        Self { load }
+    }
+}
+
+impl<'a> Command for MeasTempCommand<'a> {
+    qonly!();
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let celsius = self
+            .load
+            .borrow()
+            .celsius
+            .ok_or_else(|| Error::from(ErrorCode::HardwareMissing))?;
+        response.data(celsius).finish()
+    }
+}
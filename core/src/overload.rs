@@ -0,0 +1,276 @@
+use core::sync::atomic::{AtomicU8, Ordering};
+use embedded_hal::adc::{Channel, OneShot};
+use scpi::prelude::*;
+
+use crate::log;
+use crate::tree::{BANKS, LEGS, SERVOS};
+
+/// ADC reference and full scale reading.
+const VREF_MV: u32 = 3300;
+const FULL_SCALE: u32 = 4095;
+/// Shunt amplifier output, INA180A1 (20 V/V) on a 5 mΩ shunt.
+const MV_PER_AMP: u32 = 100;
+/// STM32F4 internal sensor, typical values from the datasheet.
+const TEMP_V25_MV: f32 = 760.0;
+const TEMP_MV_PER_C: f32 = 2.5;
+
+/// Questionable status bits, as in SCPI `STATus:QUEStionable`.
+pub const QUES_CURRENT: u16 = 1 << 1;
+pub const QUES_TEMPERATURE: u16 = 1 << 4;
+
+/// Tripped banks, bit per bank, and [TRIP_TEMPERATURE].
+static TRIPPED: AtomicU8 = AtomicU8::new(0);
+const TRIP_TEMPERATURE: u8 = 0x80;
+/// Tripped legs, bit per leg.
+static TRIPPED_LEGS: AtomicU8 = AtomicU8::new(0);
+/// Leg switched off by a running [Protection] probe, bit per leg.
+static PROBING: AtomicU8 = AtomicU8::new(0);
+
+/// Servo rail current of a bank through a shunt amplifier on an ADC input.
+pub struct CurrentSense<PIN> {
+    pin: PIN,
+    milliamps: u32,
+}
+
+impl<PIN> CurrentSense<PIN> {
+    pub fn new(pin: PIN) -> Self {
+        CurrentSense { pin, milliamps: 0 }
+    }
+
+    /// Take a sample, the result is low pass filtered. Failed conversions
+    /// are skipped.
+    pub fn sample<ADC, A>(&mut self, adc: &mut A)
+    where
+        PIN: Channel<ADC>,
+        A: OneShot<ADC, u16, PIN>,
+    {
+        let sample = match adc.read(&mut self.pin) {
+            Ok(sample) => sample as u32,
+            Err(_) => return,
+        };
+        let milliamps = sample.min(FULL_SCALE) * VREF_MV / FULL_SCALE * 1000 / MV_PER_AMP;
+        self.milliamps = (self.milliamps * 3 + milliamps) / 4;
+    }
+
+    pub fn milliamps(&self) -> u32 {
+        self.milliamps
+    }
+}
+
+/// Board temperature from the internal sensor of the MCU.
+pub struct Thermometer<PIN> {
+    pin: PIN,
+    celsius: Option<f32>,
+}
+
+impl<PIN> Thermometer<PIN> {
+    pub fn new(pin: PIN) -> Self {
+        Thermometer { pin, celsius: None }
+    }
+
+    /// Take a sample, the result is low pass filtered. Failed conversions
+    /// are skipped.
+    pub fn sample<ADC, A>(&mut self, adc: &mut A)
+    where
+        PIN: Channel<ADC>,
+        A: OneShot<ADC, u16, PIN>,
+    {
+        let sample = match adc.read(&mut self.pin) {
+            Ok(sample) => sample.min(FULL_SCALE as u16) as f32,
+            Err(_) => return,
+        };
+        let millivolts = sample * VREF_MV as f32 / FULL_SCALE as f32;
+        let celsius = (millivolts - TEMP_V25_MV) / TEMP_MV_PER_C + 25.0;
+        self.celsius = Some(match self.celsius {
+            Some(previous) => (previous * 7.0 + celsius) / 8.0,
+            None => celsius,
+        });
+    }
+
+    /// None until the first sample.
+    pub fn celsius(&self) -> Option<f32> {
+        self.celsius
+    }
+}
+
+/// Latest current and temperature readings.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ServoLoad {
+    pub milliamps: [u32; BANKS],
+    pub celsius: Option<f32>,
+}
+
+/// `SYSTem:SERVos:PROTection` limits.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ProtectionLimits {
+    /// Rail current per bank.
+    pub milliamps: u32,
+    pub celsius: f32,
+}
+
+impl ProtectionLimits {
+    pub const MILLIAMPS_MAX: u32 = 30_000;
+    pub const CELSIUS_MAX: f32 = 100.0;
+
+    pub const fn new() -> Self {
+        ProtectionLimits {
+            milliamps: 15_000,
+            celsius: 70.0,
+        }
+    }
+}
+
+/// Trips legs of banks drawing more than the limit for longer than
+/// [Protection::TRIP_MS], a jammed leg stalls its servos, and every bank when
+/// the board overheats.
+///
+/// Current is only measured per bank and every leg has servos on both banks,
+/// so the leg drawing it is found by switching the legs off one at a time for
+/// [Protection::PROBE_MS] until the current drops. The leg that brings it
+/// down trips, the others carry on. If none does, e.g. with several legs
+/// jammed, the whole bank trips.
+///
+/// Tripped legs and banks are held fully off until [clear].
+pub struct Protection {
+    over_ms: [u32; BANKS],
+    probe: Option<Probe>,
+}
+
+/// A leg switched off to see if the overload of `bank` goes away.
+#[derive(Copy, Clone)]
+struct Probe {
+    bank: usize,
+    leg: usize,
+    ms: u32,
+}
+
+impl Protection {
+    /// Long enough to ride out servos starting to move.
+    pub const TRIP_MS: u32 = 500;
+    /// Long enough for the filtered current to settle.
+    pub const PROBE_MS: u32 = 100;
+
+    pub const fn new() -> Self {
+        Protection {
+            over_ms: [0; BANKS],
+            probe: None,
+        }
+    }
+
+    /// Check `load`, measured `dt_ms` after the previous check.
+    pub fn check(&mut self, limits: &ProtectionLimits, load: &ServoLoad, dt_ms: u32) {
+        if let Some(probe) = self.probe.as_mut() {
+            probe.ms = probe.ms.saturating_add(dt_ms);
+            if probe.ms >= Self::PROBE_MS {
+                let bank = probe.bank;
+                if load.milliamps[bank] <= limits.milliamps {
+                    TRIPPED_LEGS.fetch_or(1 << probe.leg, Ordering::SeqCst);
+                    log::error("Servo overload on leg", probe.leg as u32 + 1);
+                    self.over_ms[bank] = 0;
+                    self.probe = None;
+                } else if let Some(leg) = next_leg(probe.leg + 1) {
+                    probe.leg = leg;
+                    probe.ms = 0;
+                } else {
+                    if trip(1 << bank) {
+                        log::error("Servo overload", bank as u32 + 1);
+                    }
+                    self.probe = None;
+                }
+            }
+        }
+        for (bank, (over_ms, milliamps)) in self
+            .over_ms
+            .iter_mut()
+            .zip(load.milliamps.iter())
+            .enumerate()
+        {
+            if *milliamps <= limits.milliamps {
+                *over_ms = 0;
+                continue;
+            }
+            *over_ms = over_ms.saturating_add(dt_ms);
+            if *over_ms >= Self::TRIP_MS && self.probe.is_none() && !tripped()[bank] {
+                self.probe = match next_leg(0) {
+                    Some(leg) => Some(Probe { bank, leg, ms: 0 }),
+                    None => {
+                        if trip(1 << bank) {
+                            log::error("Servo overload", bank as u32 + 1);
+                        }
+                        None
+                    }
+                };
+            }
+        }
+        let probing = self.probe.map_or(0, |probe| 1 << probe.leg);
+        PROBING.store(probing, Ordering::SeqCst);
+        if let Some(celsius) = load.celsius {
+            if celsius > limits.celsius && trip(TRIP_TEMPERATURE) {
+                log::error("Board overheated", celsius as u32);
+            }
+        }
+    }
+}
+
+/// First leg from `leg` on that is not tripped yet.
+fn next_leg(leg: usize) -> Option<usize> {
+    let tripped = TRIPPED_LEGS.load(Ordering::SeqCst);
+    (leg..LEGS).find(|leg| tripped & 1 << leg == 0)
+}
+
+/// Returns true if not already tripped.
+fn trip(bits: u8) -> bool {
+    TRIPPED.fetch_or(bits, Ordering::SeqCst) & bits != bits
+}
+
+/// Banks held off.
+pub fn tripped() -> [bool; BANKS] {
+    let bits = TRIPPED.load(Ordering::SeqCst);
+    let mut tripped = [false; BANKS];
+    for (bank, tripped) in tripped.iter_mut().enumerate() {
+        *tripped = bits & (TRIP_TEMPERATURE | 1 << bank) != 0;
+    }
+    tripped
+}
+
+/// Legs held off.
+pub fn tripped_legs() -> [bool; LEGS] {
+    let bits = TRIPPED_LEGS.load(Ordering::SeqCst);
+    let mut tripped = [false; LEGS];
+    for (leg, tripped) in tripped.iter_mut().enumerate() {
+        *tripped = bits & 1 << leg != 0;
+    }
+    tripped
+}
+
+/// Switch off the servos of tripped legs and of the leg being probed.
+pub fn hold_off_legs(targets: &mut [u16; SERVOS]) {
+    let off = TRIPPED_LEGS.load(Ordering::SeqCst) | PROBING.load(Ordering::SeqCst);
+    for (leg, servos) in targets.chunks_mut(SERVOS / LEGS).enumerate() {
+        if off & 1 << leg != 0 {
+            servos.iter_mut().for_each(|target| *target = 0);
+        }
+    }
+}
+
+/// Release tripped legs and banks.
+pub fn clear() {
+    TRIPPED.store(0, Ordering::SeqCst);
+    TRIPPED_LEGS.store(0, Ordering::SeqCst);
+}
+
+/// Show trips in the questionable status of `context`.
+pub fn report(context: &mut Context) {
+    let bits = TRIPPED.load(Ordering::SeqCst);
+    let questionable = &mut context.questionable;
+    if bits & TRIP_TEMPERATURE != 0 {
+        questionable.set_condition_bits(QUES_TEMPERATURE);
+    } else {
+        questionable.clear_condition_bits(QUES_TEMPERATURE);
+    }
+    if bits & !TRIP_TEMPERATURE != 0 || TRIPPED_LEGS.load(Ordering::SeqCst) != 0 {
+        questionable.set_condition_bits(QUES_CURRENT);
+    } else {
+        questionable.clear_condition_bits(QUES_CURRENT);
+    }
+}
@@ -0,0 +1,218 @@
+use arrayvec::ArrayString;
+use core::cell::RefCell;
+use core::fmt::Write;
+use scpi::prelude::*;
+use scpi::response::{ArrayVecFormatter, Formatter};
+
+use crate::framed::FramedHandler;
+use crate::linereader::{LineError, LineReader};
+use crate::protocol::{FrameDecoder, Message, Packet, MAX_FRAME};
+use crate::ros_bridge::{self, RosBridge, RosNode};
+use crate::telemetry::{self, CsvRecord, Format, Scheduler, Snapshot, TelemetryConfig};
+
+/// Transport of an interface.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Protocol {
+    #[default]
+    Scpi,
+    /// Binary packets, see [crate::protocol].
+    Framed,
+    /// rosserial, see [crate::ros_bridge].
+    Ros,
+}
+
+/// Settings of an interface, shared with the commands while one of its
+/// lines executes.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Session {
+    /// Reply to failing lines with the error, see `SYSTem:COMMunicate:VERBose`.
+    pub verbose: bool,
+    /// See `SYSTem:COMMunicate:PROTocol`.
+    pub protocol: Protocol,
+    /// See `SYSTem:TELemetry`.
+    pub telemetry: TelemetryConfig,
+}
+
+/// SCPI state of one interface.
+pub struct ScpiPort<const N: usize> {
+    reader: LineReader<N>,
+    formatter: ArrayVecFormatter<[u8; 256]>,
+    frames: FrameDecoder,
+    ros: RosNode,
+    session: Session,
+    scheduler: Scheduler,
+    chars_per_second: Option<u32>,
+    dropped: u32,
+    dropping: bool,
+}
+
+impl<const N: usize> ScpiPort<N> {
+    pub fn new() -> Self {
+        ScpiPort {
+            reader: LineReader::new(),
+            formatter: ArrayVecFormatter::new(),
+            frames: FrameDecoder::new(),
+            ros: RosNode::new(),
+            session: Session::default(),
+            scheduler: Scheduler::default(),
+            chars_per_second: None,
+            dropped: 0,
+            dropping: false,
+        }
+    }
+
+    /// Limit telemetry to what a serial line carrying `chars_per_second`
+    /// can send, `None` for interfaces without such a limit.
+    pub fn set_line_rate(&mut self, chars_per_second: Option<u32>) {
+        self.chars_per_second = chars_per_second;
+    }
+
+    /// Number of telemetry records `write` had no room for.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Push a received byte, execute complete lines or frames and pass any
+    /// response to `write`.
+    ///
+    /// SCPI errors go to the error queue of `context`. `session` holds the
+    /// settings of this port while a line executes.
+    #[allow(clippy::too_many_arguments)]
+    pub fn push<W>(
+        &mut self,
+        c: u8,
+        context: &mut Context,
+        session: &RefCell<Session>,
+        handler: &FramedHandler,
+        bridge: &RosBridge,
+        now_ms: u32,
+        mut write: W,
+    ) where
+        W: FnMut(&[u8]),
+    {
+        if self.session.protocol == Protocol::Ros {
+            if !self.ros.push(c, bridge, now_ms, write) {
+                self.session.protocol = Protocol::Scpi;
+            }
+            return;
+        }
+
+        if self.session.protocol == Protocol::Framed {
+            if let Some(result) = self.frames.push(c) {
+                let reply = match result {
+                    Ok(packet) => {
+                        if packet.message == Message::Scpi {
+                            self.session.protocol = Protocol::Scpi;
+                        }
+                        Packet::new(packet.seq, handler.handle(packet.message))
+                    }
+                    Err(err) => Packet::new(err.seq(), Message::Nack(err.reason())),
+                };
+                let mut frame = [0u8; MAX_FRAME];
+                let n = reply.encode(&mut frame);
+                write(&frame[..n]);
+            }
+            return;
+        }
+
+        // Move read bytes into line buffer and execute any lines
+        match self.reader.push(c) {
+            Ok(line) => {
+                session.replace(self.session);
+                let result = context.run(line, &mut self.formatter);
+                let protocol = self.session.protocol;
+                self.session = *session.borrow();
+                if self.session.protocol != protocol {
+                    self.frames = FrameDecoder::new();
+                    self.ros = RosNode::new();
+                }
+                match result {
+                    Ok(()) => {
+                        let response = self.formatter.as_slice();
+                        if !response.is_empty() {
+                            write(response);
+                        }
+                    }
+                    // Already queued by the context
+                    Err(err) => {
+                        if self.session.verbose {
+                            let line = error_line(&err);
+                            write(line.as_bytes());
+                        }
+                    }
+                }
+                // Clear line buffer
+                self.reader.clear();
+            }
+            Err(nb::Error::Other(LineError::Overflow)) => {
+                let err: Error = ErrorCode::InputBufferOverrun.into();
+                let line = error_line(&err);
+                context.push_error(err);
+                if self.session.verbose {
+                    write(line.as_bytes());
+                }
+            }
+            Err(nb::Error::WouldBlock) => {}
+        }
+    }
+
+    /// Pass a telemetry record to `write` if one is due.
+    ///
+    /// `write` returns false if it had no room for the record. Returns true
+    /// for the first record dropped after one was written, so a full queue
+    /// can be reported once rather than for every record.
+    ///
+    /// With rosserial the topics are published instead, at the telemetry
+    /// rate or [ros_bridge::RATE_DEFAULT] if telemetry is off.
+    pub fn telemetry<W>(&mut self, snapshot: &Snapshot, mut write: W) -> bool
+    where
+        W: FnMut(&[u8]) -> bool,
+    {
+        let mut config = self.session.telemetry;
+        if self.session.protocol == Protocol::Ros && config.rate == 0 {
+            config.rate = ros_bridge::RATE_DEFAULT;
+        }
+        if !self.scheduler.due(&config, snapshot.time_ms) {
+            return false;
+        }
+
+        let mut len = 0;
+        let mut written = true;
+        let mut write = |bytes: &[u8]| {
+            len += bytes.len();
+            written &= write(bytes);
+        };
+        match (self.session.protocol, config.format) {
+            (Protocol::Ros, _) => self.ros.publish(snapshot, write),
+            (_, Format::Csv) => {
+                let mut record = CsvRecord::new();
+                telemetry::write_csv(snapshot, config.mask, &mut record);
+                write(record.as_bytes());
+            }
+            (_, Format::Binary) => {
+                let mut frame = [0u8; MAX_FRAME];
+                let n =
+                    telemetry::write_binary(snapshot, config.mask, &mut self.scheduler, &mut frame);
+                write(&frame[..n]);
+            }
+        }
+
+        if let Some(chars_per_second) = self.chars_per_second {
+            self.scheduler.sent(snapshot.time_ms, len, chars_per_second);
+        }
+        if written {
+            self.dropping = false;
+            return false;
+        }
+        self.dropped = self.dropped.wrapping_add(1);
+        !core::mem::replace(&mut self.dropping, true)
+    }
+}
+
+/// Format an error like `SYSTem:ERRor:NEXT?` does.
+fn error_line(err: &Error) -> ArrayString<[u8; 128]> {
+    let mut line = ArrayString::new();
+    let message = core::str::from_utf8(err.get_message()).unwrap_or("");
+    writeln!(line, "{},\"{}\"", err.get_code(), message).ok();
+    line
+}
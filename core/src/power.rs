@@ -0,0 +1,114 @@
+use embedded_hal::digital::v2::OutputPin;
+
+use crate::estop;
+use crate::tree::{LEGS, SERVOS};
+
+/// `SYSTem:SERVos:POWer` settings.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PowerSettings {
+    pub on: bool,
+    /// Delay between legs when powering up, 0 releases all at once.
+    pub stagger_ms: u32,
+}
+
+impl PowerSettings {
+    pub const STAGGER_MAX_MS: u32 = 1000;
+
+    pub const fn new() -> Self {
+        PowerSettings {
+            on: false,
+            stagger_ms: 50,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PowerState {
+    Off,
+    /// Waiting for safe pulse widths to reach the controllers.
+    Arming,
+    /// Rail on, legs released one at a time, the given number so far.
+    Starting(usize),
+    On,
+}
+
+/// Servo power rail sequencing.
+///
+/// The rail is off at boot while the commanded pulse widths are already
+/// written, so servos never see power without a valid pulse. Powering up
+/// first holds every leg without pulses until a write succeeds, then enables
+/// the rail and releases the legs one at a time, spreading the inrush of
+/// servos moving to their position.
+pub struct PowerSequencer<PIN> {
+    rail: PIN,
+    state: PowerState,
+    since_ms: u32,
+}
+
+impl<PIN: OutputPin> PowerSequencer<PIN> {
+    /// `rail` switches servo power on when set high.
+    pub fn new(mut rail: PIN) -> Self {
+        let _ = rail.set_low();
+        PowerSequencer {
+            rail,
+            state: PowerState::Off,
+            since_ms: 0,
+        }
+    }
+
+    /// Advance the sequence and mask `targets` of legs not released yet.
+    pub fn update(&mut self, settings: &PowerSettings, targets: &mut [u16; SERVOS], now_ms: u32) {
+        self.state = match self.state {
+            _ if !settings.on || estop::is_latched() => {
+                self.cut();
+                PowerState::Off
+            }
+            PowerState::Off => PowerState::Arming,
+            PowerState::Starting(legs)
+                if now_ms.wrapping_sub(self.since_ms) >= settings.stagger_ms =>
+            {
+                self.since_ms = now_ms;
+                if legs + 1 >= LEGS {
+                    PowerState::On
+                } else {
+                    PowerState::Starting(legs + 1)
+                }
+            }
+            state => state,
+        };
+
+        let released = match self.state {
+            PowerState::Arming if settings.stagger_ms > 0 => 0,
+            PowerState::Starting(legs) => legs,
+            _ => LEGS,
+        };
+        for target in targets.iter_mut().skip(released * SERVOS / LEGS) {
+            *target = 0;
+        }
+    }
+
+    /// Switch the rail off at once, e.g. from the emergency stop input. It
+    /// stays off while the emergency stop is latched and then powers up
+    /// from the start.
+    pub fn cut(&mut self) {
+        let _ = self.rail.set_low();
+        self.state = PowerState::Off;
+    }
+
+    /// The masked targets were written to every controller.
+    pub fn written(&mut self, settings: &PowerSettings, now_ms: u32) {
+        if self.state == PowerState::Arming {
+            let _ = self.rail.set_high();
+            self.since_ms = now_ms;
+            self.state = if settings.stagger_ms > 0 {
+                PowerState::Starting(1)
+            } else {
+                PowerState::On
+            };
+        }
+    }
+
+    pub fn state(&self) -> PowerState {
+        self.state
+    }
+}
@@ -0,0 +1,407 @@
+//! Binary transport, an alternative to SCPI text on noisy links.
+//!
+//! A packet is `<seq> <kind> <payload...> <crc16>` where the CRC-16/CCITT-FALSE
+//! (little endian) covers everything before it. Packets are COBS encoded and
+//! terminated by a zero byte. Every request is answered with a packet
+//! carrying the same sequence number, an [Message::Ack], [Message::Nack] or
+//! the requested data.
+//!
+//! All multi-byte values are little endian.
+
+/// Largest unencoded packet.
+pub const MAX_PACKET: usize = 128;
+/// Largest encoded frame, including the delimiter.
+pub const MAX_FRAME: usize = MAX_PACKET + MAX_PACKET / 254 + 2;
+
+pub const SERVOS: usize = 24;
+
+const KIND_SERVO_TARGETS: u8 = 0x01;
+const KIND_BODY_POSE: u8 = 0x02;
+const KIND_READ_TELEMETRY: u8 = 0x03;
+const KIND_SCPI: u8 = 0x04;
+const KIND_ACK: u8 = 0x80;
+const KIND_NACK: u8 = 0x81;
+const KIND_TELEMETRY: u8 = 0x83;
+/// Unsolicited telemetry record, see [crate::telemetry].
+pub const KIND_TELEMETRY_RECORD: u8 = 0x84;
+
+/// Why a request was rejected.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NackReason {
+    /// Frame was damaged, CRC or COBS mismatch.
+    Corrupt = 1,
+    /// Payload length does not match the kind.
+    Length = 2,
+    UnknownKind = 3,
+    /// A value is out of range, nothing was changed.
+    OutOfRange = 4,
+    /// Refused in the current state, e.g. emergency stop latched.
+    NotAllowed = 5,
+    /// Frame did not fit in the receive buffer.
+    Overflow = 6,
+}
+
+impl NackReason {
+    fn from_u8(v: u8) -> Option<Self> {
+        Some(match v {
+            1 => NackReason::Corrupt,
+            2 => NackReason::Length,
+            3 => NackReason::UnknownKind,
+            4 => NackReason::OutOfRange,
+            5 => NackReason::NotAllowed,
+            6 => NackReason::Overflow,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Message {
+    /// Set pulse widths of all servos.
+    ServoTargets([u16; SERVOS]),
+    /// Set body rotation (roll, pitch, yaw in radians) and translation (m).
+    BodyPose {
+        rotation: [f32; 3],
+        translation: [f32; 3],
+    },
+    ReadTelemetry,
+    /// Leave the binary transport and go back to SCPI text.
+    Scpi,
+    Ack,
+    Nack(NackReason),
+    Telemetry {
+        targets: [u16; SERVOS],
+        estop: bool,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Packet {
+    pub seq: u8,
+    pub message: Message,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FrameError {
+    Overflow,
+    Cobs,
+    Crc,
+    /// Sequence number of the damaged packet, if it could be decoded.
+    Length(u8),
+    UnknownKind(u8),
+}
+
+impl FrameError {
+    pub fn reason(self) -> NackReason {
+        match self {
+            FrameError::Overflow => NackReason::Overflow,
+            FrameError::Cobs | FrameError::Crc => NackReason::Corrupt,
+            FrameError::Length(_) => NackReason::Length,
+            FrameError::UnknownKind(_) => NackReason::UnknownKind,
+        }
+    }
+
+    /// Sequence number to answer with, zero if unknown.
+    pub fn seq(self) -> u8 {
+        match self {
+            FrameError::Length(seq) | FrameError::UnknownKind(seq) => seq,
+            _ => 0,
+        }
+    }
+}
+
+//***********************************************************************************
+// # CRC and COBS
+
+/// CRC-16/CCITT-FALSE
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for b in data {
+        crc ^= (*b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// COBS encode `src` into `dst`, without delimiter.
+pub fn cobs_encode(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut code_index = 0;
+    let mut out = 1;
+    let mut code = 1u8;
+    for b in src {
+        if *b == 0 {
+            *dst.get_mut(code_index)? = code;
+            code_index = out;
+            out += 1;
+            code = 1;
+        } else {
+            *dst.get_mut(out)? = *b;
+            out += 1;
+            code += 1;
+            if code == 0xFF {
+                *dst.get_mut(code_index)? = code;
+                code_index = out;
+                out += 1;
+                code = 1;
+            }
+        }
+    }
+    *dst.get_mut(code_index)? = code;
+    Some(out)
+}
+
+/// COBS decode `src` (without delimiter) into `dst`.
+pub fn cobs_decode(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut i = 0;
+    let mut out = 0;
+    while i < src.len() {
+        let code = src[i];
+        if code == 0 {
+            return None;
+        }
+        i += 1;
+        for _ in 1..code {
+            let b = *src.get(i)?;
+            if b == 0 {
+                return None;
+            }
+            *dst.get_mut(out)? = b;
+            out += 1;
+            i += 1;
+        }
+        if code < 0xFF && i < src.len() {
+            *dst.get_mut(out)? = 0;
+            out += 1;
+        }
+    }
+    Some(out)
+}
+
+//***********************************************************************************
+// # Packets
+
+/// Little endian cursor over a payload.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn u16(&mut self) -> u16 {
+        let v = u16::from_le_bytes([self.data[0], self.data[1]]);
+        self.data = &self.data[2..];
+        v
+    }
+
+    fn f32(&mut self) -> f32 {
+        let v = f32::from_le_bytes([self.data[0], self.data[1], self.data[2], self.data[3]]);
+        self.data = &self.data[4..];
+        v
+    }
+}
+
+/// Little endian writer, silently stops at the end of the buffer.
+struct Writer<'a> {
+    data: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            if let Some(d) = self.data.get_mut(self.len) {
+                *d = *b;
+                self.len += 1;
+            }
+        }
+    }
+}
+
+impl Packet {
+    pub fn new(seq: u8, message: Message) -> Self {
+        Packet { seq, message }
+    }
+
+    /// Parse an unencoded packet, including CRC.
+    pub fn parse(data: &[u8]) -> Result<Self, FrameError> {
+        if data.len() < 4 {
+            return Err(FrameError::Crc);
+        }
+        let (body, crc) = data.split_at(data.len() - 2);
+        if crc16(body) != u16::from_le_bytes([crc[0], crc[1]]) {
+            return Err(FrameError::Crc);
+        }
+        let seq = body[0];
+        let kind = body[1];
+        let payload = &body[2..];
+        let expect = |len: usize| {
+            if payload.len() == len {
+                Ok(Reader { data: payload })
+            } else {
+                Err(FrameError::Length(seq))
+            }
+        };
+
+        let message = match kind {
+            KIND_SERVO_TARGETS => {
+                let mut r = expect(SERVOS * 2)?;
+                let mut targets = [0u16; SERVOS];
+                for t in targets.iter_mut() {
+                    *t = r.u16();
+                }
+                Message::ServoTargets(targets)
+            }
+            KIND_BODY_POSE => {
+                let mut r = expect(6 * 4)?;
+                Message::BodyPose {
+                    rotation: [r.f32(), r.f32(), r.f32()],
+                    translation: [r.f32(), r.f32(), r.f32()],
+                }
+            }
+            KIND_READ_TELEMETRY => {
+                expect(0)?;
+                Message::ReadTelemetry
+            }
+            KIND_SCPI => {
+                expect(0)?;
+                Message::Scpi
+            }
+            KIND_ACK => {
+                expect(0)?;
+                Message::Ack
+            }
+            KIND_NACK => {
+                expect(1)?;
+                Message::Nack(NackReason::from_u8(payload[0]).ok_or(FrameError::Length(seq))?)
+            }
+            KIND_TELEMETRY => {
+                let mut r = expect(SERVOS * 2 + 1)?;
+                let mut targets = [0u16; SERVOS];
+                for t in targets.iter_mut() {
+                    *t = r.u16();
+                }
+                Message::Telemetry {
+                    targets,
+                    estop: r.data[0] != 0,
+                }
+            }
+            _ => return Err(FrameError::UnknownKind(seq)),
+        };
+        Ok(Packet { seq, message })
+    }
+
+    /// Serialize into an unencoded packet, including CRC.
+    pub fn serialize(&self, buf: &mut [u8; MAX_PACKET]) -> usize {
+        let mut w = Writer { data: buf, len: 0 };
+        match &self.message {
+            Message::ServoTargets(targets) => {
+                w.bytes(&[self.seq, KIND_SERVO_TARGETS]);
+                for t in targets.iter() {
+                    w.bytes(&t.to_le_bytes());
+                }
+            }
+            Message::BodyPose {
+                rotation,
+                translation,
+            } => {
+                w.bytes(&[self.seq, KIND_BODY_POSE]);
+                for v in rotation.iter().chain(translation.iter()) {
+                    w.bytes(&v.to_le_bytes());
+                }
+            }
+            Message::ReadTelemetry => w.bytes(&[self.seq, KIND_READ_TELEMETRY]),
+            Message::Scpi => w.bytes(&[self.seq, KIND_SCPI]),
+            Message::Ack => w.bytes(&[self.seq, KIND_ACK]),
+            Message::Nack(reason) => w.bytes(&[self.seq, KIND_NACK, *reason as u8]),
+            Message::Telemetry { targets, estop } => {
+                w.bytes(&[self.seq, KIND_TELEMETRY]);
+                for t in targets.iter() {
+                    w.bytes(&t.to_le_bytes());
+                }
+                w.bytes(&[*estop as u8]);
+            }
+        }
+        let crc = crc16(&w.data[..w.len]);
+        w.bytes(&crc.to_le_bytes());
+        w.len
+    }
+
+    /// Encode into a complete frame, COBS encoded and delimited.
+    pub fn encode(&self, frame: &mut [u8; MAX_FRAME]) -> usize {
+        let mut buf = [0u8; MAX_PACKET];
+        let len = self.serialize(&mut buf);
+        delimit(&buf[..len], frame)
+    }
+}
+
+/// Encode a raw payload into a complete frame, for records which are only
+/// ever sent by the carrier. The payload is truncated to fit a packet.
+pub fn encode_frame(seq: u8, kind: u8, payload: &[u8], frame: &mut [u8; MAX_FRAME]) -> usize {
+    let mut buf = [0u8; MAX_PACKET];
+    let mut w = Writer {
+        data: &mut buf,
+        len: 0,
+    };
+    w.bytes(&[seq, kind]);
+    w.bytes(&payload[..payload.len().min(MAX_PACKET - 4)]);
+    let crc = crc16(&w.data[..w.len]);
+    w.bytes(&crc.to_le_bytes());
+    let len = w.len;
+    delimit(&buf[..len], frame)
+}
+
+fn delimit(packet: &[u8], frame: &mut [u8; MAX_FRAME]) -> usize {
+    // A frame is always large enough for a packet
+    let n = cobs_encode(packet, &mut frame[..]).unwrap_or(0);
+    frame[n] = 0;
+    n + 1
+}
+
+/// Collects bytes into frames and parses them.
+pub struct FrameDecoder {
+    buf: [u8; MAX_FRAME],
+    len: usize,
+    overflow: bool,
+}
+
+impl FrameDecoder {
+    pub const fn new() -> Self {
+        FrameDecoder {
+            buf: [0; MAX_FRAME],
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    /// Push a received byte, returns the packet once a frame is complete.
+    pub fn push(&mut self, byte: u8) -> Option<Result<Packet, FrameError>> {
+        if byte != 0 {
+            if self.len < self.buf.len() {
+                self.buf[self.len] = byte;
+                self.len += 1;
+            } else {
+                self.overflow = true;
+            }
+            return None;
+        }
+
+        let len = core::mem::replace(&mut self.len, 0);
+        if core::mem::replace(&mut self.overflow, false) {
+            return Some(Err(FrameError::Overflow));
+        }
+        // Repeated delimiters are allowed to resynchronize
+        if len == 0 {
+            return None;
+        }
+        let mut packet = [0u8; MAX_PACKET];
+        Some(match cobs_decode(&self.buf[..len], &mut packet) {
+            Some(n) => Packet::parse(&packet[..n]),
+            None => Err(FrameError::Cobs),
+        })
+    }
+}
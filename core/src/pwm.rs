@@ -0,0 +1,67 @@
+/// Nominal frequency of the PCA9685 internal oscillator.
+pub const OSCILLATOR_HZ: f32 = 25_000_000.0;
+/// Counts per PWM period.
+const STEPS: f32 = 4096.0;
+/// Prescale register limits of the PCA9685.
+const PRESCALE_MIN: f32 = 3.0;
+const PRESCALE_MAX: f32 = 255.0;
+
+/// `DIAGnostic:PWM` settings.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PwmSettings {
+    /// Servo update rate in Hz, 50 for analog servos and up to 333 for
+    /// digital ones.
+    pub frequency: u16,
+    /// Actual oscillator frequency over [OSCILLATOR_HZ], they differ by
+    /// several percent between chips.
+    pub trim: f32,
+}
+
+impl PwmSettings {
+    pub const FREQUENCY_MIN: u16 = 40;
+    pub const FREQUENCY_MAX: u16 = 333;
+    pub const TRIM_MIN: f32 = 0.9;
+    pub const TRIM_MAX: f32 = 1.1;
+
+    pub const fn new() -> Self {
+        PwmSettings {
+            frequency: 50,
+            trim: 1.0,
+        }
+    }
+
+    /// Prescaler setting closest to the frequency and the resulting count
+    /// length.
+    pub fn timing(&self) -> PwmTiming {
+        let oscillator = OSCILLATOR_HZ * self.trim;
+        let divider = (oscillator / (STEPS * self.frequency as f32) + 0.5)
+            .clamp(PRESCALE_MIN + 1.0, PRESCALE_MAX + 1.0) as u32;
+        PwmTiming {
+            prescale: (divider - 1) as u8,
+            count_us: divider as f32 * 1e6 / oscillator,
+        }
+    }
+}
+
+/// Prescaler setting of a PCA9685 and the pulse width conversion that goes
+/// with it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PwmTiming {
+    pub prescale: u8,
+    /// Length of one count in µs.
+    pub count_us: f32,
+}
+
+impl PwmTiming {
+    /// Counts for a pulse of `pulse_us`, 0 stays without pulse. Pulses
+    /// longer than the period end just before it.
+    pub fn counts(&self, pulse_us: u16) -> u16 {
+        (pulse_us as f32 / self.count_us + 0.5).min(STEPS - 1.0) as u16
+    }
+
+    /// Actual update rate in Hz, as close to the setting as the prescaler
+    /// allows.
+    pub fn frequency(&self) -> f32 {
+        1e6 / (self.count_us * STEPS)
+    }
+}
@@ -0,0 +1,297 @@
+use core::cell::RefCell;
+use nalgebra::Vector3;
+
+use crate::body::Velocity;
+use crate::estop;
+use crate::log;
+use crate::protocol::SERVOS;
+use crate::rosserial::{
+    Decoder, Reader, Writer, ID_PUBLISHER, ID_SUBSCRIBER, ID_TIME, ID_TX_STOP, MAX_MESSAGE,
+};
+use crate::servo_commands::ServoControl;
+use crate::telemetry::Snapshot;
+
+/// Pulse width of a servo at 0 rad, nominal servo with 1000 per 90 degrees.
+const PWIDTH_CENTER: f64 = 1500.0;
+const PWIDTH_PER_RAD: f64 = 1000.0 / core::f64::consts::FRAC_PI_2;
+
+/// Time is requested from the agent this often, it restarts the session
+/// if it does not hear from us.
+const SYNC_MS: u32 = 5000;
+
+/// Publication rate when telemetry is off.
+pub const RATE_DEFAULT: u16 = 10;
+
+const JOINT_STATES: u16 = 100;
+const BATTERY_STATE: u16 = 101;
+const JOINT_COMMAND: u16 = 100;
+const CMD_VEL: u16 = 101;
+
+struct Topic {
+    id: u16,
+    name: &'static [u8],
+    kind: &'static [u8],
+    md5: &'static [u8],
+}
+
+const PUBLISHERS: [Topic; 2] = [
+    Topic {
+        id: JOINT_STATES,
+        name: b"joint_states",
+        kind: b"sensor_msgs/JointState",
+        md5: b"3066dcd76a6cfaef579bd0f34173e9fd",
+    },
+    Topic {
+        id: BATTERY_STATE,
+        name: b"battery_state",
+        kind: b"sensor_msgs/BatteryState",
+        md5: b"4ddae7f048e32fda22cac764685e3974",
+    },
+];
+
+const SUBSCRIBERS: [Topic; 2] = [
+    Topic {
+        id: JOINT_COMMAND,
+        name: b"joint_command",
+        kind: b"sensor_msgs/JointState",
+        md5: b"3066dcd76a6cfaef579bd0f34173e9fd",
+    },
+    Topic {
+        id: CMD_VEL,
+        name: b"cmd_vel",
+        kind: b"geometry_msgs/Twist",
+        md5: b"9f195f881246fdfa2798d1d3eebca84a",
+    },
+];
+
+/// Joint names, `servo_<n>` with n as in `BODY:SERVos:PWIDth:SET`.
+const JOINT_NAMES: [&[u8]; SERVOS] = [
+    b"servo_1",
+    b"servo_2",
+    b"servo_3",
+    b"servo_4",
+    b"servo_5",
+    b"servo_6",
+    b"servo_7",
+    b"servo_8",
+    b"servo_9",
+    b"servo_10",
+    b"servo_11",
+    b"servo_12",
+    b"servo_13",
+    b"servo_14",
+    b"servo_15",
+    b"servo_16",
+    b"servo_17",
+    b"servo_18",
+    b"servo_19",
+    b"servo_20",
+    b"servo_21",
+    b"servo_22",
+    b"servo_23",
+    b"servo_24",
+];
+
+/// Maps ROS topics onto the carrier state.
+///
+/// Publishes `joint_states` (servo targets as angles) and `battery_state`,
+/// subscribes to `joint_command` (positions only, by name or in order) and
+/// `cmd_vel`.
+pub struct RosBridge<'a> {
+    servos: &'a RefCell<[ServoControl]>,
+    velocity: &'a RefCell<Velocity>,
+}
+
+impl<'a> RosBridge<'a> {
+    pub fn new(servos: &'a RefCell<[ServoControl]>, velocity: &'a RefCell<Velocity>) -> Self {
+        RosBridge { servos, velocity }
+    }
+
+    fn joint_command(&self, data: &[u8]) -> Option<()> {
+        if estop::is_latched() {
+            return Some(());
+        }
+        let mut r = Reader::new(data);
+        r.header()?;
+        let mut index = [0usize; SERVOS];
+        let names = r.u32()? as usize;
+        if names > SERVOS {
+            return None;
+        }
+        for i in index.iter_mut().take(names) {
+            let name = r.string()?;
+            *i = JOINT_NAMES.iter().position(|n| *n == name)?;
+        }
+        let count = r.u32()? as usize;
+        if count > SERVOS || (names != 0 && names != count) {
+            return None;
+        }
+        let mut targets = [(0usize, 0u16); SERVOS];
+        for (i, target) in targets.iter_mut().take(count).enumerate() {
+            let pwidth = PWIDTH_CENTER + r.f64()? * PWIDTH_PER_RAD;
+            let range = ServoControl::PWIDTH_MIN as f64..=ServoControl::PWIDTH_MAX as f64;
+            if !range.contains(&pwidth) {
+                return None;
+            }
+            let servo = if names == 0 { i } else { index[i] };
+            *target = (servo, (pwidth + 0.5) as u16);
+        }
+        let mut servos = self.servos.borrow_mut();
+        for (servo, pwidth) in targets.iter().take(count) {
+            servos[*servo].pulse_width = *pwidth;
+        }
+        Some(())
+    }
+
+    fn cmd_vel(&self, data: &[u8]) -> Option<()> {
+        let mut r = Reader::new(data);
+        let mut v = [0f32; 6];
+        for v in v.iter_mut() {
+            *v = r.f64()? as f32;
+        }
+        if !v.iter().all(|v| v.is_finite()) {
+            return None;
+        }
+        let mut velocity = self.velocity.borrow_mut();
+        velocity.linear = Vector3::new(v[0], v[1], v[2]);
+        velocity.angular = Vector3::new(v[3], v[4], v[5]);
+        Some(())
+    }
+}
+
+/// rosserial session of one interface.
+pub struct RosNode {
+    decoder: Decoder,
+    /// Agent time in (s, ns) at [RosNode::synced_ms].
+    time: (u32, u32),
+    synced_ms: u32,
+    requested_ms: u32,
+    seq: u32,
+}
+
+impl RosNode {
+    pub const fn new() -> Self {
+        RosNode {
+            decoder: Decoder::new(),
+            time: (0, 0),
+            synced_ms: 0,
+            requested_ms: 0,
+            seq: 0,
+        }
+    }
+
+    /// Push a received byte and handle complete messages, returns false once
+    /// the agent stops.
+    pub fn push<W>(&mut self, byte: u8, bridge: &RosBridge, now_ms: u32, mut write: W) -> bool
+    where
+        W: FnMut(&[u8]),
+    {
+        let (topic, data) = match self.decoder.push(byte) {
+            Some(message) => message,
+            None => return true,
+        };
+        match topic {
+            ID_PUBLISHER => {
+                for (id, topics) in
+                    [(ID_PUBLISHER, &PUBLISHERS), (ID_SUBSCRIBER, &SUBSCRIBERS)].iter()
+                {
+                    for topic in topics.iter() {
+                        let mut w = Writer::new();
+                        w.u16(topic.id)
+                            .string(topic.name)
+                            .string(topic.kind)
+                            .string(topic.md5)
+                            .i32(MAX_MESSAGE as i32);
+                        w.send(*id, &mut write);
+                    }
+                }
+                self.request_time(now_ms, &mut write);
+            }
+            ID_TIME => {
+                let mut r = Reader::new(data);
+                if let (Some(sec), Some(nsec)) = (r.u32(), r.u32()) {
+                    self.time = (sec, nsec);
+                    self.synced_ms = now_ms;
+                }
+            }
+            ID_TX_STOP => return false,
+            JOINT_COMMAND if bridge.joint_command(data).is_none() => {
+                log::warning("Invalid joint_command", 0);
+            }
+            CMD_VEL if bridge.cmd_vel(data).is_none() => {
+                log::warning("Invalid cmd_vel", 0);
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Publish `joint_states` and `battery_state`.
+    pub fn publish<W>(&mut self, snapshot: &Snapshot, mut write: W)
+    where
+        W: FnMut(&[u8]),
+    {
+        if snapshot.time_ms.wrapping_sub(self.requested_ms) >= SYNC_MS {
+            self.request_time(snapshot.time_ms, &mut write);
+        }
+
+        let mut w = Writer::new();
+        self.header(&mut w, snapshot.time_ms);
+        w.u32(SERVOS as u32);
+        for name in JOINT_NAMES.iter() {
+            w.string(name);
+        }
+        w.u32(SERVOS as u32);
+        for t in snapshot.targets.iter() {
+            w.f64((*t as f64 - PWIDTH_CENTER) / PWIDTH_PER_RAD);
+        }
+        // No velocity or effort
+        w.u32(0).u32(0);
+        w.send(JOINT_STATES, &mut write);
+
+        let nan = f32::NAN;
+        let mut w = Writer::new();
+        self.header(&mut w, snapshot.time_ms);
+        w.f32(snapshot.battery_mv as f32 / 1000.0)
+            // temperature, current, charge, capacity, design capacity, percentage
+            .f32(nan)
+            .f32(nan)
+            .f32(nan)
+            .f32(nan)
+            .f32(nan)
+            .f32(nan)
+            // status, health and technology unknown, present
+            .u8(0)
+            .u8(0)
+            .u8(0)
+            .u8(1)
+            // No cell voltages or temperatures, location, serial number
+            .u32(0)
+            .u32(0)
+            .string(b"")
+            .string(b"");
+        w.send(BATTERY_STATE, &mut write);
+    }
+
+    fn request_time<W>(&mut self, now_ms: u32, write: W)
+    where
+        W: FnMut(&[u8]),
+    {
+        self.requested_ms = now_ms;
+        let mut w = Writer::new();
+        w.u32(0).u32(0);
+        w.send(ID_TIME, write);
+    }
+
+    /// `std_msgs/Header` stamped with the agent's time.
+    fn header(&mut self, w: &mut Writer, now_ms: u32) {
+        let elapsed = now_ms.wrapping_sub(self.synced_ms);
+        let nsec = self.time.1 as u64 + (elapsed % 1000) as u64 * 1_000_000;
+        let sec = self.time.0 + elapsed / 1000 + (nsec / 1_000_000_000) as u32;
+        self.seq = self.seq.wrapping_add(1);
+        w.u32(self.seq)
+            .u32(sec)
+            .u32((nsec % 1_000_000_000) as u32)
+            .string(b"");
+    }
+}
@@ -0,0 +1,248 @@
+//! rosserial wire protocol, version 2 (ROS Hydro and later), as spoken by
+//! `rosserial_python`'s `serial_node.py` and compatible agents.
+//!
+//! A message is
+//! `0xFF 0xFE <len u16> <len checksum> <topic u16> <data...> <checksum>`
+//! where each checksum is 255 minus the byte sum of the fields it covers,
+//! modulo 256. Message data uses the ROS1 serialization: little endian
+//! primitives, strings and arrays prefixed with a u32 length.
+
+use arrayvec::ArrayVec;
+
+/// Topic negotiation, the agent sends an empty message to request topics
+/// and publishers are announced on it.
+pub const ID_PUBLISHER: u16 = 0;
+pub const ID_SUBSCRIBER: u16 = 1;
+pub const ID_TIME: u16 = 10;
+/// The agent is shutting down.
+pub const ID_TX_STOP: u16 = 11;
+
+/// Largest message, data only.
+pub const MAX_MESSAGE: usize = 512;
+
+const SYNC: u8 = 0xFF;
+const VERSION: u8 = 0xFE;
+
+fn checksum(data: &[u8]) -> u8 {
+    255 - data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum State {
+    Sync,
+    Version,
+    Length(usize),
+    LengthChecksum,
+    Topic(usize),
+    Data,
+    Checksum,
+}
+
+/// Collects bytes into messages, damaged ones are dropped like the agent
+/// does.
+pub struct Decoder {
+    state: State,
+    header: [u8; 4],
+    len: usize,
+    data: [u8; MAX_MESSAGE],
+    pos: usize,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Decoder {
+            state: State::Sync,
+            header: [0; 4],
+            len: 0,
+            data: [0; MAX_MESSAGE],
+            pos: 0,
+        }
+    }
+
+    /// Push a received byte, returns topic and data once a message is
+    /// complete.
+    pub fn push(&mut self, byte: u8) -> Option<(u16, &[u8])> {
+        self.state = match self.state {
+            State::Sync if byte == SYNC => State::Version,
+            State::Sync => State::Sync,
+            State::Version if byte == VERSION => State::Length(0),
+            // Another sync byte may start the real header
+            State::Version if byte == SYNC => State::Version,
+            State::Version => State::Sync,
+            State::Length(i) => {
+                self.header[i] = byte;
+                if i == 0 {
+                    State::Length(1)
+                } else {
+                    State::LengthChecksum
+                }
+            }
+            State::LengthChecksum => {
+                self.len = u16::from_le_bytes([self.header[0], self.header[1]]) as usize;
+                if byte != checksum(&self.header[..2]) || self.len > MAX_MESSAGE {
+                    State::Sync
+                } else {
+                    State::Topic(0)
+                }
+            }
+            State::Topic(i) => {
+                self.header[2 + i] = byte;
+                self.pos = 0;
+                match (i, self.len) {
+                    (0, _) => State::Topic(1),
+                    (_, 0) => State::Checksum,
+                    _ => State::Data,
+                }
+            }
+            State::Data => {
+                self.data[self.pos] = byte;
+                self.pos += 1;
+                if self.pos == self.len {
+                    State::Checksum
+                } else {
+                    State::Data
+                }
+            }
+            State::Checksum => {
+                self.state = State::Sync;
+                let sum = checksum(&self.header[2..])
+                    .wrapping_sub(255)
+                    .wrapping_add(checksum(&self.data[..self.len]));
+                if byte != sum {
+                    return None;
+                }
+                let topic = u16::from_le_bytes([self.header[2], self.header[3]]);
+                return Some((topic, &self.data[..self.len]));
+            }
+        };
+        None
+    }
+}
+
+/// Serializes message data.
+pub struct Writer {
+    data: ArrayVec<[u8; MAX_MESSAGE]>,
+    overflow: bool,
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Writer {
+            data: ArrayVec::new(),
+            overflow: false,
+        }
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        if self.data.try_extend_from_slice(bytes).is_err() {
+            self.overflow = true;
+        }
+        self
+    }
+
+    pub fn u8(&mut self, v: u8) -> &mut Self {
+        self.bytes(&[v])
+    }
+
+    pub fn u16(&mut self, v: u16) -> &mut Self {
+        self.bytes(&v.to_le_bytes())
+    }
+
+    pub fn u32(&mut self, v: u32) -> &mut Self {
+        self.bytes(&v.to_le_bytes())
+    }
+
+    pub fn i32(&mut self, v: i32) -> &mut Self {
+        self.bytes(&v.to_le_bytes())
+    }
+
+    pub fn f32(&mut self, v: f32) -> &mut Self {
+        self.bytes(&v.to_le_bytes())
+    }
+
+    pub fn f64(&mut self, v: f64) -> &mut Self {
+        self.bytes(&v.to_le_bytes())
+    }
+
+    pub fn string(&mut self, s: &[u8]) -> &mut Self {
+        self.u32(s.len() as u32).bytes(s)
+    }
+
+    /// Pass the framed message to `write` in one piece, nothing if the data
+    /// overflowed.
+    pub fn send<W>(&self, topic: u16, mut write: W)
+    where
+        W: FnMut(&[u8]),
+    {
+        if self.overflow {
+            return;
+        }
+        let len = (self.data.len() as u16).to_le_bytes();
+        let topic = topic.to_le_bytes();
+        let sum = checksum(&topic)
+            .wrapping_sub(255)
+            .wrapping_add(checksum(&self.data));
+        // Header and checksum add 8 bytes to the data
+        let mut message = ArrayVec::<[u8; 2 * MAX_MESSAGE]>::new();
+        message.extend(
+            [
+                SYNC,
+                VERSION,
+                len[0],
+                len[1],
+                checksum(&len),
+                topic[0],
+                topic[1],
+            ]
+            .iter()
+            .copied(),
+        );
+        message.extend(self.data.iter().copied());
+        message.push(sum);
+        write(&message);
+    }
+}
+
+/// Deserializes message data, every read returns `None` past the end.
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    pub fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        if n > self.data.len() {
+            return None;
+        }
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Some(head)
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        let b = self.bytes(4)?;
+        Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn f64(&mut self) -> Option<f64> {
+        let b = self.bytes(8)?;
+        let mut v = [0u8; 8];
+        v.copy_from_slice(b);
+        Some(f64::from_le_bytes(v))
+    }
+
+    pub fn string(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    /// Skip a `std_msgs/Header`.
+    pub fn header(&mut self) -> Option<()> {
+        self.bytes(12)?;
+        self.string()?;
+        Some(())
+    }
+}
@@ -0,0 +1,81 @@
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+
+/// Slowest supported baud rate, the fastest depends on the peripheral clock.
+pub const BAUD_MIN: u32 = 1200;
+
+/// Receive buffer full, bytes dropped.
+pub const ERR_OVERFLOW: u8 = 0x01;
+/// Hardware overrun, byte lost before it was read.
+pub const ERR_OVERRUN: u8 = 0x02;
+pub const ERR_FRAMING: u8 = 0x04;
+pub const ERR_NOISE: u8 = 0x08;
+pub const ERR_PARITY: u8 = 0x10;
+/// Any other line error.
+pub const ERR_OTHER: u8 = 0x20;
+
+/// Serial line counters, updated from the receive interrupt.
+pub struct SerialStats {
+    pub received: AtomicU32,
+    pub overflow: AtomicU32,
+    pub overrun: AtomicU32,
+    pub framing: AtomicU32,
+    pub noise: AtomicU32,
+    pub parity: AtomicU32,
+    pub other: AtomicU32,
+}
+
+impl SerialStats {
+    const fn new() -> Self {
+        SerialStats {
+            received: AtomicU32::new(0),
+            overflow: AtomicU32::new(0),
+            overrun: AtomicU32::new(0),
+            framing: AtomicU32::new(0),
+            noise: AtomicU32::new(0),
+            parity: AtomicU32::new(0),
+            other: AtomicU32::new(0),
+        }
+    }
+}
+
+impl SerialStats {
+    /// Total of all error counters.
+    pub fn errors(&self) -> u32 {
+        [
+            &self.overflow,
+            &self.overrun,
+            &self.framing,
+            &self.noise,
+            &self.parity,
+            &self.other,
+        ]
+        .iter()
+        .map(|c| c.load(Ordering::Relaxed))
+        .fold(0, u32::wrapping_add)
+    }
+}
+
+pub static STATS: SerialStats = SerialStats::new();
+
+/// Errors not yet reported to the SCPI error queue.
+static PENDING: AtomicU8 = AtomicU8::new(0);
+
+/// Count an error and flag it for reporting, unknown errors count as
+/// [ERR_OTHER].
+pub fn error(err: u8) {
+    let (counter, err) = match err {
+        ERR_OVERFLOW => (&STATS.overflow, err),
+        ERR_OVERRUN => (&STATS.overrun, err),
+        ERR_FRAMING => (&STATS.framing, err),
+        ERR_NOISE => (&STATS.noise, err),
+        ERR_PARITY => (&STATS.parity, err),
+        _ => (&STATS.other, ERR_OTHER),
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+    PENDING.fetch_or(err, Ordering::Relaxed);
+}
+
+/// Take errors that occurred since the last call.
+pub fn take_errors() -> u8 {
+    PENDING.swap(0, Ordering::Relaxed)
+}
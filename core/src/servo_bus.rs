@@ -0,0 +1,240 @@
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use pwm_pca9685::{Channel, Error, Pca9685};
+use scpi::prelude::{Context, Error as ScpiError, ErrorCode};
+
+use crate::pwm::{PwmSettings, PwmTiming};
+use crate::{estop, log, overload};
+
+/// Number of attempts for every transfer before it is counted as failed.
+pub const I2C_ATTEMPTS: u8 = 3;
+/// Failed updates in a row before a controller is reported unreachable.
+pub const UNREACHABLE_THRESHOLD: u32 = 5;
+
+/// Error bookkeeping for one PCA9685 controller.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BusHealth {
+    /// Failed transfer attempts.
+    pub errors: u32,
+    /// Retried transfer attempts.
+    pub retries: u32,
+    /// Bus recoveries triggered by this controller.
+    pub recoveries: u32,
+    /// Failed updates in a row, reset on success.
+    pub consecutive: u32,
+    /// Set once `consecutive` reaches [UNREACHABLE_THRESHOLD].
+    pub unreachable: bool,
+}
+
+/// Outcome of a [ServoBank::update].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BankStatus {
+    Ok,
+    /// Update failed but the controller is not (yet) considered lost.
+    Failed,
+    /// Update failed and the controller just crossed [UNREACHABLE_THRESHOLD].
+    Unreachable,
+    /// First successful update after having been unreachable.
+    Recovered,
+}
+
+/// A PCA9685 with retries, bus recovery and error counting.
+///
+/// The controller is (re)initialized lazily on the first update and after
+/// every failure, in case it lost power or was reset by the bus recovery.
+pub struct ServoBank<I2C> {
+    pwm: Pca9685<I2C>,
+    prescale: u8,
+    needs_init: bool,
+    pub health: BusHealth,
+}
+
+impl<I2C, E> ServoBank<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    pub fn new(pwm: Pca9685<I2C>) -> Self {
+        ServoBank {
+            pwm,
+            prescale: PwmSettings::new().timing().prescale,
+            needs_init: true,
+            health: BusHealth::default(),
+        }
+    }
+
+    /// Change the PWM frequency, written with the next update.
+    pub fn set_prescale(&mut self, prescale: u8) {
+        if prescale != self.prescale {
+            self.prescale = prescale;
+            self.needs_init = true;
+        }
+    }
+
+    /// Write off-times of all 16 channels in counts, see
+    /// [ServoBank::transfer].
+    pub fn update<R>(&mut self, off: &[u16; 16], recover: R) -> BankStatus
+    where
+        R: FnMut(),
+    {
+        self.transfer(|pwm| pwm.set_all_on_off(&[0u16; 16], off), recover)
+    }
+
+    /// Turn all 16 channels fully off, see [ServoBank::transfer].
+    pub fn full_off<R>(&mut self, recover: R) -> BankStatus
+    where
+        R: FnMut(),
+    {
+        self.transfer(|pwm| pwm.set_channel_full_off(Channel::All), recover)
+    }
+
+    /// Run `op` against the controller.
+    ///
+    /// A failed attempt runs `recover` (which should free and reset the bus)
+    /// before retrying, up to [I2C_ATTEMPTS] attempts in total.
+    fn transfer<F, R>(&mut self, mut op: F, mut recover: R) -> BankStatus
+    where
+        F: FnMut(&mut Pca9685<I2C>) -> Result<(), Error<E>>,
+        R: FnMut(),
+    {
+        for attempt in 0..I2C_ATTEMPTS {
+            if attempt > 0 {
+                self.health.retries += 1;
+            }
+            match self.try_init().and_then(|_| op(&mut self.pwm)) {
+                Ok(()) => {
+                    self.health.consecutive = 0;
+                    return if core::mem::replace(&mut self.health.unreachable, false) {
+                        BankStatus::Recovered
+                    } else {
+                        BankStatus::Ok
+                    };
+                }
+                Err(_) => {
+                    self.health.errors += 1;
+                    self.health.recoveries += 1;
+                    self.needs_init = true;
+                    recover();
+                }
+            }
+        }
+
+        self.health.consecutive += 1;
+        if !self.health.unreachable && self.health.consecutive >= UNREACHABLE_THRESHOLD {
+            self.health.unreachable = true;
+            BankStatus::Unreachable
+        } else {
+            BankStatus::Failed
+        }
+    }
+
+    fn try_init(&mut self) -> Result<(), Error<E>> {
+        if self.needs_init {
+            self.pwm.enable()?;
+            self.pwm.set_prescale(self.prescale)?;
+            self.needs_init = false;
+        }
+        Ok(())
+    }
+}
+
+/// Both controllers of the body, even servos on the first and odd servos on
+/// the second.
+pub struct ServoBanks<I2C> {
+    pub banks: [ServoBank<I2C>; 2],
+    /// Pulse widths last written to the controllers, zero while off.
+    pub outputs: [u16; 24],
+    timing: PwmTiming,
+}
+
+impl<I2C, E> ServoBanks<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    pub fn new(first: ServoBank<I2C>, second: ServoBank<I2C>) -> Self {
+        ServoBanks {
+            banks: [first, second],
+            outputs: [0; 24],
+            timing: PwmSettings::new().timing(),
+        }
+    }
+
+    /// Apply PWM `settings`, the controllers are reinitialized with the
+    /// next update if the prescaler changes.
+    pub fn configure(&mut self, settings: &PwmSettings) {
+        self.timing = settings.timing();
+        for bank in self.banks.iter_mut() {
+            bank.set_prescale(self.timing.prescale);
+        }
+    }
+
+    /// Write `targets` in µs, banks set in `off` are turned fully off
+    /// instead.
+    ///
+    /// Channels without a servo idle at 1500 µs.
+    pub fn update<R>(
+        &mut self,
+        targets: &[u16; 24],
+        off: [bool; 2],
+        mut recover: R,
+    ) -> [BankStatus; 2]
+    where
+        R: FnMut(),
+    {
+        let mut status = [BankStatus::Ok; 2];
+        for (bank, pwm) in self.banks.iter_mut().enumerate() {
+            let off = off[bank];
+            status[bank] = if off {
+                pwm.full_off(&mut recover)
+            } else {
+                let mut on_time = [self.timing.counts(1500); 16];
+                for (index, t) in targets.iter().skip(bank).step_by(2).enumerate() {
+                    on_time[index] = self.timing.counts(*t);
+                }
+                pwm.update(&on_time, &mut recover)
+            };
+            if let BankStatus::Ok | BankStatus::Recovered = status[bank] {
+                for i in (bank..24).step_by(2) {
+                    self.outputs[i] = if off { 0 } else { targets[i] };
+                }
+            }
+        }
+        status
+    }
+
+    pub fn health(&self) -> [BusHealth; 2] {
+        [self.banks[0].health, self.banks[1].health]
+    }
+
+    /// Failed transfers of both controllers.
+    pub fn errors(&self) -> u32 {
+        self.banks[0].health.errors + self.banks[1].health.errors
+    }
+}
+
+/// Banks to hold fully off, every bank while the emergency stop is latched
+/// and those tripped by the overload protection.
+pub fn held_off() -> [bool; 2] {
+    let estop = estop::is_latched();
+    let mut off = overload::tripped();
+    for off in off.iter_mut() {
+        *off |= estop;
+    }
+    off
+}
+
+/// Log controllers becoming unreachable or recovering, unreachable ones are
+/// also reported on `context`.
+pub fn report(status: &[BankStatus; 2], context: &mut Context) {
+    for (bank, status) in status.iter().enumerate() {
+        match status {
+            BankStatus::Unreachable => {
+                log::error("Servo controller unreachable", bank as u32 + 1);
+                context.push_error(ScpiError::extended(
+                    ErrorCode::DeviceSpecificError,
+                    b"Servo controller unreachable",
+                ));
+            }
+            BankStatus::Recovered => log::info("Servo controller recovered", bank as u32 + 1),
+            _ => {}
+        }
+    }
+}
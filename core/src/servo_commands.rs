@@ -1,18 +1,24 @@
 use core::cell::RefCell;
-use core::convert::{TryFrom, TryInto};
+use core::convert::TryInto;
 use scpi::error::Result;
 use scpi::expression::numeric_list::{NumericList, Token as NumericItem};
+use scpi::format::Arbitrary;
 use scpi::prelude::*;
+use scpi::tokenizer::Token;
+
+use crate::estop;
 
 #[derive(Copy, Clone, Debug)]
 pub struct ServoControl {
+    /// In µs, 0 for no pulse.
     pub pulse_width: u16,
     pub enable: bool,
 }
 
 impl ServoControl {
-    const PWIDTH_MIN: u16 = 0u16;
-    const PWIDTH_MAX: u16 = 4095u16;
+    pub const PWIDTH_MIN: u16 = 0u16;
+    /// Fits in the period of the fastest `DIAGnostic:PWM:FREQuency`.
+    pub const PWIDTH_MAX: u16 = 3000u16;
 
     pub fn new() -> Self {
         ServoControl {
@@ -32,6 +38,19 @@ macro_rules! servo_ctrl_new {
     };
 }
 
+/// New pulse widths are refused while the emergency stop is latched, like the
+/// other transports do.
+fn check_estop() -> Result<()> {
+    if estop::is_latched() {
+        Err(Error::extended(
+            ErrorCode::ExecutionError,
+            b"Emergency stop latched",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
 pub struct BodyServoPwidthAllCommand<'a> {
     servos: &'a RefCell<[ServoControl]>,
 }
@@ -42,17 +61,21 @@ impl<'a> BodyServoPwidthAllCommand<'a> {
 
 impl<'a> Command for BodyServoPwidthAllCommand<'a> {
     fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        check_estop()?;
         let mut servo_pwidth = [0u16; 24];
-        let mut num = 0u8;
-        let mut pulses: NumericList = args.next_data(false)?.unwrap().try_into()?;
-        for (i, puls) in pulses.enumerate() {
+        let mut num = 0usize;
+        let pulses: NumericList = args.next_data(false)?.unwrap().try_into()?;
+        for puls in pulses {
             if let NumericItem::Numeric(pwidth) = puls? {
                 let pwidth: u16 = pwidth.numeric_range(
                     ServoControl::PWIDTH_MIN,
                     ServoControl::PWIDTH_MAX,
                     |_| Err(ErrorCode::IllegalParameterValue.into()),
                 )?;
-                servo_pwidth[i] = pwidth;
+                // Too many values
+                *servo_pwidth
+                    .get_mut(num)
+                    .ok_or_else(|| Error::from(ErrorCode::IllegalParameterValue))? = pwidth;
             } else {
                 return Err(ErrorCode::IllegalParameterValue.into());
             }
@@ -63,7 +86,7 @@ impl<'a> Command for BodyServoPwidthAllCommand<'a> {
             Err(ErrorCode::IllegalParameterValue.into())
         } else {
             let mut servos = self.servos.borrow_mut();
-            for (val, mut servo) in servo_pwidth.iter().zip(servos.iter_mut()) {
+            for (val, servo) in servo_pwidth.iter().zip(servos.iter_mut()) {
                 servo.pulse_width = *val;
             }
             Ok(())
@@ -84,6 +107,62 @@ impl<'a> Command for BodyServoPwidthAllCommand<'a> {
     }
 }
 
+/// # `[:BODY]:SERVos:PWIDth:BLOCk <block>`
+/// Set all pulse widths from a definite length block of 24 little-endian
+/// u16 values, e.g. `#248<48 bytes>`. Refused while the emergency stop is
+/// latched.
+///
+/// # `[:BODY]:SERVos:PWIDth:BLOCk?`
+/// Query all pulse widths as a block.
+///
+pub struct BodyServoPwidthBlockCommand<'a> {
+    servos: &'a RefCell<[ServoControl]>,
+}
+
+impl<'a> BodyServoPwidthBlockCommand<'a> {
+    servo_ctrl_new!();
+}
+
+impl<'a> Command for BodyServoPwidthBlockCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        check_estop()?;
+        let data = match args.next_data(false)?.unwrap() {
+            Token::ArbitraryBlockData(data) => data,
+            _ => return Err(ErrorCode::DataTypeError.into()),
+        };
+        if data.len() != 48 {
+            return Err(ErrorCode::IllegalParameterValue.into());
+        }
+        let mut servo_pwidth = [0u16; 24];
+        for (val, bytes) in servo_pwidth.iter_mut().zip(data.chunks_exact(2)) {
+            *val = u16::from_le_bytes([bytes[0], bytes[1]]);
+            if !(ServoControl::PWIDTH_MIN..=ServoControl::PWIDTH_MAX).contains(val) {
+                return Err(ErrorCode::IllegalParameterValue.into());
+            }
+        }
+
+        let mut servos = self.servos.borrow_mut();
+        for (val, servo) in servo_pwidth.iter().zip(servos.iter_mut()) {
+            servo.pulse_width = *val;
+        }
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let servos = self.servos.borrow();
+        let mut data = [0u8; 48];
+        for (bytes, servo) in data.chunks_exact_mut(2).zip(servos.iter()) {
+            bytes.copy_from_slice(&servo.pulse_width.to_le_bytes());
+        }
+        response.data(Arbitrary(&data)).finish()
+    }
+}
+
 pub struct BodyServoPwidthSetCommand<'a> {
     servos: &'a RefCell<[ServoControl]>,
 }
@@ -94,6 +173,7 @@ impl<'a> BodyServoPwidthSetCommand<'a> {
 
 impl<'a> Command for BodyServoPwidthSetCommand<'a> {
     fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        check_estop()?;
         let mut servos = self.servos.borrow_mut();
         let index: usize = args
             .next_data(false)?
@@ -142,7 +222,7 @@ impl<'a> Command for BodyServoStatAllCommand<'a> {
     fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
         let enable: bool = args.next_data(false)?.unwrap().try_into()?;
         let mut servos = self.servos.borrow_mut();
-        for mut servo in servos.iter_mut() {
+        for servo in servos.iter_mut() {
             servo.enable = enable;
         }
         Ok(())
@@ -0,0 +1,124 @@
+use crate::pwm::PwmSettings;
+use crate::serial::BAUD_MIN;
+
+const MAGIC: u32 = 0x4153_4832;
+/// Size of the stored form.
+pub const WORDS: usize = 7;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SerialSettings {
+    pub baud: u32,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+}
+
+impl SerialSettings {
+    pub const fn new() -> Self {
+        SerialSettings {
+            baud: 9600,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+        }
+    }
+
+    /// Characters per second the line carries, with start, parity and stop
+    /// bits.
+    pub fn chars_per_second(&self) -> u32 {
+        let parity = (self.parity != Parity::None) as u32;
+        let stop = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        self.baud / (1 + 8 + parity + stop)
+    }
+}
+
+/// Settings kept across resets, stored by the firmware in backup SRAM.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub serial: SerialSettings,
+    pub pwm: PwmSettings,
+}
+
+impl Settings {
+    pub const fn new() -> Self {
+        Settings {
+            serial: SerialSettings::new(),
+            pwm: PwmSettings::new(),
+        }
+    }
+
+    /// Stored form, with a magic number and checksum.
+    pub fn to_words(&self) -> [u32; WORDS] {
+        let mut words = [
+            MAGIC,
+            self.serial.baud,
+            self.serial.parity as u32,
+            self.serial.stop_bits as u32,
+            self.pwm.frequency as u32,
+            self.pwm.trim.to_bits(),
+            0,
+        ];
+        words[WORDS - 1] = checksum(&words[..WORDS - 1]);
+        words
+    }
+
+    /// Parse the stored form, `None` if nothing valid is stored or the baud
+    /// rate is not between [BAUD_MIN] and `baud_max`.
+    pub fn from_words(words: &[u32; WORDS], baud_max: u32) -> Option<Self> {
+        if words[0] != MAGIC || words[WORDS - 1] != checksum(&words[..WORDS - 1]) {
+            return None;
+        }
+        if !(BAUD_MIN..=baud_max).contains(&words[1]) {
+            return None;
+        }
+        let parity = match words[2] {
+            0 => Parity::None,
+            1 => Parity::Even,
+            2 => Parity::Odd,
+            _ => return None,
+        };
+        let stop_bits = match words[3] {
+            0 => StopBits::One,
+            1 => StopBits::Two,
+            _ => return None,
+        };
+        let pwm = PwmSettings {
+            frequency: words[4] as u16,
+            trim: f32::from_bits(words[5]),
+        };
+        if !(PwmSettings::FREQUENCY_MIN as u32..=PwmSettings::FREQUENCY_MAX as u32)
+            .contains(&words[4])
+            || !(PwmSettings::TRIM_MIN..=PwmSettings::TRIM_MAX).contains(&pwm.trim)
+        {
+            return None;
+        }
+        Some(Settings {
+            serial: SerialSettings {
+                baud: words[1],
+                parity,
+                stop_bits,
+            },
+            pwm,
+        })
+    }
+}
+
+fn checksum(words: &[u32]) -> u32 {
+    words
+        .iter()
+        .fold(0x5A5A_5A5A, |acc, w| acc.rotate_left(5) ^ w)
+}
@@ -0,0 +1,40 @@
+use core::sync::atomic::{AtomicU8, Ordering};
+use scpi::error::Result;
+use scpi::prelude::*;
+use scpi::qonly;
+
+/// Emergency stop is latched.
+pub const STB_ESTOP: u8 = 0x01;
+
+/// Device specific bits (0, 1 and 7) of the status byte.
+static DEVICE_STB: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_stb(bits: u8) {
+    DEVICE_STB.fetch_or(bits, Ordering::SeqCst);
+}
+
+pub fn clear_stb(bits: u8) {
+    DEVICE_STB.fetch_and(!bits, Ordering::SeqCst);
+}
+
+pub fn device_stb() -> u8 {
+    DEVICE_STB.load(Ordering::SeqCst)
+}
+
+/// # `*STB?`
+/// Read the status byte, including the device specific bits.
+///
+pub struct StbCommand;
+
+impl Command for StbCommand {
+    qonly!();
+
+    fn query(
+        &self,
+        context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(context.get_stb() | device_stb()).finish()
+    }
+}
@@ -0,0 +1,847 @@
+use core::cell::RefCell;
+use core::convert::TryInto;
+use core::sync::atomic::Ordering;
+
+use scpi::error::Result;
+use scpi::format::Character;
+use scpi::prelude::*;
+use scpi::tokenizer::Token;
+use scpi::{nquery, qonly};
+
+use crate::port::{Protocol, Session};
+use crate::crash::CrashLog;
+use crate::overload::{self, ProtectionLimits};
+use crate::power::{PowerSettings, PowerState};
+use crate::settings::{Parity, SerialSettings, StopBits};
+use crate::telemetry::{self, Format};
+use crate::{estop, log, serial};
+
+/// # `SYSTem:ESTop`
+/// Trip the emergency stop. All servo outputs are turned fully off and the
+/// servo rail switched off until the latch is released with
+/// `SYSTem:ESTop:RESet`. Targets are cleared, servos stay without pulse
+/// after the release until commanded again.
+///
+/// # `SYSTem:ESTop?`
+/// Query if the emergency stop is latched.
+///
+pub struct SystEstopCommand;
+
+impl Command for SystEstopCommand {
+    fn event(&self, _context: &mut Context, _args: &mut Tokenizer) -> Result<()> {
+        estop::trip();
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(estop::is_latched()).finish()
+    }
+}
+
+/// # `SYSTem:ESTop:RESet`
+/// Release the emergency stop latch.
+///
+/// Fails with an execution error while the emergency stop input is active.
+///
+pub struct SystEstopResetCommand;
+
+impl Command for SystEstopResetCommand {
+    nquery!();
+
+    fn event(&self, _context: &mut Context, _args: &mut Tokenizer) -> Result<()> {
+        if estop::reset() {
+            Ok(())
+        } else {
+            Err(Error::extended(
+                ErrorCode::ExecutionError,
+                b"Emergency stop input active",
+            ))
+        }
+    }
+}
+
+/// # `SYSTem:CRASh?`
+/// Query the panic or HardFault which caused the last reset.
+///
+/// Returns `0` if the last reset was not caused by a crash, otherwise
+/// `<kind>,<message>,<pc>,<lr>,<cfsr>,<hfsr>,<mmfar>,<bfar>` where kind is
+/// `1` for a panic and `2` for a HardFault.
+///
+pub struct SystCrashCommand<'a> {
+    crashes: &'a dyn CrashLog,
+}
+
+impl<'a> SystCrashCommand<'a> {
+    pub fn new(crashes: &'a dyn CrashLog) -> Self {
+        Self { crashes }
+    }
+}
+
+impl<'a> Command for SystCrashCommand<'a> {
+    qonly!();
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        if let Some(record) = self.crashes.last() {
+            response
+                .data(record.kind() as u32)
+                .data(record.message())
+                .data(record.frame[6])
+                .data(record.frame[5])
+                .data(record.cfsr)
+                .data(record.hfsr)
+                .data(record.mmfar)
+                .data(record.bfar)
+                .finish()
+        } else {
+            response.data(0u32).finish()
+        }
+    }
+}
+
+/// # `SYSTem:CRASh:CLEar`
+/// Forget the last crash.
+///
+pub struct SystCrashClearCommand<'a> {
+    crashes: &'a dyn CrashLog,
+}
+
+impl<'a> SystCrashClearCommand<'a> {
+    pub fn new(crashes: &'a dyn CrashLog) -> Self {
+        Self { crashes }
+    }
+}
+
+impl<'a> Command for SystCrashClearCommand<'a> {
+    nquery!();
+
+    fn event(&self, _context: &mut Context, _args: &mut Tokenizer) -> Result<()> {
+        self.crashes.clear();
+        Ok(())
+    }
+}
+
+/// # `SYSTem:LOG[:NEXT]?`
+/// Remove and return the oldest log entry as `<level>,<message>,<value>`.
+///
+/// Returns `NONE,"",0` if the log is empty.
+///
+pub struct SystLogNextCommand;
+
+impl Command for SystLogNextCommand {
+    qonly!();
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        if let Some(entry) = log::next() {
+            response
+                .data(Character(entry.level.name()))
+                .data(entry.message.as_bytes())
+                .data(entry.value)
+                .finish()
+        } else {
+            response
+                .data(Character(b"NONE"))
+                .data(&b""[..])
+                .data(0u32)
+                .finish()
+        }
+    }
+}
+
+/// # `SYSTem:LOG:DROPped?`
+/// Query the number of entries overwritten before they were read.
+///
+pub struct SystLogDroppedCommand;
+
+impl Command for SystLogDroppedCommand {
+    qonly!();
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(log::dropped()).finish()
+    }
+}
+
+/// # `SYSTem:LOG:CLEar`
+/// Remove all log entries.
+///
+pub struct SystLogClearCommand;
+
+impl Command for SystLogClearCommand {
+    nquery!();
+
+    fn event(&self, _context: &mut Context, _args: &mut Tokenizer) -> Result<()> {
+        log::clear();
+        Ok(())
+    }
+}
+
+/// # `SYSTem:COMMunicate:SERial:STATistics?`
+/// Query serial line counters.
+///
+/// Returns `<received>,<overflow>,<overrun>,<framing>,<noise>,<parity>,<other>`.
+///
+pub struct SystCommSerStatCommand;
+
+impl Command for SystCommSerStatCommand {
+    qonly!();
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let stats = &serial::STATS;
+        response
+            .data(stats.received.load(Ordering::Relaxed))
+            .data(stats.overflow.load(Ordering::Relaxed))
+            .data(stats.overrun.load(Ordering::Relaxed))
+            .data(stats.framing.load(Ordering::Relaxed))
+            .data(stats.noise.load(Ordering::Relaxed))
+            .data(stats.parity.load(Ordering::Relaxed))
+            .data(stats.other.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// # `SYSTem:COMMunicate:SERial:BAUD <baud>`
+/// Set the baud rate.
+///
+/// Line settings take effect and are stored once the response to the current
+/// line has been sent. Pressing reset twice within 1.5 s restores 9600 bps,
+/// no parity and one stop bit.
+///
+/// # `SYSTem:COMMunicate:SERial:BAUD?`
+/// Query the baud rate.
+///
+pub struct SystCommSerBaudCommand<'a> {
+    serial: &'a RefCell<SerialSettings>,
+    /// Fastest rate the peripheral clock allows.
+    baud_max: u32,
+}
+
+impl<'a> SystCommSerBaudCommand<'a> {
+    pub fn new(serial: &'a RefCell<SerialSettings>, baud_max: u32) -> Self {
+        Self { serial, baud_max }
+    }
+}
+
+impl<'a> Command for SystCommSerBaudCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let baud: u32 = args.next_data(false)?.unwrap().numeric_range(
+            serial::BAUD_MIN,
+            self.baud_max,
+            |_| Err(ErrorCode::IllegalParameterValue.into()),
+        )?;
+        self.serial.borrow_mut().baud = baud;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(self.serial.borrow().baud).finish()
+    }
+}
+
+/// # `SYSTem:COMMunicate:SERial:PARity NONE|EVEN|ODD`
+/// Set the parity, see `SYSTem:COMMunicate:SERial:BAUD`.
+///
+/// # `SYSTem:COMMunicate:SERial:PARity?`
+/// Query the parity.
+///
+pub struct SystCommSerParCommand<'a> {
+    serial: &'a RefCell<SerialSettings>,
+}
+
+impl<'a> SystCommSerParCommand<'a> {
+    pub fn new(serial: &'a RefCell<SerialSettings>) -> Self {
+        Self { serial }
+    }
+}
+
+impl<'a> Command for SystCommSerParCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let parity = match args.next_data(false)?.unwrap() {
+            Token::CharacterProgramData(s) if s.eq_ignore_ascii_case(b"NONE") => Parity::None,
+            Token::CharacterProgramData(s) if s.eq_ignore_ascii_case(b"EVEN") => Parity::Even,
+            Token::CharacterProgramData(s) if s.eq_ignore_ascii_case(b"ODD") => Parity::Odd,
+            _ => return Err(ErrorCode::IllegalParameterValue.into()),
+        };
+        self.serial.borrow_mut().parity = parity;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let name: &[u8] = match self.serial.borrow().parity {
+            Parity::None => b"NONE",
+            Parity::Even => b"EVEN",
+            Parity::Odd => b"ODD",
+        };
+        response.data(Character(name)).finish()
+    }
+}
+
+/// # `SYSTem:COMMunicate:SERial:SBITs 1|2`
+/// Set the number of stop bits, see `SYSTem:COMMunicate:SERial:BAUD`.
+///
+/// # `SYSTem:COMMunicate:SERial:SBITs?`
+/// Query the number of stop bits.
+///
+pub struct SystCommSerSbitCommand<'a> {
+    serial: &'a RefCell<SerialSettings>,
+}
+
+impl<'a> SystCommSerSbitCommand<'a> {
+    pub fn new(serial: &'a RefCell<SerialSettings>) -> Self {
+        Self { serial }
+    }
+}
+
+impl<'a> Command for SystCommSerSbitCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let bits: u8 = args
+            .next_data(false)?
+            .unwrap()
+            .numeric_range(1, 2, |_| Err(ErrorCode::IllegalParameterValue.into()))?;
+        self.serial.borrow_mut().stop_bits = if bits == 2 {
+            StopBits::Two
+        } else {
+            StopBits::One
+        };
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let bits = match self.serial.borrow().stop_bits {
+            StopBits::One => 1u8,
+            StopBits::Two => 2u8,
+        };
+        response.data(bits).finish()
+    }
+}
+
+/// # `SYSTem:COMMunicate:VERBose <boolean>`
+/// Reply to every failing line with its error as `<code>,"<message>"`, in
+/// the same format as `SYSTem:ERRor:NEXT?`. The error is still queued.
+///
+/// Applies to the interface the command was received on.
+///
+/// # `SYSTem:COMMunicate:VERBose?`
+/// Query if verbose mode is enabled.
+///
+pub struct SystCommVerbCommand<'a> {
+    session: &'a RefCell<Session>,
+}
+
+impl<'a> SystCommVerbCommand<'a> {
+    pub fn new(session: &'a RefCell<Session>) -> Self {
+        Self { session }
+    }
+}
+
+impl<'a> Command for SystCommVerbCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        self.session.borrow_mut().verbose = args.next_data(false)?.unwrap().try_into()?;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(self.session.borrow().verbose).finish()
+    }
+}
+
+/// # `SYSTem:COMMunicate:PROTocol SCPI|FRAMed|ROS`
+/// Select the transport of the interface the command was received on.
+///
+/// `FRAMed` switches to COBS framed binary packets with CRC-16 after the
+/// current line, see [crate::protocol]. A `Scpi` packet switches back.
+///
+/// `ROS` switches to rosserial for a ROS agent, see [crate::ros_bridge].
+/// The agent stopping switches back.
+///
+/// # `SYSTem:COMMunicate:PROTocol?`
+/// Query the transport, always `SCPI` when asked over SCPI.
+///
+pub struct SystCommProtCommand<'a> {
+    session: &'a RefCell<Session>,
+}
+
+impl<'a> SystCommProtCommand<'a> {
+    pub fn new(session: &'a RefCell<Session>) -> Self {
+        Self { session }
+    }
+}
+
+impl<'a> Command for SystCommProtCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let protocol = match args.next_data(false)?.unwrap() {
+            Token::CharacterProgramData(s) if s.eq_ignore_ascii_case(b"SCPI") => Protocol::Scpi,
+            Token::CharacterProgramData(s)
+                if s.eq_ignore_ascii_case(b"FRAM") || s.eq_ignore_ascii_case(b"FRAMED") =>
+            {
+                Protocol::Framed
+            }
+            Token::CharacterProgramData(s) if s.eq_ignore_ascii_case(b"ROS") => Protocol::Ros,
+            _ => return Err(ErrorCode::IllegalParameterValue.into()),
+        };
+        self.session.borrow_mut().protocol = protocol;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(Character(b"SCPI")).finish()
+    }
+}
+
+/// # `SYSTem:TELemetry:RATE <Hz>`
+/// Push telemetry records on the interface the command was received on,
+/// at most 100 per second. 0 disables telemetry. On USART2 records are
+/// held back while the previous one is still on the line, so the rate
+/// drops to what the baud rate carries.
+///
+/// # `SYSTem:TELemetry:RATE?`
+/// Query the telemetry rate.
+///
+pub struct SystTelRateCommand<'a> {
+    session: &'a RefCell<Session>,
+}
+
+impl<'a> SystTelRateCommand<'a> {
+    pub fn new(session: &'a RefCell<Session>) -> Self {
+        Self { session }
+    }
+}
+
+impl<'a> Command for SystTelRateCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        self.session.borrow_mut().telemetry.rate =
+            args.next_data(false)?
+                .unwrap()
+                .numeric_range(0, telemetry::RATE_MAX, |_| {
+                    Err(ErrorCode::IllegalParameterValue.into())
+                })?;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(self.session.borrow().telemetry.rate).finish()
+    }
+}
+
+/// # `SYSTem:TELemetry:MASK <mask>`
+/// Select telemetry fields, a sum of
+/// * 1 - servo targets
+/// * 2 - servo outputs
+/// * 4 - battery voltage
+/// * 8 - loop timing
+/// * 16 - error counters and emergency stop
+/// * 32 - gait phase, -1 while no gait runs
+///
+/// # `SYSTem:TELemetry:MASK?`
+/// Query the selected fields.
+///
+pub struct SystTelMaskCommand<'a> {
+    session: &'a RefCell<Session>,
+}
+
+impl<'a> SystTelMaskCommand<'a> {
+    pub fn new(session: &'a RefCell<Session>) -> Self {
+        Self { session }
+    }
+}
+
+impl<'a> Command for SystTelMaskCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        self.session.borrow_mut().telemetry.mask =
+            args.next_data(false)?
+                .unwrap()
+                .numeric_range(0, telemetry::FIELD_ALL, |_| {
+                    Err(ErrorCode::IllegalParameterValue.into())
+                })?;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(self.session.borrow().telemetry.mask).finish()
+    }
+}
+
+/// # `SYSTem:TELemetry:FORMat CSV|BINary`
+/// Select `TLM <time>,<fields...>` text lines or framed binary records,
+/// see [crate::telemetry].
+///
+/// # `SYSTem:TELemetry:FORMat?`
+/// Query the telemetry format.
+///
+pub struct SystTelFormCommand<'a> {
+    session: &'a RefCell<Session>,
+}
+
+impl<'a> SystTelFormCommand<'a> {
+    pub fn new(session: &'a RefCell<Session>) -> Self {
+        Self { session }
+    }
+}
+
+impl<'a> Command for SystTelFormCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let format = match args.next_data(false)?.unwrap() {
+            Token::CharacterProgramData(s) if s.eq_ignore_ascii_case(b"CSV") => Format::Csv,
+            Token::CharacterProgramData(s)
+                if s.eq_ignore_ascii_case(b"BIN") || s.eq_ignore_ascii_case(b"BINARY") =>
+            {
+                Format::Binary
+            }
+            _ => return Err(ErrorCode::IllegalParameterValue.into()),
+        };
+        self.session.borrow_mut().telemetry.format = format;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let name: &[u8] = match self.session.borrow().telemetry.format {
+            Format::Csv => b"CSV",
+            Format::Binary => b"BIN",
+        };
+        response.data(Character(name)).finish()
+    }
+}
+
+/// # `SYSTem:SERVos:PROTection:CURRent <amps>`
+/// Set the rail current a servo bank may draw, at most 30 A. When a bank is
+/// above the limit for half a second its legs are switched off one at a time
+/// and the leg that brings the current down trips. The whole bank is turned
+/// off only if no single leg does.
+///
+/// # `SYSTem:SERVos:PROTection:CURRent?`
+/// Query the current limit in amperes.
+///
+pub struct SystServProtCurrCommand<'a> {
+    limits: &'a RefCell<ProtectionLimits>,
+}
+
+impl<'a> SystServProtCurrCommand<'a> {
+    pub fn new(limits: &'a RefCell<ProtectionLimits>) -> Self {
+        Self { limits }
+    }
+}
+
+impl<'a> Command for SystServProtCurrCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let amps: f32 = args.next_data(false)?.unwrap().try_into()?;
+        let milliamps = amps * 1000.0;
+        if !(0.0..=ProtectionLimits::MILLIAMPS_MAX as f32).contains(&milliamps) {
+            return Err(ErrorCode::DataOutOfRange.into());
+        }
+        self.limits.borrow_mut().milliamps = milliamps as u32;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response
+            .data(self.limits.borrow().milliamps as f32 / 1000.0)
+            .finish()
+    }
+}
+
+/// # `SYSTem:SERVos:PROTection:TEMPerature <celsius>`
+/// Set the board temperature at which all servos are turned fully off, at
+/// most 100 °C.
+///
+/// # `SYSTem:SERVos:PROTection:TEMPerature?`
+/// Query the temperature limit in °C.
+///
+pub struct SystServProtTempCommand<'a> {
+    limits: &'a RefCell<ProtectionLimits>,
+}
+
+impl<'a> SystServProtTempCommand<'a> {
+    pub fn new(limits: &'a RefCell<ProtectionLimits>) -> Self {
+        Self { limits }
+    }
+}
+
+impl<'a> Command for SystServProtTempCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let celsius: f32 = args.next_data(false)?.unwrap().try_into()?;
+        if !(0.0..=ProtectionLimits::CELSIUS_MAX).contains(&celsius) {
+            return Err(ErrorCode::DataOutOfRange.into());
+        }
+        self.limits.borrow_mut().celsius = celsius;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(self.limits.borrow().celsius).finish()
+    }
+}
+
+/// # `SYSTem:SERVos:PROTection:TRIPped?`
+/// Query which servo banks are held off by the protection, `1` or `0` per
+/// bank. Banks only trip when the board overheats or no single leg draws the
+/// overload current.
+///
+pub struct SystServProtTripCommand;
+
+impl Command for SystServProtTripCommand {
+    qonly!();
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        for tripped in overload::tripped().iter() {
+            response.data(*tripped);
+        }
+        response.finish()
+    }
+}
+
+/// # `SYSTem:SERVos:PROTection:TRIPped:LEG?`
+/// Query which legs are held off by the protection, `1` or `0` per leg. A
+/// bank over the current limit trips the leg found to draw it, see
+/// [overload::Protection].
+///
+pub struct SystServProtTripLegCommand;
+
+impl Command for SystServProtTripLegCommand {
+    qonly!();
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        for tripped in overload::tripped_legs().iter() {
+            response.data(*tripped);
+        }
+        response.finish()
+    }
+}
+
+/// # `SYSTem:SERVos:PROTection:CLEar`
+/// Release legs and banks held off by the protection. They trip again if
+/// still overloaded.
+///
+pub struct SystServProtClearCommand;
+
+impl Command for SystServProtClearCommand {
+    nquery!();
+
+    fn event(&self, _context: &mut Context, _args: &mut Tokenizer) -> Result<()> {
+        overload::clear();
+        Ok(())
+    }
+}
+
+/// # `SYSTem:SERVos:POWer <bool>`
+/// Switch the servo power rail. Powering up releases the legs one at a
+/// time, see `SYSTem:SERVos:POWer:STAGger`. Off at boot.
+///
+/// # `SYSTem:SERVos:POWer?`
+/// Query if servo power is requested.
+///
+pub struct SystServPowCommand<'a> {
+    power: &'a RefCell<PowerSettings>,
+}
+
+impl<'a> SystServPowCommand<'a> {
+    pub fn new(power: &'a RefCell<PowerSettings>) -> Self {
+        Self { power }
+    }
+}
+
+impl<'a> Command for SystServPowCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        self.power.borrow_mut().on = args.next_data(false)?.unwrap().try_into()?;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(self.power.borrow().on).finish()
+    }
+}
+
+/// # `SYSTem:SERVos:POWer:STAGger <ms>`
+/// Set the delay between legs when powering up, at most 1000 ms. 0 powers
+/// all legs at once.
+///
+/// # `SYSTem:SERVos:POWer:STAGger?`
+/// Query the delay in ms.
+///
+pub struct SystServPowStagCommand<'a> {
+    power: &'a RefCell<PowerSettings>,
+}
+
+impl<'a> SystServPowStagCommand<'a> {
+    pub fn new(power: &'a RefCell<PowerSettings>) -> Self {
+        Self { power }
+    }
+}
+
+impl<'a> Command for SystServPowStagCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        let ms: u32 = args.next_data(false)?.unwrap().numeric_range(
+            0,
+            PowerSettings::STAGGER_MAX_MS,
+            |_| Err(ErrorCode::IllegalParameterValue.into()),
+        )?;
+        self.power.borrow_mut().stagger_ms = ms;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(self.power.borrow().stagger_ms).finish()
+    }
+}
+
+/// # `SYSTem:SERVos:POWer:STATe?`
+/// Query the power sequence, `OFF`, `ARMing` while safe pulse widths are
+/// written, `STARting` while legs are released or `ON`.
+///
+pub struct SystServPowStatCommand<'a> {
+    state: &'a RefCell<PowerState>,
+}
+
+impl<'a> SystServPowStatCommand<'a> {
+    pub fn new(state: &'a RefCell<PowerState>) -> Self {
+        Self { state }
+    }
+}
+
+impl<'a> Command for SystServPowStatCommand<'a> {
+    qonly!();
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        let name: &[u8] = match *self.state.borrow() {
+            PowerState::Off => b"OFF",
+            PowerState::Arming => b"ARM",
+            PowerState::Starting(_) => b"STAR",
+            PowerState::On => b"ON",
+        };
+        response.data(Character(name)).finish()
+    }
+}
+
+/// # `SYSTem:CONTact:FITTed <mask>`
+/// Select the legs with a foot contact sensor fitted, bit 0 for leg 1. The
+/// others read as unknown, an open switch looks the same as a missing one.
+/// None at boot.
+///
+/// # `SYSTem:CONTact:FITTed?`
+/// Query the legs with a sensor fitted.
+///
+pub struct SystContFittCommand<'a> {
+    fitted: &'a RefCell<u8>,
+}
+
+impl<'a> SystContFittCommand<'a> {
+    pub fn new(fitted: &'a RefCell<u8>) -> Self {
+        Self { fitted }
+    }
+}
+
+impl<'a> Command for SystContFittCommand<'a> {
+    fn event(&self, _context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+        *self.fitted.borrow_mut() = args
+            .next_data(false)?
+            .unwrap()
+            .numeric_range(0, u8::MAX, |_| Err(ErrorCode::IllegalParameterValue.into()))?;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        _context: &mut Context,
+        _args: &mut Tokenizer,
+        response: &mut ResponseUnit,
+    ) -> Result<()> {
+        response.data(*self.fitted.borrow()).finish()
+    }
+}
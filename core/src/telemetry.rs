@@ -0,0 +1,188 @@
+use arrayvec::ArrayVec;
+use core::fmt::Write;
+
+use crate::protocol::{encode_frame, KIND_TELEMETRY_RECORD, MAX_FRAME, SERVOS};
+
+/// Commanded servo pulse widths.
+pub const FIELD_TARGETS: u8 = 0x01;
+/// Pulse widths last written to the controllers, zero while off.
+pub const FIELD_OUTPUTS: u8 = 0x02;
+/// Battery voltage in mV.
+pub const FIELD_BATTERY: u8 = 0x04;
+/// Last and longest main loop time in µs.
+pub const FIELD_TIMING: u8 = 0x08;
+/// I2C and serial error totals and the emergency stop latch.
+pub const FIELD_ERRORS: u8 = 0x10;
+/// Gait phase in thousandths of a cycle, -1 while no gait runs.
+pub const FIELD_GAIT: u8 = 0x20;
+pub const FIELD_ALL: u8 = 0x3F;
+
+/// Highest supported record rate.
+pub const RATE_MAX: u16 = 100;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    /// `TLM <time>,<field>,...` text lines.
+    Csv,
+    /// Framed records of kind [KIND_TELEMETRY_RECORD].
+    Binary,
+}
+
+/// Telemetry settings of an interface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TelemetryConfig {
+    /// Records per second, 0 disables telemetry.
+    pub rate: u16,
+    pub mask: u8,
+    pub format: Format,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        TelemetryConfig {
+            rate: 0,
+            mask: FIELD_ALL,
+            format: Format::Csv,
+        }
+    }
+}
+
+/// State of the carrier at one instant.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Snapshot {
+    pub time_ms: u32,
+    pub targets: [u16; SERVOS],
+    pub outputs: [u16; SERVOS],
+    pub battery_mv: u32,
+    pub loop_us: u32,
+    pub loop_max_us: u32,
+    pub i2c_errors: u32,
+    pub serial_errors: u32,
+    pub estop: bool,
+    /// Thousandths of the gait cycle, `None` while no gait runs.
+    pub gait_phase: Option<u16>,
+}
+
+/// Schedules records at the configured rate.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Scheduler {
+    next_ms: u32,
+    seq: u8,
+}
+
+impl Scheduler {
+    /// True if a record is due at `now_ms`.
+    pub fn due(&mut self, config: &TelemetryConfig, now_ms: u32) -> bool {
+        if config.rate == 0 || (now_ms.wrapping_sub(self.next_ms) as i32) < 0 {
+            return false;
+        }
+        let period = 1000 / config.rate.min(RATE_MAX) as u32;
+        self.next_ms = self.next_ms.wrapping_add(period);
+        // Do not try to catch up after a stall
+        if (now_ms.wrapping_sub(self.next_ms) as i32) >= 0 {
+            self.next_ms = now_ms.wrapping_add(period);
+        }
+        true
+    }
+
+    /// Hold the next record back until `len` bytes written at `now_ms` have
+    /// left a line carrying `chars_per_second`.
+    pub fn sent(&mut self, now_ms: u32, len: usize, chars_per_second: u32) {
+        let busy_ms = (len as u32 * 1000).div_ceil(chars_per_second.max(1));
+        let free_ms = now_ms.wrapping_add(busy_ms);
+        if (free_ms.wrapping_sub(self.next_ms) as i32) > 0 {
+            self.next_ms = free_ms;
+        }
+    }
+
+    fn next_seq(&mut self) -> u8 {
+        self.seq = self.seq.wrapping_add(1);
+        self.seq
+    }
+}
+
+/// Text record buffer, large enough for every field.
+pub type CsvRecord = arrayvec::ArrayString<[u8; 512]>;
+
+/// Format a `TLM` text line with the fields selected by `mask`.
+pub fn write_csv(snapshot: &Snapshot, mask: u8, out: &mut CsvRecord) {
+    write!(out, "TLM {}", snapshot.time_ms).ok();
+    if mask & FIELD_TARGETS != 0 {
+        for t in snapshot.targets.iter() {
+            write!(out, ",{}", t).ok();
+        }
+    }
+    if mask & FIELD_OUTPUTS != 0 {
+        for o in snapshot.outputs.iter() {
+            write!(out, ",{}", o).ok();
+        }
+    }
+    if mask & FIELD_BATTERY != 0 {
+        write!(out, ",{}", snapshot.battery_mv).ok();
+    }
+    if mask & FIELD_TIMING != 0 {
+        write!(out, ",{},{}", snapshot.loop_us, snapshot.loop_max_us).ok();
+    }
+    if mask & FIELD_ERRORS != 0 {
+        write!(
+            out,
+            ",{},{},{}",
+            snapshot.i2c_errors, snapshot.serial_errors, snapshot.estop as u8
+        )
+        .ok();
+    }
+    if mask & FIELD_GAIT != 0 {
+        match snapshot.gait_phase {
+            Some(phase) => write!(out, ",{}", phase).ok(),
+            None => write!(out, ",-1").ok(),
+        };
+    }
+    out.push('\n');
+}
+
+/// Encode a binary record with the fields selected by `mask`.
+///
+/// The payload is `<mask> <time u32>` followed by the selected fields in bit
+/// order, pulse widths and the gait phase as u16 and everything else as
+/// u32, the latch as u8. A gait phase of 0xFFFF means no gait runs.
+pub fn write_binary(
+    snapshot: &Snapshot,
+    mask: u8,
+    scheduler: &mut Scheduler,
+    frame: &mut [u8; MAX_FRAME],
+) -> usize {
+    let mut payload = ArrayVec::<[u8; 128]>::new();
+    let mut put = |bytes: &[u8]| {
+        for b in bytes {
+            payload.try_push(*b).ok();
+        }
+    };
+    put(&[mask]);
+    put(&snapshot.time_ms.to_le_bytes());
+    if mask & FIELD_TARGETS != 0 {
+        for t in snapshot.targets.iter() {
+            put(&t.to_le_bytes());
+        }
+    }
+    if mask & FIELD_OUTPUTS != 0 {
+        for o in snapshot.outputs.iter() {
+            put(&o.to_le_bytes());
+        }
+    }
+    if mask & FIELD_BATTERY != 0 {
+        put(&snapshot.battery_mv.to_le_bytes());
+    }
+    if mask & FIELD_TIMING != 0 {
+        put(&snapshot.loop_us.to_le_bytes());
+        put(&snapshot.loop_max_us.to_le_bytes());
+    }
+    if mask & FIELD_ERRORS != 0 {
+        put(&snapshot.i2c_errors.to_le_bytes());
+        put(&snapshot.serial_errors.to_le_bytes());
+        put(&[snapshot.estop as u8]);
+    }
+    if mask & FIELD_GAIT != 0 {
+        put(&snapshot.gait_phase.unwrap_or(0xFFFF).to_le_bytes());
+    }
+    encode_frame(scheduler.next_seq(), KIND_TELEMETRY_RECORD, &payload, frame)
+}
@@ -0,0 +1,684 @@
+//! The command tree, shared by the firmware and the simulator.
+//!
+//! [State] holds what the commands act on, [Commands] the command handlers
+//! borrowing it and [carrier_tree!](crate::carrier_tree) builds the tree
+//! from them.
+
+use core::cell::RefCell;
+use nalgebra::{Rotation3, Translation3};
+use scpi::prelude::*;
+
+use crate::attitude::Attitude;
+use crate::body::{self, Velocity};
+use crate::body_commands::*;
+use crate::contact::DescentSettings;
+use crate::crash::CrashLog;
+use crate::diag_commands::*;
+use crate::framed::FramedHandler;
+use crate::level::LevelSettings;
+use crate::measure_commands::*;
+use crate::overload::{ProtectionLimits, ServoLoad};
+use crate::port::Session;
+use crate::power::{PowerSettings, PowerState};
+use crate::pwm::PwmSettings;
+use crate::ros_bridge::RosBridge;
+use crate::servo_bus::BusHealth;
+use crate::servo_commands::*;
+use crate::settings::SerialSettings;
+use crate::system_commands::*;
+
+pub const SERVOS: usize = 24;
+/// Three servos per leg.
+pub const LEGS: usize = SERVOS / 3;
+/// PCA9685 controllers, even servos on the first and odd on the second.
+pub const BANKS: usize = 2;
+
+pub struct CarrierDevice;
+
+impl Device for CarrierDevice {
+    fn cls(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn rst(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// State shared by the commands and the other transports.
+pub struct State {
+    pub servos: RefCell<[ServoControl; SERVOS]>,
+    pub rotation: RefCell<Rotation3<f32>>,
+    pub translation: RefCell<Translation3<f32>>,
+    pub velocity: RefCell<Velocity>,
+    /// Measured attitude, None without a working IMU.
+    pub attitude: RefCell<Option<Attitude>>,
+    pub level: RefCell<LevelSettings>,
+    /// Levelling correction added to the commanded rotation.
+    pub level_correction: RefCell<Attitude>,
+    /// Foot contact per leg, None without a working sensor.
+    pub contacts: RefCell<[Option<bool>; LEGS]>,
+    /// Legs with a contact sensor fitted, bit per leg.
+    pub contacts_fitted: RefCell<u8>,
+    pub descent: RefCell<DescentSettings>,
+    /// Servo rail currents and board temperature.
+    pub servo_load: RefCell<ServoLoad>,
+    pub protection: RefCell<ProtectionLimits>,
+    pub power: RefCell<PowerSettings>,
+    /// Where the power sequence is, reported by the firmware.
+    pub power_state: RefCell<PowerState>,
+    pub bus_health: RefCell<[BusHealth; BANKS]>,
+    /// Requested PWM frequency and calibration, applied by the firmware.
+    pub pwm: RefCell<PwmSettings>,
+    pub session: RefCell<Session>,
+    /// Requested line settings, applied by the firmware once idle.
+    pub serial: RefCell<SerialSettings>,
+}
+
+impl State {
+    pub fn new(serial: SerialSettings) -> Self {
+        State {
+            servos: RefCell::new([ServoControl::new(); SERVOS]),
+            rotation: RefCell::new(Rotation3::identity()),
+            translation: RefCell::new(Translation3::new(0.0, 0.0, 0.0)),
+            velocity: RefCell::new(Velocity::zero()),
+            attitude: RefCell::new(None),
+            level: RefCell::new(LevelSettings::new()),
+            level_correction: RefCell::new(Attitude::default()),
+            contacts: RefCell::new([None; LEGS]),
+            contacts_fitted: RefCell::new(0),
+            descent: RefCell::new(DescentSettings::new()),
+            servo_load: RefCell::new(ServoLoad::default()),
+            protection: RefCell::new(ProtectionLimits::new()),
+            power: RefCell::new(PowerSettings::new()),
+            power_state: RefCell::new(PowerState::Off),
+            bus_health: RefCell::new([BusHealth::default(); BANKS]),
+            pwm: RefCell::new(PwmSettings::new()),
+            session: RefCell::new(Session::default()),
+            serial: RefCell::new(serial),
+        }
+    }
+
+    pub fn framed_handler(&self) -> FramedHandler<'_> {
+        FramedHandler::new(&self.servos, &self.rotation, &self.translation)
+    }
+
+    pub fn ros_bridge(&self) -> RosBridge<'_> {
+        RosBridge::new(&self.servos, &self.velocity)
+    }
+
+    /// Cancel motion after an emergency stop. Targets are cleared to no
+    /// pulse, so nothing moves when the stop is released until new targets
+    /// are commanded.
+    pub fn stop(&self) {
+        for servo in self.servos.borrow_mut().iter_mut() {
+            servo.pulse_width = 0;
+        }
+        self.velocity.replace(Velocity::zero());
+    }
+
+    /// Commanded pulse widths, with the body rotation and the levelling
+    /// correction applied through the foot heights.
+    pub fn targets(&self) -> [u16; SERVOS] {
+        let mut targets = [0u16; SERVOS];
+        for (t, s) in targets.iter_mut().zip(self.servos.borrow().iter()) {
+            *t = s.pulse_width;
+        }
+        let (roll, pitch, _) = self.rotation.borrow().euler_angles();
+        let correction = *self.level_correction.borrow();
+        body::tilt(
+            &mut targets,
+            &Attitude {
+                roll: roll + correction.roll,
+                pitch: pitch + correction.pitch,
+            },
+        );
+        targets
+    }
+}
+
+/// Handlers of the commands which need state.
+pub struct Commands<'a> {
+    pub body_att_rot: BodyAttRotCommand<'a>,
+    pub body_att_tran: BodyAttTranCommand<'a>,
+    pub body_vel: BodyVelCommand<'a>,
+    pub body_lev: BodyLevCommand<'a>,
+    pub body_lev_gain: BodyLevGainCommand<'a>,
+    pub body_lev_lim: BodyLevLimCommand<'a>,
+    pub body_lev_corr: BodyLevCorrCommand<'a>,
+    pub body_gait_adap: BodyGaitAdapCommand<'a>,
+    pub body_gait_adap_depth: BodyGaitAdapDepthCommand<'a>,
+    pub servo_pwidth_all: BodyServoPwidthAllCommand<'a>,
+    pub servo_pwidth_set: BodyServoPwidthSetCommand<'a>,
+    pub servo_pwidth_block: BodyServoPwidthBlockCommand<'a>,
+    pub servo_stat_all: BodyServoStatAllCommand<'a>,
+    pub servo_stat_set: BodyServoStatSetCommand<'a>,
+    pub diag_i2c_errors: DiagI2cErrorsCommand<'a>,
+    pub diag_pwm_freq: DiagPwmFreqCommand<'a>,
+    pub diag_pwm_trim: DiagPwmTrimCommand<'a>,
+    pub meas_att: MeasAttCommand<'a>,
+    pub meas_leg_cont: MeasLegContCommand<'a>,
+    pub meas_serv_curr: MeasServCurrCommand<'a>,
+    pub meas_temp: MeasTempCommand<'a>,
+    pub syst_comm_verb: SystCommVerbCommand<'a>,
+    pub syst_comm_prot: SystCommProtCommand<'a>,
+    pub syst_comm_ser_baud: SystCommSerBaudCommand<'a>,
+    pub syst_comm_ser_par: SystCommSerParCommand<'a>,
+    pub syst_comm_ser_sbit: SystCommSerSbitCommand<'a>,
+    pub syst_tel_rate: SystTelRateCommand<'a>,
+    pub syst_tel_mask: SystTelMaskCommand<'a>,
+    pub syst_tel_form: SystTelFormCommand<'a>,
+    pub syst_crash: SystCrashCommand<'a>,
+    pub syst_crash_clear: SystCrashClearCommand<'a>,
+    pub syst_serv_prot_curr: SystServProtCurrCommand<'a>,
+    pub syst_serv_prot_temp: SystServProtTempCommand<'a>,
+    pub syst_serv_pow: SystServPowCommand<'a>,
+    pub syst_serv_pow_stag: SystServPowStagCommand<'a>,
+    pub syst_serv_pow_stat: SystServPowStatCommand<'a>,
+    pub syst_cont_fitt: SystContFittCommand<'a>,
+}
+
+impl<'a> Commands<'a> {
+    /// `baud_max` is the highest baud rate the serial port can reach.
+    pub fn new(state: &'a State, crashes: &'a dyn CrashLog, baud_max: u32) -> Self {
+        Commands {
+            body_att_rot: BodyAttRotCommand::new(&state.rotation),
+            body_att_tran: BodyAttTranCommand::new(&state.translation),
+            body_vel: BodyVelCommand::new(&state.velocity),
+            body_lev: BodyLevCommand::new(&state.level),
+            body_lev_gain: BodyLevGainCommand::new(&state.level),
+            body_lev_lim: BodyLevLimCommand::new(&state.level),
+            body_lev_corr: BodyLevCorrCommand::new(&state.level_correction),
+            body_gait_adap: BodyGaitAdapCommand::new(&state.descent),
+            body_gait_adap_depth: BodyGaitAdapDepthCommand::new(&state.descent),
+            servo_pwidth_all: BodyServoPwidthAllCommand::new(&state.servos),
+            servo_pwidth_set: BodyServoPwidthSetCommand::new(&state.servos),
+            servo_pwidth_block: BodyServoPwidthBlockCommand::new(&state.servos),
+            servo_stat_all: BodyServoStatAllCommand::new(&state.servos),
+            servo_stat_set: BodyServoStatSetCommand::new(&state.servos),
+            diag_i2c_errors: DiagI2cErrorsCommand::new(&state.bus_health),
+            diag_pwm_freq: DiagPwmFreqCommand::new(&state.pwm),
+            diag_pwm_trim: DiagPwmTrimCommand::new(&state.pwm),
+            meas_att: MeasAttCommand::new(&state.attitude),
+            meas_leg_cont: MeasLegContCommand::new(&state.contacts),
+            meas_serv_curr: MeasServCurrCommand::new(&state.servo_load),
+            meas_temp: MeasTempCommand::new(&state.servo_load),
+            syst_comm_verb: SystCommVerbCommand::new(&state.session),
+            syst_comm_prot: SystCommProtCommand::new(&state.session),
+            syst_comm_ser_baud: SystCommSerBaudCommand::new(&state.serial, baud_max),
+            syst_comm_ser_par: SystCommSerParCommand::new(&state.serial),
+            syst_comm_ser_sbit: SystCommSerSbitCommand::new(&state.serial),
+            syst_tel_rate: SystTelRateCommand::new(&state.session),
+            syst_tel_mask: SystTelMaskCommand::new(&state.session),
+            syst_tel_form: SystTelFormCommand::new(&state.session),
+            syst_crash: SystCrashCommand::new(crashes),
+            syst_crash_clear: SystCrashClearCommand::new(crashes),
+            syst_serv_prot_curr: SystServProtCurrCommand::new(&state.protection),
+            syst_serv_prot_temp: SystServProtTempCommand::new(&state.protection),
+            syst_serv_pow: SystServPowCommand::new(&state.power),
+            syst_serv_pow_stag: SystServPowStagCommand::new(&state.power),
+            syst_serv_pow_stat: SystServPowStatCommand::new(&state.power_state),
+            syst_cont_fitt: SystContFittCommand::new(&state.contacts_fitted),
+        }
+    }
+}
+
+/// Build the command tree from a [Commands](crate::tree::Commands).
+///
+/// ```ignore
+/// let tree = carrier_tree!(commands, GIT_VERSION);
+/// ```
+///
+/// The scpi macros used need `scpi::prelude::*`, `scpi::ieee488::commands::*`
+/// and `scpi::scpi::commands::*` in scope.
+#[macro_export]
+macro_rules! carrier_tree {
+    ($commands:ident, $firmware:expr) => {
+        $crate::scpi::scpi_tree![
+            // Create default IEEE488 mandated commands
+            $crate::scpi::ieee488_idn!(b"GPA-Robotics", b"ash-carrier", b"0", $firmware),
+            $crate::scpi::ieee488_cls!(),
+            $crate::scpi::ieee488_ese!(),
+            $crate::scpi::ieee488_esr!(),
+            $crate::scpi::ieee488_opc!(),
+            $crate::scpi::ieee488_rst!(),
+            $crate::scpi::ieee488_sre!(),
+            Node {
+                name: b"*STB",
+                optional: false,
+                handler: Some(&$crate::status::StbCommand),
+                sub: &[]
+            },
+            $crate::scpi::ieee488_tst!(),
+            $crate::scpi::ieee488_wai!(),
+            // Create default SCPI mandated STATus subsystem
+            $crate::scpi::scpi_status!(),
+            // Create default SCPI mandated SYSTem subsystem
+            $crate::scpi::scpi_system!(
+                Node {
+                    name: b"ESTop",
+                    optional: false,
+                    handler: Some(&$crate::system_commands::SystEstopCommand),
+                    sub: &[
+                        Node {
+                            name: b"RESet",
+                            optional: false,
+                            handler: Some(&$crate::system_commands::SystEstopResetCommand),
+                            sub: &[]
+                        },
+                    ]
+                },
+                Node {
+                    name: b"COMMunicate",
+                    optional: false,
+                    handler: None,
+                    sub: &[
+                        Node {
+                            name: b"VERBose",
+                            optional: false,
+                            handler: Some(&$commands.syst_comm_verb),
+                            sub: &[]
+                        },
+                        Node {
+                            name: b"PROTocol",
+                            optional: false,
+                            handler: Some(&$commands.syst_comm_prot),
+                            sub: &[]
+                        },
+                        Node {
+                            name: b"SERial",
+                            optional: false,
+                            handler: None,
+                            sub: &[
+                                Node {
+                                    name: b"BAUD",
+                                    optional: false,
+                                    handler: Some(&$commands.syst_comm_ser_baud),
+                                    sub: &[]
+                                },
+                                Node {
+                                    name: b"PARity",
+                                    optional: false,
+                                    handler: Some(&$commands.syst_comm_ser_par),
+                                    sub: &[]
+                                },
+                                Node {
+                                    name: b"SBITs",
+                                    optional: false,
+                                    handler: Some(&$commands.syst_comm_ser_sbit),
+                                    sub: &[]
+                                },
+                                Node {
+                                    name: b"STATistics",
+                                    optional: false,
+                                    handler: Some(&$crate::system_commands::SystCommSerStatCommand),
+                                    sub: &[]
+                                },
+                            ]
+                        },
+                    ]
+                },
+                Node {
+                    name: b"TELemetry",
+                    optional: false,
+                    handler: None,
+                    sub: &[
+                        Node {
+                            name: b"RATE",
+                            optional: false,
+                            handler: Some(&$commands.syst_tel_rate),
+                            sub: &[]
+                        },
+                        Node {
+                            name: b"MASK",
+                            optional: false,
+                            handler: Some(&$commands.syst_tel_mask),
+                            sub: &[]
+                        },
+                        Node {
+                            name: b"FORMat",
+                            optional: false,
+                            handler: Some(&$commands.syst_tel_form),
+                            sub: &[]
+                        },
+                    ]
+                },
+                Node {
+                    name: b"LOG",
+                    optional: false,
+                    handler: None,
+                    sub: &[
+                        Node {
+                            name: b"NEXT",
+                            optional: true,
+                            handler: Some(&$crate::system_commands::SystLogNextCommand),
+                            sub: &[]
+                        },
+                        Node {
+                            name: b"DROPped",
+                            optional: false,
+                            handler: Some(&$crate::system_commands::SystLogDroppedCommand),
+                            sub: &[]
+                        },
+                        Node {
+                            name: b"CLEar",
+                            optional: false,
+                            handler: Some(&$crate::system_commands::SystLogClearCommand),
+                            sub: &[]
+                        },
+                    ]
+                },
+                Node {
+                    name: b"CRASh",
+                    optional: false,
+                    handler: Some(&$commands.syst_crash),
+                    sub: &[
+                        Node {
+                            name: b"CLEar",
+                            optional: false,
+                            handler: Some(&$commands.syst_crash_clear),
+                            sub: &[]
+                        },
+                    ]
+                },
+                Node {
+                    name: b"SERVos",
+                    optional: false,
+                    handler: None,
+                    sub: &[
+                        Node {
+                            name: b"PROTection",
+                            optional: false,
+                            handler: None,
+                            sub: &[
+                                Node {
+                                    name: b"CURRent",
+                                    optional: false,
+                                    handler: Some(&$commands.syst_serv_prot_curr),
+                                    sub: &[]
+                                },
+                                Node {
+                                    name: b"TEMPerature",
+                                    optional: false,
+                                    handler: Some(&$commands.syst_serv_prot_temp),
+                                    sub: &[]
+                                },
+                                Node {
+                                    name: b"TRIPped",
+                                    optional: false,
+                                    handler: Some(&$crate::system_commands::SystServProtTripCommand),
+                                    sub: &[
+                                        Node {
+                                            name: b"LEG",
+                                            optional: false,
+                                            handler: Some(&$crate::system_commands::SystServProtTripLegCommand),
+                                            sub: &[]
+                                        },
+                                    ]
+                                },
+                                Node {
+                                    name: b"CLEar",
+                                    optional: false,
+                                    handler: Some(&$crate::system_commands::SystServProtClearCommand),
+                                    sub: &[]
+                                },
+                            ]
+                        },
+                        Node {
+                            name: b"POWer",
+                            optional: false,
+                            handler: Some(&$commands.syst_serv_pow),
+                            sub: &[
+                                Node {
+                                    name: b"STAGger",
+                                    optional: false,
+                                    handler: Some(&$commands.syst_serv_pow_stag),
+                                    sub: &[]
+                                },
+                                Node {
+                                    name: b"STATe",
+                                    optional: false,
+                                    handler: Some(&$commands.syst_serv_pow_stat),
+                                    sub: &[]
+                                },
+                            ]
+                        },
+                    ]
+                },
+                Node {
+                    name: b"CONTact",
+                    optional: false,
+                    handler: None,
+                    sub: &[
+                        Node {
+                            name: b"FITTed",
+                            optional: false,
+                            handler: Some(&$commands.syst_cont_fitt),
+                            sub: &[]
+                        },
+                    ]
+                }
+            ),
+            //
+            $crate::scpi::scpi_crate_version!(),
+            Node {
+                name: b"BODY",
+                optional: true,
+                handler: None,
+                sub: &[
+                    Node {
+                        name: b"SERVos",
+                        optional: false,
+                        handler: None,
+                        sub: &[
+                            Node {
+                                name: b"PWIDth",
+                                optional: false,
+                                handler: None,
+                                sub: &[
+                                    Node {
+                                        name: b"ALL",
+                                        optional: true,
+                                        handler: Some(&$commands.servo_pwidth_all),
+                                        sub: &[]
+                                    },
+                                    Node {
+                                        name: b"SET",
+                                        optional: false,
+                                        handler: Some(&$commands.servo_pwidth_set),
+                                        sub: &[]
+                                    },
+                                    Node {
+                                        name: b"BLOCk",
+                                        optional: false,
+                                        handler: Some(&$commands.servo_pwidth_block),
+                                        sub: &[]
+                                    },
+                                ]
+                            },
+                            Node {
+                                name: b"STATe",
+                                optional: false,
+                                handler: None,
+                                sub: &[
+                                    Node {
+                                        name: b"ALL",
+                                        optional: true,
+                                        handler: Some(&$commands.servo_stat_all),
+                                        sub: &[]
+                                    },
+                                    Node {
+                                        name: b"SET",
+                                        optional: false,
+                                        handler: Some(&$commands.servo_stat_set),
+                                        sub: &[]
+                                    },
+                                ]
+                            },
+                        ]
+                    },
+                    Node {
+                        name: b"EYE",
+                        optional: false,
+                        handler: Some(&$commands.body_att_tran),
+                        sub: &[]
+                    },
+                    Node {
+                        name: b"ATTitude",
+                        optional: false,
+                        handler: None,
+                        sub: &[
+                            Node {
+                                name: b"ROTation",
+                                optional: false,
+                                handler: Some(&$commands.body_att_rot),
+                                sub: &[]
+                            },
+                            Node {
+                                name: b"TRANslation",
+                                optional: false,
+                                handler: Some(&$commands.body_att_tran),
+                                sub: &[]
+                            },
+                        ]
+                    },
+                    Node {
+                        name: b"VELocity",
+                        optional: false,
+                        handler: Some(&$commands.body_vel),
+                        sub: &[]
+                    },
+                    Node {
+                        name: b"LEVel",
+                        optional: false,
+                        handler: Some(&$commands.body_lev),
+                        sub: &[
+                            Node {
+                                name: b"GAIN",
+                                optional: false,
+                                handler: Some(&$commands.body_lev_gain),
+                                sub: &[]
+                            },
+                            Node {
+                                name: b"LIMit",
+                                optional: false,
+                                handler: Some(&$commands.body_lev_lim),
+                                sub: &[]
+                            },
+                            Node {
+                                name: b"CORRection",
+                                optional: false,
+                                handler: Some(&$commands.body_lev_corr),
+                                sub: &[]
+                            },
+                        ]
+                    },
+                    Node {
+                        name: b"GAIT",
+                        optional: false,
+                        handler: None,
+                        sub: &[
+                            Node {
+                                name: b"ADAPtive",
+                                optional: false,
+                                handler: Some(&$commands.body_gait_adap),
+                                sub: &[
+                                    Node {
+                                        name: b"DEPTh",
+                                        optional: false,
+                                        handler: Some(&$commands.body_gait_adap_depth),
+                                        sub: &[]
+                                    },
+                                ]
+                            },
+                        ]
+                    },
+                ]
+            },
+            Node {
+                name: b"DIAGnostic",
+                optional: false,
+                handler: None,
+                sub: &[
+                    Node {
+                        name: b"I2C",
+                        optional: false,
+                        handler: None,
+                        sub: &[
+                            Node {
+                                name: b"ERRors",
+                                optional: false,
+                                handler: Some(&$commands.diag_i2c_errors),
+                                sub: &[]
+                            },
+                        ]
+                    },
+                    Node {
+                        name: b"PWM",
+                        optional: false,
+                        handler: None,
+                        sub: &[
+                            Node {
+                                name: b"FREQuency",
+                                optional: false,
+                                handler: Some(&$commands.diag_pwm_freq),
+                                sub: &[]
+                            },
+                            Node {
+                                name: b"TRIM",
+                                optional: false,
+                                handler: Some(&$commands.diag_pwm_trim),
+                                sub: &[]
+                            },
+                        ]
+                    },
+                ]
+            },
+            Node {
+                name: b"MEASure",
+                optional: false,
+                handler: None,
+                sub: &[
+                    Node {
+                        name: b"ATTitude",
+                        optional: false,
+                        handler: Some(&$commands.meas_att),
+                        sub: &[]
+                    },
+                    Node {
+                        name: b"LEG",
+                        optional: false,
+                        handler: None,
+                        sub: &[
+                            Node {
+                                name: b"CONTact",
+                                optional: false,
+                                handler: Some(&$commands.meas_leg_cont),
+                                sub: &[]
+                            },
+                        ]
+                    },
+                    Node {
+                        name: b"SERVos",
+                        optional: false,
+                        handler: None,
+                        sub: &[
+                            Node {
+                                name: b"CURRent",
+                                optional: false,
+                                handler: Some(&$commands.meas_serv_curr),
+                                sub: &[]
+                            },
+                        ]
+                    },
+                    Node {
+                        name: b"TEMPerature",
+                        optional: false,
+                        handler: Some(&$commands.meas_temp),
+                        sub: &[]
+                    },
+                ]
+            }
+        ]
+    };
+}
@@ -0,0 +1,122 @@
+use ash_carrier_core::attitude::{Attitude, ImuSample, Mahony};
+use nalgebra::Vector3;
+
+const DT: f32 = 0.01;
+/// Loose enough for the filter to have settled, tight enough to catch sign
+/// and axis mix-ups.
+const TOLERANCE: f32 = 0.01;
+
+/// What a resting IMU reads at `roll` and `pitch`.
+fn at_rest(roll: f32, pitch: f32, gyro: Vector3<f32>) -> ImuSample {
+    ImuSample {
+        accel: Vector3::new(
+            -pitch.sin(),
+            roll.sin() * pitch.cos(),
+            roll.cos() * pitch.cos(),
+        ),
+        gyro,
+    }
+}
+
+fn run(filter: &mut Mahony, sample: &ImuSample, seconds: f32) -> Attitude {
+    for _ in 0..(seconds / DT) as usize {
+        filter.update(sample, DT);
+    }
+    filter.attitude().unwrap()
+}
+
+fn assert_close(attitude: Attitude, roll: f32, pitch: f32) {
+    assert!(
+        (attitude.roll - roll).abs() < TOLERANCE && (attitude.pitch - pitch).abs() < TOLERANCE,
+        "{:?}, expected roll {} pitch {}",
+        attitude,
+        roll,
+        pitch
+    );
+}
+
+#[test]
+fn nothing_before_the_first_sample() {
+    let mut filter = Mahony::new(Mahony::KP, Mahony::KI);
+    assert_eq!(filter.attitude(), None);
+    // Free fall is not a usable first sample
+    filter.update(
+        &ImuSample {
+            accel: Vector3::zeros(),
+            gyro: Vector3::zeros(),
+        },
+        DT,
+    );
+    assert_eq!(filter.attitude(), None);
+}
+
+#[test]
+fn first_sample_sets_attitude() {
+    for &(roll, pitch) in &[(0.0, 0.0), (0.3, 0.0), (0.0, -0.4), (-0.2, 0.25)] {
+        let mut filter = Mahony::new(Mahony::KP, Mahony::KI);
+        filter.update(&at_rest(roll, pitch, Vector3::zeros()), DT);
+        assert_close(filter.attitude().unwrap(), roll, pitch);
+    }
+}
+
+#[test]
+fn converges_to_new_tilt() {
+    let mut filter = Mahony::new(Mahony::KP, Mahony::KI);
+    filter.update(&at_rest(0.0, 0.0, Vector3::zeros()), DT);
+    // Tilted without the gyro noticing, only the accelerometer corrects
+    let attitude = run(&mut filter, &at_rest(0.2, -0.1, Vector3::zeros()), 10.0);
+    assert_close(attitude, 0.2, -0.1);
+}
+
+#[test]
+fn integrates_rotation() {
+    let mut filter = Mahony::new(Mahony::KP, 0.0);
+    filter.update(&at_rest(0.0, 0.0, Vector3::zeros()), DT);
+    // Roll at 0.5 rad/s for one second, then pitch at -0.3 rad/s
+    let steps = (1.0 / DT) as usize;
+    for i in 1..=steps {
+        let roll = 0.5 * i as f32 * DT;
+        filter.update(&at_rest(roll, 0.0, Vector3::new(0.5, 0.0, 0.0)), DT);
+    }
+    assert_close(filter.attitude().unwrap(), 0.5, 0.0);
+    for i in 1..=steps {
+        let pitch = -0.3 * i as f32 * DT;
+        let gyro = Vector3::new(0.0, -0.3 * 0.5f32.cos(), 0.3 * 0.5f32.sin());
+        filter.update(&at_rest(0.5, pitch, gyro), DT);
+    }
+    assert_close(filter.attitude().unwrap(), 0.5, -0.3);
+}
+
+#[test]
+fn gyro_bias_is_learned() {
+    let bias = Vector3::new(0.02, -0.03, 0.0);
+    let mut filter = Mahony::new(Mahony::KP, Mahony::KI);
+    let attitude = run(&mut filter, &at_rest(0.1, 0.1, bias), 60.0);
+    assert_close(attitude, 0.1, 0.1);
+    let learned = filter.bias();
+    assert!((learned.x + bias.x).abs() < 0.005, "{:?}", learned);
+    assert!((learned.y + bias.y).abs() < 0.005, "{:?}", learned);
+}
+
+#[test]
+fn impacts_do_not_disturb() {
+    let mut filter = Mahony::new(Mahony::KP, Mahony::KI);
+    filter.update(&at_rest(0.0, 0.0, Vector3::zeros()), DT);
+    // Sideways kicks well beyond 1 g
+    let kick = ImuSample {
+        accel: Vector3::new(0.0, 2.0, 1.0),
+        gyro: Vector3::zeros(),
+    };
+    let attitude = run(&mut filter, &kick, 1.0);
+    assert_close(attitude, 0.0, 0.0);
+}
+
+#[test]
+fn reset_starts_over() {
+    let mut filter = Mahony::new(Mahony::KP, Mahony::KI);
+    filter.update(&at_rest(0.3, 0.0, Vector3::zeros()), DT);
+    filter.reset();
+    assert_eq!(filter.attitude(), None);
+    filter.update(&at_rest(-0.3, 0.0, Vector3::zeros()), DT);
+    assert_close(filter.attitude().unwrap(), -0.3, 0.0);
+}
@@ -0,0 +1,564 @@
+//! Command lines fed through the carrier tree, asserting the responses, the
+//! queued errors and what was left in the servo state.
+
+use ash_carrier_core::attitude::Attitude;
+use ash_carrier_core::carrier_tree;
+use ash_carrier_core::crash::{CrashLog, CrashRecord};
+use ash_carrier_core::log;
+use ash_carrier_core::overload::ServoLoad;
+use ash_carrier_core::port::ScpiPort;
+use ash_carrier_core::power::PowerState;
+use ash_carrier_core::servo_commands::ServoControl;
+use ash_carrier_core::settings::SerialSettings;
+use ash_carrier_core::tree::{CarrierDevice, Commands, State, SERVOS};
+use scpi::ieee488::commands::*;
+use scpi::prelude::*;
+use scpi::scpi::commands::*;
+use std::sync::Mutex;
+
+const EXECUTION_ERROR: i16 = -200;
+const DATA_TYPE_ERROR: i16 = -104;
+const PARAMETER_NOT_ALLOWED: i16 = -108;
+const MISSING_PARAMETER: i16 = -109;
+const UNDEFINED_HEADER: i16 = -113;
+const INVALID_BLOCK_DATA: i16 = -161;
+const DATA_OUT_OF_RANGE: i16 = -222;
+const ILLEGAL_PARAMETER_VALUE: i16 = -224;
+const HARDWARE_MISSING: i16 = -241;
+const INPUT_BUFFER_OVERRUN: i16 = -363;
+
+/// The emergency stop latch is global, tests must not overlap.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn lock() -> std::sync::MutexGuard<'static, ()> {
+    SERIAL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+struct NoCrashLog;
+
+impl CrashLog for NoCrashLog {
+    fn last(&self) -> Option<CrashRecord> {
+        None
+    }
+
+    fn clear(&self) {}
+}
+
+enum Expect {
+    /// Executes without a response.
+    Ok,
+    Response(Vec<u8>),
+    /// Fails with this error code, leaving the servos untouched.
+    Error(i16),
+    /// Fails with this error code after changing the servos, by an earlier
+    /// unit of the line or because extra parameters are only found once the
+    /// handler has returned.
+    ErrorAfter(i16),
+}
+
+struct Case {
+    line: Vec<u8>,
+    expect: Expect,
+    servos: Option<fn(&[ServoControl; SERVOS])>,
+}
+
+impl Case {
+    /// Also check the servos once the line has executed.
+    fn then(mut self, servos: fn(&[ServoControl; SERVOS])) -> Self {
+        self.servos = Some(servos);
+        self
+    }
+}
+
+fn case<L: Into<Vec<u8>>>(line: L, expect: Expect) -> Case {
+    Case {
+        line: line.into(),
+        expect,
+        servos: None,
+    }
+}
+
+fn ok<L: Into<Vec<u8>>>(line: L) -> Case {
+    case(line, Expect::Ok)
+}
+
+fn query<L: Into<Vec<u8>>, R: Into<Vec<u8>>>(line: L, response: R) -> Case {
+    case(line, Expect::Response(response.into()))
+}
+
+fn error<L: Into<Vec<u8>>>(line: L, code: i16) -> Case {
+    case(line, Expect::Error(code))
+}
+
+fn error_after<L: Into<Vec<u8>>>(line: L, code: i16) -> Case {
+    case(line, Expect::ErrorAfter(code))
+}
+
+/// `command` with a parenthesized list of `n` times `value`.
+fn list(command: &str, value: &str, n: usize) -> String {
+    format!("{} ({})", command, vec![value; n].join(","))
+}
+
+/// `BODY:SERV:PWID:BLOC` with a definite length block of `widths`.
+fn block(widths: &[u16]) -> Vec<u8> {
+    let data: Vec<u8> = widths.iter().flat_map(|w| w.to_le_bytes()).collect();
+    let length = data.len().to_string();
+    let mut line = format!("BODY:SERV:PWID:BLOC #{}{}", length.len(), length).into_bytes();
+    line.extend(data);
+    line
+}
+
+/// Run `cases` in order on a freshly started carrier.
+fn run(cases: &[Case]) {
+    run_with(|_| (), cases)
+}
+
+/// Run `cases` in order on a carrier prepared by `setup`.
+fn run_with(setup: fn(&State), cases: &[Case]) {
+    let _lock = lock();
+    let state = State::new(SerialSettings::new());
+    setup(&state);
+    let commands = Commands::new(&state, &NoCrashLog, 115200);
+    let tree = carrier_tree!(commands, b"test");
+    let mut device = CarrierDevice;
+    let mut errors = ArrayErrorQueue::<[Error; 10]>::new();
+    let mut context = Context::new(&mut device, &mut errors, tree);
+    let mut formatter = ArrayVecFormatter::<[u8; 512]>::new();
+
+    for case in cases {
+        let line = String::from_utf8_lossy(&case.line).into_owned();
+        let before = *state.servos.borrow();
+        let result = context.run(&case.line, &mut formatter);
+        let response = formatter.as_slice();
+        match &case.expect {
+            Expect::Ok => {
+                assert_eq!(result, Ok(()), "{}", line);
+                assert_eq!(response, b"", "{}", line);
+            }
+            Expect::Response(expected) => {
+                assert_eq!(result, Ok(()), "{}", line);
+                assert_eq!(
+                    response.strip_suffix(b"\n"),
+                    Some(&expected[..]),
+                    "{}",
+                    line
+                );
+            }
+            Expect::Error(code) | Expect::ErrorAfter(code) => {
+                let err = result.expect_err(&line);
+                assert_eq!(err.get_code(), *code, "{}", line);
+                // Queued exactly once
+                assert_eq!(context.errors.len(), 1, "{}", line);
+                assert_eq!(
+                    context.errors.pop_front_error().get_code(),
+                    *code,
+                    "{}",
+                    line
+                );
+            }
+        }
+        if let Expect::Error(_) = case.expect {
+            let after = *state.servos.borrow();
+            for (b, a) in before.iter().zip(after.iter()) {
+                assert_eq!(
+                    (b.pulse_width, b.enable),
+                    (a.pulse_width, a.enable),
+                    "{}",
+                    line
+                );
+            }
+        }
+        assert_eq!(context.errors.len(), 0, "{}", line);
+        if let Some(servos) = case.servos {
+            servos(&state.servos.borrow());
+        }
+    }
+}
+
+#[test]
+fn pulse_width_set() {
+    run(&[
+        query("BODY:SERV:PWID:SET? 1", "1500"),
+        ok("BODY:SERV:PWID:SET 1,1200").then(|s| assert_eq!(s[0].pulse_width, 1200)),
+        ok("SERV:PWID:SET 24,1800").then(|s| assert_eq!(s[23].pulse_width, 1800)),
+        ok("BODY:SERV:PWID:SET 2,0").then(|s| assert_eq!(s[1].pulse_width, 0)),
+        ok("BODY:SERV:PWID:SET 3,3000").then(|s| assert_eq!(s[2].pulse_width, 3000)),
+        query("BODY:SERVOS:PWIDTH:SET? 24", "1800"),
+        // Boundaries of the index and the width
+        error("BODY:SERV:PWID:SET 0,1500", DATA_OUT_OF_RANGE),
+        error("BODY:SERV:PWID:SET 25,1500", DATA_OUT_OF_RANGE),
+        error("BODY:SERV:PWID:SET 1,3001", DATA_OUT_OF_RANGE),
+        error("BODY:SERV:PWID:SET 1,-1", DATA_OUT_OF_RANGE),
+        error("BODY:SERV:PWID:SET? 0", DATA_OUT_OF_RANGE),
+        error("BODY:SERV:PWID:SET? 25", DATA_OUT_OF_RANGE),
+        // Malformed and missing parameters
+        error("BODY:SERV:PWID:SET 1", MISSING_PARAMETER),
+        error("BODY:SERV:PWID:SET", MISSING_PARAMETER),
+        error("BODY:SERV:PWID:SET? ", MISSING_PARAMETER),
+        error("BODY:SERV:PWID:SET 1,\"1500\"", DATA_TYPE_ERROR),
+        error("BODY:SERV:PWID:SET ON,1500", DATA_TYPE_ERROR),
+        error_after("BODY:SERV:PWID:SET 1,1300,3", PARAMETER_NOT_ALLOWED)
+            .then(|s| assert_eq!(s[0].pulse_width, 1300)),
+    ]);
+}
+
+#[test]
+fn pulse_width_all() {
+    run(&[
+        ok(list("BODY:SERV:PWID:ALL", "1300", 24))
+            .then(|s| assert!(s.iter().all(|s| s.pulse_width == 1300))),
+        query("BODY:SERV:PWID:ALL?", vec!["1300"; 24].join(",")),
+        ok(list("BODY:SERV:PWID:ALL", "3000", 24)),
+        ok(list("BODY:SERV:PWID", "0", 24)),
+        // One too few or too many
+        error(
+            list("BODY:SERV:PWID:ALL", "1400", 23),
+            ILLEGAL_PARAMETER_VALUE,
+        ),
+        error(
+            list("BODY:SERV:PWID:ALL", "1400", 25),
+            ILLEGAL_PARAMETER_VALUE,
+        ),
+        error(
+            list("BODY:SERV:PWID:ALL", "1400", 100),
+            ILLEGAL_PARAMETER_VALUE,
+        ),
+        error("BODY:SERV:PWID:ALL ()", ILLEGAL_PARAMETER_VALUE),
+        // Out of range values anywhere in the list
+        error(list("BODY:SERV:PWID:ALL", "3001", 24), DATA_OUT_OF_RANGE),
+        error(
+            format!("BODY:SERV:PWID:ALL ({},-1)", vec!["1400"; 23].join(",")),
+            DATA_OUT_OF_RANGE,
+        ),
+        // Ranges are not pulse widths
+        error("BODY:SERV:PWID:ALL (1000:1023)", ILLEGAL_PARAMETER_VALUE),
+        // Not a list
+        error(
+            format!("BODY:SERV:PWID:ALL {}", vec!["1400"; 24].join(",")),
+            DATA_TYPE_ERROR,
+        ),
+        error("BODY:SERV:PWID:ALL", MISSING_PARAMETER),
+        query("BODY:SERV:PWID:SET? 12", "0"),
+    ]);
+}
+
+#[test]
+fn pulse_width_block() {
+    let mut widths = [1500u16; SERVOS];
+    widths[0] = 0;
+    widths[23] = 3000;
+    run(&[
+        ok(block(&widths)).then(|s| {
+            assert_eq!(s[0].pulse_width, 0);
+            assert_eq!(s[1].pulse_width, 1500);
+            assert_eq!(s[23].pulse_width, 3000);
+        }),
+        query("BODY:SERV:PWID:BLOC?", block(&widths).split_off(20)),
+        error(block(&widths[..23]), ILLEGAL_PARAMETER_VALUE),
+        error(block(&[1500; SERVOS + 1]), ILLEGAL_PARAMETER_VALUE),
+        error(block(&[]), ILLEGAL_PARAMETER_VALUE),
+        error(block(&[3001; SERVOS]), ILLEGAL_PARAMETER_VALUE),
+        // Shorter than its header says
+        error(&b"BODY:SERV:PWID:BLOC #248\x00\x01"[..], INVALID_BLOCK_DATA),
+        error("BODY:SERV:PWID:BLOC 1500", DATA_TYPE_ERROR),
+        error("BODY:SERV:PWID:BLOC", MISSING_PARAMETER),
+        query("BODY:SERV:PWID:SET? 24", "3000"),
+    ]);
+}
+
+#[test]
+fn pulse_widths_refused_in_emergency_stop() {
+    run(&[
+        ok("BODY:SERV:PWID:SET 1,1200"),
+        ok("SYST:EST"),
+        query("SYST:EST?", "1"),
+        error("BODY:SERV:PWID:SET 1,1800", EXECUTION_ERROR),
+        error(list("BODY:SERV:PWID:ALL", "1800", 24), EXECUTION_ERROR),
+        error(block(&[1800; 24]), EXECUTION_ERROR),
+        // Queries still answer
+        query("BODY:SERV:PWID:SET? 1", "1200"),
+        ok("SYST:EST:RES"),
+        ok("BODY:SERV:PWID:SET 1,1800"),
+        query("BODY:SERV:PWID:SET? 1", "1800"),
+    ]);
+}
+
+#[test]
+fn servo_state() {
+    run(&[
+        query("BODY:SERV:STAT:SET? 1", "0"),
+        ok("BODY:SERV:STAT:SET 1,ON").then(|s| assert!(s[0].enable && !s[1].enable)),
+        ok("BODY:SERV:STAT:SET 24,1").then(|s| assert!(s[23].enable)),
+        query("BODY:SERV:STAT:SET? 24", "1"),
+        // ALL is the default
+        query(
+            "BODY:SERV:STAT?",
+            "1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1",
+        ),
+        ok("BODY:SERV:STAT:ALL ON").then(|s| assert!(s.iter().all(|s| s.enable))),
+        ok("BODY:SERV:STAT:ALL 0").then(|s| assert!(s.iter().all(|s| !s.enable))),
+        query(
+            "BODY:SERV:STAT:ALL?",
+            "0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0",
+        ),
+        error("BODY:SERV:STAT:SET 0,ON", DATA_OUT_OF_RANGE),
+        error("BODY:SERV:STAT:SET 25,ON", DATA_OUT_OF_RANGE),
+        error("BODY:SERV:STAT:SET 1,2", ILLEGAL_PARAMETER_VALUE),
+        error("BODY:SERV:STAT:SET 1,MAYBE", ILLEGAL_PARAMETER_VALUE),
+        error("BODY:SERV:STAT:SET 1", MISSING_PARAMETER),
+        error("BODY:SERV:STAT:ALL", MISSING_PARAMETER),
+        // Only the failing unit is rejected
+        error_after("BODY:SERV:STAT:SET 2,ON;SET 0,ON", DATA_OUT_OF_RANGE)
+            .then(|s| assert!(s[1].enable)),
+    ]);
+}
+
+#[test]
+fn pwm_settings() {
+    run(&[
+        query("DIAG:PWM:FREQ?", "50"),
+        query("DIAG:PWM:TRIM?", "1.0"),
+        ok("DIAG:PWM:FREQ 333"),
+        ok("DIAGNOSTIC:PWM:FREQUENCY 40"),
+        query("DIAG:PWM:FREQ?", "40"),
+        error("DIAG:PWM:FREQ 39", DATA_OUT_OF_RANGE),
+        error("DIAG:PWM:FREQ 334", DATA_OUT_OF_RANGE),
+        ok("DIAG:PWM:TRIM 1.03125"),
+        query("DIAG:PWM:TRIM?", "1.03125"),
+        error("DIAG:PWM:TRIM 1.2", DATA_OUT_OF_RANGE),
+        error("DIAG:PWM:TRIM 0.8", DATA_OUT_OF_RANGE),
+        error("DIAG:PWM:TRIM", MISSING_PARAMETER),
+    ]);
+}
+
+#[test]
+fn measured_attitude() {
+    run(&[
+        error("MEAS:ATT?", HARDWARE_MISSING),
+        error("MEAS:ATT 0,0", UNDEFINED_HEADER),
+    ]);
+    run_with(
+        |state| {
+            state.attitude.replace(Some(Attitude {
+                roll: 0.25,
+                pitch: -0.5,
+            }));
+        },
+        &[query("MEASURE:ATTITUDE?", "0.25,-0.5")],
+    );
+}
+
+#[test]
+fn body_level() {
+    run(&[
+        query("BODY:LEV?", "0"),
+        ok("BODY:LEV ON"),
+        query("LEVEL?", "1"),
+        ok("BODY:LEV:GAIN 0.5,2"),
+        query("BODY:LEV:GAIN?", "0.5,2.0"),
+        error("BODY:LEV:GAIN -1,2", DATA_OUT_OF_RANGE),
+        error("BODY:LEV:GAIN 0.5,11", DATA_OUT_OF_RANGE),
+        error("BODY:LEV:GAIN 0.5", MISSING_PARAMETER),
+        ok("BODY:LEV:LIM 0.25,1"),
+        query("BODY:LEV:LIM?", "0.25,1.0"),
+        error("BODY:LEV:LIM 0.6,1", DATA_OUT_OF_RANGE),
+        error("BODY:LEV:LIM 0.25,-1", DATA_OUT_OF_RANGE),
+        query("BODY:LEV:CORR?", "0.0,0.0"),
+        error("BODY:LEV:CORR 0,0", UNDEFINED_HEADER),
+        ok("BODY:LEV OFF"),
+        query("BODY:LEV?", "0"),
+    ]);
+    run_with(
+        |state| {
+            state.level_correction.replace(Attitude {
+                roll: -0.125,
+                pitch: 0.25,
+            });
+        },
+        &[query("BODY:LEVEL:CORRECTION?", "-0.125,0.25")],
+    );
+}
+
+#[test]
+fn foot_contact() {
+    run(&[
+        query("MEAS:LEG:CONT?", "-1,-1,-1,-1,-1,-1,-1,-1"),
+        error("MEAS:LEG:CONT 1", UNDEFINED_HEADER),
+        query("SYST:CONT:FITT?", "0"),
+        ok("SYST:CONT:FITT 9"),
+        query("SYSTEM:CONTACT:FITTED?", "9"),
+        error("SYST:CONT:FITT 256", DATA_OUT_OF_RANGE),
+        query("BODY:GAIT:ADAP?", "0"),
+        ok("BODY:GAIT:ADAP ON"),
+        query("GAIT:ADAPTIVE?", "1"),
+        ok("BODY:GAIT:ADAP:DEPT 0.0625"),
+        query("BODY:GAIT:ADAP:DEPT?", "0.0625"),
+        ok("BODY:GAIT:ADAP:DEPT 25 mm"),
+        error("BODY:GAIT:ADAP:DEPT 0.2", DATA_OUT_OF_RANGE),
+        error("BODY:GAIT:ADAP:DEPT -0.01", DATA_OUT_OF_RANGE),
+    ]);
+    run_with(
+        |state| {
+            let mut contacts = state.contacts.borrow_mut();
+            contacts[0] = Some(true);
+            contacts[3] = Some(false);
+        },
+        &[query("MEASURE:LEG:CONTACT?", "1,-1,-1,0,-1,-1,-1,-1")],
+    );
+}
+
+#[test]
+fn servo_protection() {
+    run(&[
+        query("MEAS:SERV:CURR?", "0.0,0.0"),
+        error("MEAS:TEMP?", HARDWARE_MISSING),
+        query("SYST:SERV:PROT:CURR?", "15.0"),
+        ok("SYST:SERV:PROT:CURR 2.5"),
+        query("SYST:SERV:PROT:CURR?", "2.5"),
+        error("SYST:SERV:PROT:CURR 31", DATA_OUT_OF_RANGE),
+        error("SYST:SERV:PROT:CURR -1", DATA_OUT_OF_RANGE),
+        query("SYST:SERV:PROT:TEMP?", "70.0"),
+        ok("SYST:SERV:PROT:TEMP 60"),
+        query("SYSTEM:SERVOS:PROTECTION:TEMPERATURE?", "60.0"),
+        error("SYST:SERV:PROT:TEMP 101", DATA_OUT_OF_RANGE),
+        query("SYST:SERV:PROT:TRIP?", "0,0"),
+        ok("SYST:SERV:PROT:CLE"),
+        error("SYST:SERV:PROT:CLE?", UNDEFINED_HEADER),
+    ]);
+    run_with(
+        |state| {
+            state.servo_load.replace(ServoLoad {
+                milliamps: [1250, 16500],
+                celsius: Some(41.5),
+            });
+        },
+        &[
+            query("MEASURE:SERVOS:CURRENT?", "1.25,16.5"),
+            query("MEAS:TEMP?", "41.5"),
+        ],
+    );
+}
+
+#[test]
+fn servo_power() {
+    run(&[
+        query("SYST:SERV:POW?", "0"),
+        query("SYST:SERV:POW:STAT?", "OFF"),
+        ok("SYST:SERV:POW ON"),
+        query("SYST:SERV:POW?", "1"),
+        query("SYST:SERV:POW:STAG?", "50"),
+        ok("SYST:SERV:POW:STAG 0"),
+        ok("SYSTEM:SERVOS:POWER:STAGGER 1000"),
+        query("SYST:SERV:POW:STAG?", "1000"),
+        error("SYST:SERV:POW:STAG 1001", DATA_OUT_OF_RANGE),
+        error("SYST:SERV:POW:STAT ON", UNDEFINED_HEADER),
+        ok("SYST:SERV:POW OFF"),
+    ]);
+    run_with(
+        |state| {
+            state.power_state.replace(PowerState::Starting(3));
+        },
+        &[query("SYST:SERV:POW:STAT?", "STAR")],
+    );
+}
+
+#[test]
+fn serial_parity() {
+    run(&[
+        query("SYST:COMM:SER:PAR?", "NONE"),
+        ok("SYST:COMM:SER:PAR EVEN"),
+        query("SYSTEM:COMMUNICATE:SERIAL:PARITY?", "EVEN"),
+        error("SYST:COMM:SER:PAR MARK", ILLEGAL_PARAMETER_VALUE),
+    ]);
+}
+
+#[test]
+fn protocol() {
+    run(&[query("SYST:COMM:PROT?", "SCPI")]);
+}
+
+#[test]
+fn telemetry_format() {
+    run(&[
+        query("SYST:TEL:FORM?", "CSV"),
+        ok("SYST:TEL:FORM BIN"),
+        query("SYSTEM:TELEMETRY:FORMAT?", "BIN"),
+    ]);
+}
+
+#[test]
+fn log_entries() {
+    run_with(
+        |_| {
+            while log::next().is_some() {}
+            log::warning("Servo controller unreachable", 2);
+        },
+        &[
+            query("SYST:LOG?", "WARNING,\"Servo controller unreachable\",2"),
+            query("SYST:LOG:NEXT?", "NONE,\"\",0"),
+        ],
+    );
+}
+
+/// Lines pushed through a [ScpiPort] one byte at a time, returning what it
+/// wrote back.
+fn port_lines(lines: &[String]) -> (String, [ServoControl; SERVOS]) {
+    let _lock = lock();
+    let state = State::new(SerialSettings::new());
+    let commands = Commands::new(&state, &NoCrashLog, 115200);
+    let framed_handler = state.framed_handler();
+    let ros_bridge = state.ros_bridge();
+    let tree = carrier_tree!(commands, b"test");
+    let mut device = CarrierDevice;
+    let mut errors = ArrayErrorQueue::<[Error; 10]>::new();
+    let mut context = Context::new(&mut device, &mut errors, tree);
+    let mut port = ScpiPort::<256>::new();
+    let mut output = Vec::new();
+
+    for line in lines {
+        for c in line.bytes().chain(Some(b'\n')) {
+            port.push(
+                c,
+                &mut context,
+                &state.session,
+                &framed_handler,
+                &ros_bridge,
+                0,
+                |bytes: &[u8]| output.extend_from_slice(bytes),
+            );
+        }
+    }
+    let servos = *state.servos.borrow();
+    (String::from_utf8(output).unwrap(), servos)
+}
+
+#[test]
+fn over_long_line_is_discarded() {
+    let long = list("BODY:SERV:PWID:ALL", "1000", 60);
+    let short = list("BODY:SERV:PWID:ALL", "1000", 24);
+    assert!(long.len() > 256 && short.len() < 256);
+    let (output, servos) = port_lines(&[long, short, "SYST:ERR?".into(), "SYST:ERR?".into()]);
+    let errors: Vec<&str> = output.lines().collect();
+    assert!(errors[0].starts_with(&format!("{},", INPUT_BUFFER_OVERRUN)));
+    assert!(errors[1].starts_with("0,"), "{}", output);
+    // The line after the overrun is not affected by it
+    assert!(servos.iter().all(|s| s.pulse_width == 1000));
+}
+
+#[test]
+fn failed_line_is_queued_once() {
+    let (output, _) = port_lines(&[
+        "BODY:SERV:PWID:SET 0,1500".into(),
+        "SYST:ERR?".into(),
+        "SYST:ERR?".into(),
+    ]);
+    let errors: Vec<&str> = output.lines().collect();
+    assert!(errors[0].starts_with(&format!("{},", DATA_OUT_OF_RANGE)));
+    assert!(errors[1].starts_with("0,"), "{}", output);
+}
+
+#[test]
+fn bogus_block_header_does_not_stall_the_port() {
+    let (output, _) = port_lines(&["BODY:SERV:PWID:BLOC #9999999999".into(), "*IDN?".into()]);
+    assert!(output.contains(",ash-carrier,"), "{}", output);
+}
@@ -0,0 +1,118 @@
+use ash_carrier_core::contact::{
+    self, ContactSensor, DescentSettings, ForceSensor, Switch, Touchdown,
+};
+use embedded_hal::digital::v2::InputPin;
+use std::cell::Cell;
+
+const DT: f32 = 0.01;
+const GROUND: f32 = -0.1;
+
+struct FakePin(Result<bool, ()>);
+
+impl InputPin for FakePin {
+    type Error = ();
+
+    fn is_high(&self) -> Result<bool, ()> {
+        self.0
+    }
+
+    fn is_low(&self) -> Result<bool, ()> {
+        self.0.map(|high| !high)
+    }
+}
+
+fn adaptive() -> DescentSettings {
+    DescentSettings {
+        adaptive: true,
+        ..DescentSettings::new()
+    }
+}
+
+/// Swing from 0 down to [GROUND] at `speed` m/s, then hold. The ground is
+/// actually at `floor`, returns where the foot ends up after `seconds`.
+fn swing(settings: &DescentSettings, floor: f32, sensor: bool, seconds: f32) -> (f32, Touchdown) {
+    let speed = 0.2;
+    let mut touchdown = Touchdown::new();
+    let mut height = 0.0;
+    for i in 1..=(seconds / DT) as usize {
+        let planned = (-speed * i as f32 * DT).max(GROUND);
+        let contact = if sensor { Some(height <= floor) } else { None };
+        height = touchdown.update(settings, planned, GROUND, contact, DT);
+    }
+    (height, touchdown)
+}
+
+#[test]
+fn switch_closes_to_ground() {
+    assert_eq!(Switch::new(FakePin(Ok(false))).contact(), Some(true));
+    assert_eq!(Switch::new(FakePin(Ok(true))).contact(), Some(false));
+    assert_eq!(Switch::new(FakePin(Err(()))).contact(), None);
+}
+
+#[test]
+fn only_fitted_sensors_are_read() {
+    let mut switches = [(); 8].map(|_| Switch::new(FakePin(Ok(false))));
+    switches[1] = Switch::new(FakePin(Ok(true)));
+    switches[2] = Switch::new(FakePin(Err(())));
+    assert_eq!(
+        contact::read(&mut switches, 0b0000_0111),
+        [Some(true), Some(false), None, None, None, None, None, None]
+    );
+    assert_eq!(contact::read(&mut switches, 0), [None; 8]);
+}
+
+#[test]
+fn force_sensor_has_hysteresis() {
+    let load = Cell::new(Some(0u16));
+    let mut sensor = ForceSensor::new(|| load.get(), 600, 400);
+    for &(reading, contact) in &[
+        (Some(0), Some(false)),
+        (Some(500), Some(false)),
+        (Some(600), Some(true)),
+        (Some(500), Some(true)),
+        (None, None),
+        (Some(450), Some(true)),
+        (Some(399), Some(false)),
+        (Some(500), Some(false)),
+    ] {
+        load.set(reading);
+        assert_eq!(sensor.contact(), contact, "{:?}", reading);
+    }
+}
+
+#[test]
+fn fixed_height_without_adaptive() {
+    let (height, touchdown) = swing(&DescentSettings::new(), GROUND - 0.02, true, 2.0);
+    assert_eq!(height, GROUND);
+    assert_eq!(touchdown.landed(), None);
+}
+
+#[test]
+fn fixed_height_without_sensor() {
+    let (height, _) = swing(&adaptive(), GROUND - 0.02, false, 2.0);
+    assert_eq!(height, GROUND);
+}
+
+#[test]
+fn descends_into_a_hole() {
+    let floor = GROUND - 0.02;
+    let (height, touchdown) = swing(&adaptive(), floor, true, 2.0);
+    assert!((height - floor).abs() < 0.001, "{}", height);
+    assert_eq!(touchdown.landed(), Some(height));
+}
+
+#[test]
+fn stops_early_on_a_step() {
+    let floor = GROUND + 0.03;
+    let (height, touchdown) = swing(&adaptive(), floor, true, 2.0);
+    assert!((height - floor).abs() < 0.003, "{}", height);
+    assert_eq!(touchdown.landed(), Some(height));
+}
+
+#[test]
+fn search_depth_is_limited() {
+    let settings = adaptive();
+    let (height, touchdown) = swing(&settings, GROUND - 1.0, true, 5.0);
+    assert!((height - (GROUND - settings.max_depth)).abs() < 1e-6);
+    assert_eq!(touchdown.landed(), None);
+}
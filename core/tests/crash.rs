@@ -0,0 +1,63 @@
+//! Crash records and reading them back with `SYSTem:CRASh?`.
+
+use core::fmt::Write;
+
+use ash_carrier_core::carrier_tree;
+use ash_carrier_core::crash::{CrashKind, CrashLog, CrashRecord};
+use ash_carrier_core::settings::SerialSettings;
+use ash_carrier_core::tree::{CarrierDevice, Commands, State};
+use scpi::ieee488::commands::*;
+use scpi::prelude::*;
+use scpi::scpi::commands::*;
+
+struct LastCrash(CrashRecord);
+
+impl CrashLog for LastCrash {
+    fn last(&self) -> Option<CrashRecord> {
+        Some(self.0)
+    }
+
+    fn clear(&self) {}
+}
+
+fn panic_record(message: &str) -> CrashRecord {
+    // Whatever was in RAM before the record was started
+    let mut record: CrashRecord = unsafe { core::mem::zeroed() };
+    record.begin(CrashKind::Panic);
+    write!(record, "panicked at {}", message).unwrap();
+    record.commit();
+    record
+}
+
+#[test]
+fn message_is_truncated() {
+    let record = panic_record(&"x".repeat(200));
+    assert_eq!(record.message().len(), 128);
+    assert!(record.message().starts_with(b"panicked at xxx"));
+}
+
+#[test]
+fn non_ascii_message_is_readable() {
+    // Two byte characters, the last one cut in half
+    let record = panic_record(&format!("ab\n{}", "ü".repeat(100)));
+    let message = record.message();
+    assert_eq!(message.len(), 128);
+    assert!(message.starts_with(b"panicked at ab???"));
+    assert!(message.iter().all(|b| (b' '..=b'~').contains(b)));
+
+    let state = State::new(SerialSettings::new());
+    let crashes = LastCrash(record);
+    let commands = Commands::new(&state, &crashes, 115200);
+    let tree = carrier_tree!(commands, b"test");
+    let mut device = CarrierDevice;
+    let mut errors = ArrayErrorQueue::<[Error; 10]>::new();
+    let mut context = Context::new(&mut device, &mut errors, tree);
+    let mut formatter = ArrayVecFormatter::<[u8; 512]>::new();
+    context.run(b"SYST:CRAS?", &mut formatter).unwrap();
+    let response = String::from_utf8(formatter.as_slice().to_vec()).unwrap();
+    assert!(
+        response.starts_with("1,\"panicked at ab????"),
+        "{}",
+        response
+    );
+}
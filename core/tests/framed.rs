@@ -0,0 +1,142 @@
+//! Requests over the binary transport, from the handler up to a port that
+//! switched to `FRAMed`.
+
+use ash_carrier_core::carrier_tree;
+use ash_carrier_core::crash::{CrashLog, CrashRecord};
+use ash_carrier_core::port::ScpiPort;
+use ash_carrier_core::protocol::*;
+use ash_carrier_core::settings::SerialSettings;
+use ash_carrier_core::tree::{CarrierDevice, Commands, State};
+use scpi::ieee488::commands::*;
+use scpi::prelude::*;
+use scpi::scpi::commands::*;
+
+struct NoCrashLog;
+
+impl CrashLog for NoCrashLog {
+    fn last(&self) -> Option<CrashRecord> {
+        None
+    }
+
+    fn clear(&self) {}
+}
+
+fn decode(frame: &[u8]) -> Vec<Packet> {
+    let mut decoder = FrameDecoder::new();
+    frame
+        .iter()
+        .filter_map(|b| decoder.push(*b))
+        .map(Result::unwrap)
+        .collect()
+}
+
+#[test]
+fn servo_targets_are_acked() {
+    let state = State::new(SerialSettings::new());
+    let handler = state.framed_handler();
+    let mut targets = [1500u16; SERVOS];
+    targets[5] = 1200;
+    assert_eq!(handler.handle(Message::ServoTargets(targets)), Message::Ack);
+    assert_eq!(state.servos.borrow()[5].pulse_width, 1200);
+    assert_eq!(
+        handler.handle(Message::ReadTelemetry),
+        Message::Telemetry {
+            targets,
+            estop: false,
+        }
+    );
+}
+
+#[test]
+fn out_of_range_targets_are_nacked() {
+    let state = State::new(SerialSettings::new());
+    let handler = state.framed_handler();
+    let mut targets = [1500u16; SERVOS];
+    targets[0] = 1200;
+    targets[23] = 3001;
+    assert_eq!(
+        handler.handle(Message::ServoTargets(targets)),
+        Message::Nack(NackReason::OutOfRange)
+    );
+    // Nothing of a rejected packet is applied
+    assert_eq!(state.servos.borrow()[0].pulse_width, 1500);
+}
+
+#[test]
+fn body_pose_must_be_finite() {
+    let state = State::new(SerialSettings::new());
+    let handler = state.framed_handler();
+    let pose = Message::BodyPose {
+        rotation: [0.0, 0.0, 0.5],
+        translation: [0.0, 0.0, 0.05],
+    };
+    assert_eq!(handler.handle(pose), Message::Ack);
+    assert!((state.translation.borrow().z - 0.05).abs() < 1e-6);
+    let pose = Message::BodyPose {
+        rotation: [f32::NAN, 0.0, 0.0],
+        translation: [0.0; 3],
+    };
+    assert_eq!(handler.handle(pose), Message::Nack(NackReason::OutOfRange));
+    assert!((state.translation.borrow().z - 0.05).abs() < 1e-6);
+}
+
+#[test]
+fn replies_are_not_requests() {
+    let state = State::new(SerialSettings::new());
+    let handler = state.framed_handler();
+    assert_eq!(
+        handler.handle(Message::Ack),
+        Message::Nack(NackReason::UnknownKind)
+    );
+}
+
+#[test]
+fn port_switches_to_frames_and_back() {
+    let state = State::new(SerialSettings::new());
+    let commands = Commands::new(&state, &NoCrashLog, 115200);
+    let framed_handler = state.framed_handler();
+    let ros_bridge = state.ros_bridge();
+    let tree = carrier_tree!(commands, b"test");
+    let mut device = CarrierDevice;
+    let mut errors = ArrayErrorQueue::<[Error; 10]>::new();
+    let mut context = Context::new(&mut device, &mut errors, tree);
+    let mut port = ScpiPort::<256>::new();
+    let mut output = Vec::new();
+
+    let mut input = b"SYST:COMM:PROT FRAM\n".to_vec();
+    let mut frame = [0u8; MAX_FRAME];
+    let mut targets = [1500u16; SERVOS];
+    targets[2] = 1800;
+    let n = Packet::new(1, Message::ServoTargets(targets)).encode(&mut frame);
+    input.extend_from_slice(&frame[..n]);
+    // The sequence number of a corrupted frame can't be trusted
+    let n = Packet::new(2, Message::ReadTelemetry).encode(&mut frame);
+    frame[3] ^= 0x01;
+    input.extend_from_slice(&frame[..n]);
+    let n = Packet::new(3, Message::Scpi).encode(&mut frame);
+    input.extend_from_slice(&frame[..n]);
+    input.extend_from_slice(b"BODY:SERV:PWID:SET? 3\n");
+
+    for c in input {
+        port.push(
+            c,
+            &mut context,
+            &state.session,
+            &framed_handler,
+            &ros_bridge,
+            0,
+            |bytes: &[u8]| output.extend_from_slice(bytes),
+        );
+    }
+    // The text reply follows the last frame delimiter
+    let text = output.iter().rposition(|b| *b == 0).unwrap() + 1;
+    assert_eq!(
+        decode(&output[..text]),
+        vec![
+            Packet::new(1, Message::Ack),
+            Packet::new(0, Message::Nack(NackReason::Corrupt)),
+            Packet::new(3, Message::Ack),
+        ]
+    );
+    assert_eq!(&output[text..], b"1800\n");
+}
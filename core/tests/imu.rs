@@ -0,0 +1,136 @@
+use ash_carrier_core::imu::{AttitudeEstimator, Error, Mpu6050, MPU6050_ADDRESS};
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+struct Registers {
+    registers: [u8; 128],
+    connected: bool,
+}
+
+/// Register file of an MPU-6050, clones share it.
+#[derive(Clone)]
+struct FakeImu(Rc<RefCell<Registers>>);
+
+impl FakeImu {
+    fn new() -> Self {
+        let mut registers = [0u8; 128];
+        registers[0x75] = 0x68;
+        // Asleep after power on
+        registers[0x6B] = 0x40;
+        FakeImu(Rc::new(RefCell::new(Registers {
+            registers,
+            connected: true,
+        })))
+    }
+
+    fn set(&self, register: usize, values: &[i16]) {
+        let registers = &mut self.0.borrow_mut().registers;
+        for (i, v) in values.iter().enumerate() {
+            registers[register + 2 * i..register + 2 * i + 2].copy_from_slice(&v.to_be_bytes());
+        }
+    }
+
+    fn get(&self, register: usize) -> u8 {
+        self.0.borrow().registers[register]
+    }
+
+    fn set_connected(&self, connected: bool) {
+        self.0.borrow_mut().connected = connected;
+    }
+}
+
+impl Write for FakeImu {
+    type Error = ();
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+        let mut fake = self.0.borrow_mut();
+        if !fake.connected || address != MPU6050_ADDRESS {
+            return Err(());
+        }
+        let start = bytes[0] as usize;
+        fake.registers[start..start + bytes.len() - 1].copy_from_slice(&bytes[1..]);
+        Ok(())
+    }
+}
+
+impl WriteRead for FakeImu {
+    type Error = ();
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), ()> {
+        let fake = self.0.borrow();
+        if !fake.connected || address != MPU6050_ADDRESS {
+            return Err(());
+        }
+        let start = bytes[0] as usize;
+        buffer.copy_from_slice(&fake.registers[start..start + buffer.len()]);
+        Ok(())
+    }
+}
+
+#[test]
+fn init_configures_ranges() {
+    let fake = FakeImu::new();
+    Mpu6050::new(fake.clone(), MPU6050_ADDRESS).init().unwrap();
+    // Awake on the gyro clock, 44 Hz filter, 500 deg/s and 4 g
+    assert_eq!(fake.get(0x6B), 0x01);
+    assert_eq!(fake.get(0x1A), 0x03);
+    assert_eq!(fake.get(0x1B), 0x08);
+    assert_eq!(fake.get(0x1C), 0x08);
+}
+
+#[test]
+fn wrong_device_is_rejected() {
+    let fake = FakeImu::new();
+    fake.0.borrow_mut().registers[0x75] = 0x70;
+    assert_eq!(
+        Mpu6050::new(fake, MPU6050_ADDRESS).init(),
+        Err(Error::WrongDevice(0x70))
+    );
+}
+
+#[test]
+fn read_scales_to_g_and_rad_per_s() {
+    let fake = FakeImu::new();
+    // 1 g up, -0.5 g forward, temperature, 65.5 LSB is 1 deg/s
+    fake.set(0x3B, &[-4096, 0, 8192, 1234, 655, 0, -131]);
+    let sample = Mpu6050::new(fake, MPU6050_ADDRESS).read().unwrap();
+    assert_eq!(sample.accel.x, -0.5);
+    assert_eq!(sample.accel.y, 0.0);
+    assert_eq!(sample.accel.z, 1.0);
+    assert!((sample.gyro.x - 10f32.to_radians()).abs() < 1e-5);
+    assert!((sample.gyro.z + 2f32.to_radians()).abs() < 1e-5);
+}
+
+#[test]
+fn estimator_recovers_after_failure() {
+    let fake = FakeImu::new();
+    // Pitched forward by 30 degrees
+    fake.set(0x3B, &[-4096, 0, 7094]);
+    let mut estimator = AttitudeEstimator::new(Mpu6050::new(fake.clone(), MPU6050_ADDRESS));
+    // The first update initializes
+    assert_eq!(estimator.update(0.01), None);
+    let attitude = estimator.update(0.01).unwrap();
+    assert!((attitude.pitch - 30f32.to_radians()).abs() < 0.01, "{:?}", attitude);
+
+    fake.set_connected(false);
+    assert_eq!(estimator.update(0.01), None);
+    assert_eq!(estimator.errors, 1);
+    // Power cycled while away
+    fake.0.borrow_mut().registers[0x6B] = 0x40;
+    fake.set_connected(true);
+    assert_eq!(estimator.update(0.01), None);
+    assert_eq!(fake.get(0x6B), 0x01);
+    assert!(estimator.update(0.01).is_some());
+}
+
+#[test]
+fn estimator_without_imu() {
+    let fake = FakeImu::new();
+    fake.set_connected(false);
+    let mut estimator = AttitudeEstimator::new(Mpu6050::new(fake, MPU6050_ADDRESS));
+    for _ in 0..3 {
+        assert_eq!(estimator.update(0.01), None);
+    }
+    assert_eq!(estimator.errors, 3);
+}
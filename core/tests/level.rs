@@ -0,0 +1,212 @@
+use ash_carrier_core::attitude::Attitude;
+use ash_carrier_core::level::{LevelSettings, Leveller};
+use ash_carrier_core::settings::SerialSettings;
+use ash_carrier_core::tree::State;
+use nalgebra::Rotation3;
+
+const DT: f32 = 0.01;
+const TOLERANCE: f32 = 0.005;
+
+fn enabled() -> LevelSettings {
+    LevelSettings {
+        enabled: true,
+        ..LevelSettings::new()
+    }
+}
+
+fn attitude(roll: f32, pitch: f32) -> Attitude {
+    Attitude { roll, pitch }
+}
+
+/// Stand on a `slope` for `seconds`, the body tilts with the ground plus
+/// whatever rotation is applied. Returns the measured attitude.
+fn stand(
+    leveller: &mut Leveller,
+    settings: &LevelSettings,
+    commanded: Attitude,
+    slope: Attitude,
+    seconds: f32,
+) -> Attitude {
+    let mut measured = attitude(0.0, 0.0);
+    for _ in 0..(seconds / DT) as usize {
+        let correction = leveller.correction();
+        measured = attitude(
+            slope.roll + commanded.roll + correction.roll,
+            slope.pitch + commanded.pitch + correction.pitch,
+        );
+        leveller.update(settings, &commanded, Some(measured), DT);
+    }
+    measured
+}
+
+fn assert_close(attitude: Attitude, roll: f32, pitch: f32) {
+    assert!(
+        (attitude.roll - roll).abs() < TOLERANCE && (attitude.pitch - pitch).abs() < TOLERANCE,
+        "{:?}, expected roll {} pitch {}",
+        attitude,
+        roll,
+        pitch
+    );
+}
+
+#[test]
+fn levels_on_slope() {
+    let mut leveller = Leveller::new();
+    let measured = stand(
+        &mut leveller,
+        &enabled(),
+        attitude(0.0, 0.0),
+        attitude(0.1, -0.2),
+        10.0,
+    );
+    assert_close(measured, 0.0, 0.0);
+    assert_close(leveller.correction(), -0.1, 0.2);
+}
+
+#[test]
+fn commanded_rotation_is_relative_to_gravity() {
+    let mut leveller = Leveller::new();
+    let measured = stand(
+        &mut leveller,
+        &enabled(),
+        attitude(0.05, 0.1),
+        attitude(0.15, 0.0),
+        10.0,
+    );
+    assert_close(measured, 0.05, 0.1);
+}
+
+#[test]
+fn correction_saturates() {
+    let settings = enabled();
+    let limit = settings.config.max_angle;
+    let mut leveller = Leveller::new();
+    stand(
+        &mut leveller,
+        &settings,
+        attitude(0.0, 0.0),
+        attitude(0.0, -0.5),
+        30.0,
+    );
+    assert_close(leveller.correction(), 0.0, limit);
+
+    // No wind up, back on flat ground the correction comes off the limit
+    // right away
+    stand(
+        &mut leveller,
+        &settings,
+        attitude(0.0, 0.0),
+        attitude(0.0, 0.0),
+        0.1,
+    );
+    assert!(leveller.correction().pitch < limit - 0.01);
+}
+
+#[test]
+fn correction_is_rate_limited() {
+    let settings = enabled();
+    let mut leveller = Leveller::new();
+    let mut previous = 0.0;
+    for _ in 0..100 {
+        let roll = leveller
+            .update(&settings, &attitude(0.0, 0.0), Some(attitude(0.3, 0.0)), DT)
+            .roll;
+        assert!((roll - previous).abs() <= settings.config.max_rate * DT * 1.001);
+        previous = roll;
+    }
+    assert!(previous < 0.0);
+}
+
+#[test]
+fn returns_to_zero_when_off_or_without_imu() {
+    for &(enable, imu) in &[(false, true), (true, false)] {
+        let mut leveller = Leveller::new();
+        let mut settings = enabled();
+        stand(
+            &mut leveller,
+            &settings,
+            attitude(0.0, 0.0),
+            attitude(0.2, 0.0),
+            10.0,
+        );
+        settings.enabled = enable;
+        let measured = if imu { Some(attitude(0.2, 0.0)) } else { None };
+        let before = leveller.correction().roll;
+        let step = leveller
+            .update(&settings, &attitude(0.0, 0.0), measured, DT)
+            .roll;
+        // Eases out instead of jumping
+        assert!((step - before).abs() <= settings.config.max_rate * DT * 1.001);
+        for _ in 0..100 {
+            leveller.update(&settings, &attitude(0.0, 0.0), measured, DT);
+        }
+        assert_eq!(leveller.correction(), attitude(0.0, 0.0));
+    }
+}
+
+#[test]
+fn off_by_default() {
+    let settings = LevelSettings::new();
+    let mut leveller = Leveller::new();
+    stand(
+        &mut leveller,
+        &settings,
+        attitude(0.0, 0.0),
+        attitude(0.2, 0.2),
+        1.0,
+    );
+    assert_eq!(leveller.correction(), attitude(0.0, 0.0));
+}
+
+/// Femur servos of the left front and left back legs.
+const FRONT_FEMUR: usize = 1;
+const BACK_FEMUR: usize = 10;
+
+#[test]
+fn correction_reaches_the_targets() {
+    for &enable in &[true, false] {
+        let state = State::new(SerialSettings::new());
+        let mut settings = enabled();
+        settings.enabled = enable;
+        state.level.replace(settings);
+        let flat = state.targets();
+
+        // Nose up on a ramp
+        let mut leveller = Leveller::new();
+        stand(
+            &mut leveller,
+            &settings,
+            attitude(0.0, 0.0),
+            attitude(0.0, -0.2),
+            10.0,
+        );
+        state.level_correction.replace(leveller.correction());
+        let targets = state.targets();
+        if enable {
+            // The front legs shorten and the back legs lengthen
+            assert!(targets[FRONT_FEMUR] < flat[FRONT_FEMUR] - 50);
+            assert!(targets[BACK_FEMUR] > flat[BACK_FEMUR] + 50);
+            assert_eq!(targets[0], flat[0]);
+            assert_eq!(targets[2], flat[2]);
+        } else {
+            assert_eq!(targets, flat);
+        }
+    }
+}
+
+#[test]
+fn rotation_tilts_through_the_femurs() {
+    let state = State::new(SerialSettings::new());
+    state.servos.borrow_mut()[BACK_FEMUR].pulse_width = 0;
+    let flat = state.targets();
+    // Right side down
+    state
+        .rotation
+        .replace(Rotation3::from_euler_angles(0.1, 0.0, 0.0));
+    let targets = state.targets();
+    // Left legs lengthen, the right ones are mirrored and shorten
+    assert!(targets[FRONT_FEMUR] > flat[FRONT_FEMUR]);
+    assert!(targets[4 * 3 + 1] > flat[4 * 3 + 1]);
+    // Without a pulse nothing is tilted
+    assert_eq!(targets[BACK_FEMUR], 0);
+}
@@ -0,0 +1,120 @@
+use ash_carrier_core::linereader::{LineError, LineReader};
+
+/// Feed `input` and collect every completed line and error.
+fn lines<const N: usize>(
+    reader: &mut LineReader<N>,
+    input: &[u8],
+) -> Vec<Result<Vec<u8>, LineError>> {
+    let mut out = Vec::new();
+    for &b in input {
+        match reader.push(b) {
+            Ok(line) => {
+                out.push(Ok(line.to_vec()));
+                reader.clear();
+            }
+            Err(nb::Error::Other(err)) => out.push(Err(err)),
+            Err(nb::Error::WouldBlock) => {}
+        }
+    }
+    out
+}
+
+#[test]
+fn terminators() {
+    let mut reader = LineReader::<32>::new();
+    assert_eq!(
+        lines(&mut reader, b"*IDN?\nSYST:ERR?\r\n*RST\r*CLS\n"),
+        vec![
+            Ok(b"*IDN?".to_vec()),
+            Ok(b"SYST:ERR?".to_vec()),
+            Ok(b"*RST".to_vec()),
+            Ok(b"*CLS".to_vec()),
+        ]
+    );
+}
+
+#[test]
+fn empty_lines_are_reported() {
+    let mut reader = LineReader::<8>::new();
+    assert_eq!(
+        lines(&mut reader, b"\n\r\n"),
+        vec![Ok(Vec::new()), Ok(Vec::new())]
+    );
+}
+
+#[test]
+fn overflow_resynchronizes() {
+    let mut reader = LineReader::<4>::new();
+    assert_eq!(
+        lines(&mut reader, b"ABCDEFGH\nOK\n"),
+        vec![Err(LineError::Overflow), Ok(b"OK".to_vec())]
+    );
+}
+
+#[test]
+fn block_data_is_kept() {
+    let mut reader = LineReader::<32>::new();
+    let input = b"DATA #14\r\n\"#;DATA?\n";
+    assert_eq!(
+        lines(&mut reader, input),
+        vec![Ok(b"DATA #14\r\n\"#;DATA?".to_vec())]
+    );
+}
+
+#[test]
+fn terminators_in_strings_end_the_line() {
+    let mut reader = LineReader::<32>::new();
+    assert_eq!(
+        lines(&mut reader, b"A \"#1\nB\n"),
+        vec![Ok(b"A \"#1".to_vec()), Ok(b"B".to_vec())]
+    );
+}
+
+#[test]
+fn full_buffer_is_no_overflow() {
+    let mut reader = LineReader::<4>::new();
+    assert_eq!(
+        lines(&mut reader, b"ABCD\r\nABCDE\r\n"),
+        vec![Ok(b"ABCD".to_vec()), Err(LineError::Overflow)]
+    );
+}
+
+#[test]
+fn overflow_is_reported_once_per_line() {
+    let mut reader = LineReader::<2>::new();
+    // The CRLF ending the discarded line does not produce an empty line
+    assert_eq!(
+        lines(&mut reader, b"ABCDEFGHIJ\r\n\r\nOK\r"),
+        vec![Err(LineError::Overflow), Ok(Vec::new()), Ok(b"OK".to_vec())]
+    );
+}
+
+#[test]
+fn cr_and_lf_across_pushes() {
+    let mut reader = LineReader::<8>::new();
+    assert_eq!(lines(&mut reader, b"A\r"), vec![Ok(b"A".to_vec())]);
+    // The LF completing the CRLF
+    assert_eq!(lines(&mut reader, b"\nB\n"), vec![Ok(b"B".to_vec())]);
+}
+
+#[test]
+fn oversized_block_header_resynchronizes() {
+    let mut reader = LineReader::<32>::new();
+    assert_eq!(
+        lines(&mut reader, b"DATA #9999999999\n*IDN?\n"),
+        vec![Ok(b"DATA #9999999999".to_vec()), Ok(b"*IDN?".to_vec())]
+    );
+}
+
+#[test]
+fn overflow_in_block_data_resynchronizes() {
+    let mut reader = LineReader::<8>::new();
+    assert_eq!(
+        lines(&mut reader, b"DATA #18ABC\nDEFGH\n*IDN?\n"),
+        vec![
+            Err(LineError::Overflow),
+            Ok(b"DEFGH".to_vec()),
+            Ok(b"*IDN?".to_vec()),
+        ]
+    );
+}
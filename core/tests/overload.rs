@@ -0,0 +1,196 @@
+use ash_carrier_core::overload::{
+    self, CurrentSense, Protection, ProtectionLimits, ServoLoad, Thermometer, QUES_CURRENT,
+    QUES_TEMPERATURE,
+};
+use ash_carrier_core::tree::{CarrierDevice, LEGS, SERVOS};
+use embedded_hal::adc::{Channel, OneShot};
+use scpi::prelude::*;
+use std::sync::Mutex;
+
+/// The trip state is global.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn lock() -> std::sync::MutexGuard<'static, ()> {
+    let guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    overload::clear();
+    guard
+}
+
+struct FakeAdc(Option<u16>);
+struct FakePin;
+
+impl Channel<FakeAdc> for FakePin {
+    type ID = u8;
+
+    fn channel() -> u8 {
+        0
+    }
+}
+
+impl OneShot<FakeAdc, u16, FakePin> for FakeAdc {
+    type Error = ();
+
+    fn read(&mut self, _pin: &mut FakePin) -> nb::Result<u16, ()> {
+        self.0.ok_or(nb::Error::Other(()))
+    }
+}
+
+fn load(first: u32, second: u32, celsius: f32) -> ServoLoad {
+    ServoLoad {
+        milliamps: [first, second],
+        celsius: Some(celsius),
+    }
+}
+
+#[test]
+fn current_is_scaled_and_filtered() {
+    let mut sense = CurrentSense::new(FakePin);
+    // 1 V, 10 A
+    let mut adc = FakeAdc(Some(1241));
+    for _ in 0..50 {
+        sense.sample(&mut adc);
+    }
+    assert!(
+        (9990..=10000).contains(&sense.milliamps()),
+        "{}",
+        sense.milliamps()
+    );
+    // Failed conversions are skipped
+    sense.sample(&mut FakeAdc(None));
+    assert!((9990..=10000).contains(&sense.milliamps()));
+}
+
+#[test]
+fn temperature_is_converted() {
+    let mut thermometer = Thermometer::new(FakePin);
+    assert_eq!(thermometer.celsius(), None);
+    // 0.86 V is 65 °C
+    thermometer.sample(&mut FakeAdc(Some(1067)));
+    let celsius = thermometer.celsius().unwrap();
+    assert!((celsius - 65.0).abs() < 0.5, "{}", celsius);
+}
+
+/// Checks until every leg was probed.
+const BANK_TRIP_STEPS: u32 = (Protection::TRIP_MS + LEGS as u32 * Protection::PROBE_MS) / 10;
+
+#[test]
+fn jammed_leg_trips_alone() {
+    let _lock = lock();
+    let limits = ProtectionLimits::new();
+    let mut protection = Protection::new();
+    let over = load(0, limits.milliamps + 1, 30.0);
+    for _ in 0..Protection::TRIP_MS / 10 {
+        protection.check(&limits, &over, 10);
+    }
+    // The first leg is switched off to see if it draws the current
+    let mut targets = [1500u16; SERVOS];
+    overload::hold_off_legs(&mut targets);
+    assert_eq!(&targets[..4], &[0, 0, 0, 1500]);
+    for _ in 0..Protection::PROBE_MS / 10 {
+        protection.check(&limits, &over, 10);
+    }
+    // It doesn't, the second leg is
+    for _ in 0..Protection::PROBE_MS / 10 {
+        protection.check(&limits, &load(0, 0, 30.0), 10);
+    }
+    let mut expected = [false; LEGS];
+    expected[1] = true;
+    assert_eq!(overload::tripped_legs(), expected);
+    assert_eq!(overload::tripped(), [false, false]);
+    let mut targets = [1500u16; SERVOS];
+    overload::hold_off_legs(&mut targets);
+    assert_eq!(&targets[..7], &[1500, 1500, 1500, 0, 0, 0, 1500]);
+
+    // Stays tripped until cleared
+    protection.check(&limits, &load(0, 0, 30.0), 10);
+    assert_eq!(overload::tripped_legs(), expected);
+    overload::clear();
+    assert_eq!(overload::tripped_legs(), [false; LEGS]);
+}
+
+#[test]
+fn stalled_bank_trips_when_no_leg_draws_it() {
+    let _lock = lock();
+    let limits = ProtectionLimits::new();
+    let mut protection = Protection::new();
+    let over = load(0, limits.milliamps + 1, 30.0);
+    for _ in 1..BANK_TRIP_STEPS {
+        protection.check(&limits, &over, 10);
+    }
+    assert_eq!(overload::tripped(), [false, false]);
+    protection.check(&limits, &over, 10);
+    assert_eq!(overload::tripped(), [false, true]);
+    assert_eq!(overload::tripped_legs(), [false; LEGS]);
+    // No leg is switched off for the probe anymore
+    let mut targets = [1500u16; SERVOS];
+    overload::hold_off_legs(&mut targets);
+    assert_eq!(targets, [1500; SERVOS]);
+
+    // Stays tripped with the bank off until cleared
+    protection.check(&limits, &load(0, 0, 30.0), 10);
+    assert_eq!(overload::tripped(), [false, true]);
+    overload::clear();
+    assert_eq!(overload::tripped(), [false, false]);
+}
+
+#[test]
+fn short_peaks_do_not_trip() {
+    let _lock = lock();
+    let limits = ProtectionLimits::new();
+    let mut protection = Protection::new();
+    let steps = Protection::TRIP_MS / 10;
+    for _ in 0..10 {
+        for _ in 1..steps {
+            protection.check(&limits, &load(limits.milliamps + 1, 0, 30.0), 10);
+        }
+        protection.check(&limits, &load(limits.milliamps, 0, 30.0), 10);
+    }
+    assert_eq!(overload::tripped(), [false, false]);
+}
+
+#[test]
+fn overheating_trips_every_bank() {
+    let _lock = lock();
+    let limits = ProtectionLimits::new();
+    let mut protection = Protection::new();
+    protection.check(&limits, &load(0, 0, limits.celsius), 10);
+    assert_eq!(overload::tripped(), [false, false]);
+    protection.check(&limits, &load(0, 0, limits.celsius + 1.0), 10);
+    assert_eq!(overload::tripped(), [true, true]);
+    overload::clear();
+}
+
+#[test]
+fn trips_are_questionable() {
+    let _lock = lock();
+    let mut device = CarrierDevice;
+    let mut errors = ArrayErrorQueue::<[Error; 10]>::new();
+    let tree = Node {
+        name: b"ROOT",
+        optional: false,
+        handler: None,
+        sub: &[],
+    };
+    let mut context = Context::new(&mut device, &mut errors, &tree);
+    context.questionable.ptr_filter = 0xFFFF;
+
+    let limits = ProtectionLimits::new();
+    let mut protection = Protection::new();
+    for _ in 0..BANK_TRIP_STEPS {
+        protection.check(&limits, &load(limits.milliamps + 1, 0, 30.0), 10);
+    }
+    overload::report(&mut context);
+    assert_eq!(context.questionable.condition, QUES_CURRENT);
+    protection.check(&limits, &load(0, 0, limits.celsius + 1.0), 10);
+    overload::report(&mut context);
+    assert_eq!(
+        context.questionable.condition,
+        QUES_CURRENT | QUES_TEMPERATURE
+    );
+
+    overload::clear();
+    overload::report(&mut context);
+    assert_eq!(context.questionable.condition, 0);
+    // The trips remain as events
+    assert_eq!(context.questionable.event, QUES_CURRENT | QUES_TEMPERATURE);
+}
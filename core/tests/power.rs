@@ -0,0 +1,131 @@
+use ash_carrier_core::power::{PowerSequencer, PowerSettings, PowerState};
+use ash_carrier_core::tree::{LEGS, SERVOS};
+use embedded_hal::digital::v2::OutputPin;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Servo rail switch, clones share the state.
+#[derive(Clone, Default)]
+struct Rail(Rc<Cell<bool>>);
+
+impl Rail {
+    fn is_on(&self) -> bool {
+        self.0.get()
+    }
+}
+
+impl OutputPin for Rail {
+    type Error = ();
+
+    fn set_low(&mut self) -> Result<(), ()> {
+        self.0.set(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), ()> {
+        self.0.set(true);
+        Ok(())
+    }
+}
+
+const TARGETS: [u16; SERVOS] = [1500; SERVOS];
+
+fn on(stagger_ms: u32) -> PowerSettings {
+    PowerSettings {
+        on: true,
+        stagger_ms,
+    }
+}
+
+/// Legs getting pulses.
+fn released(targets: &[u16; SERVOS]) -> usize {
+    let legs = targets
+        .chunks(SERVOS / LEGS)
+        .take_while(|leg| leg.iter().all(|t| *t == 1500))
+        .count();
+    assert!(
+        targets[legs * SERVOS / LEGS..].iter().all(|t| *t == 0),
+        "{:?}",
+        targets
+    );
+    legs
+}
+
+fn step(power: &mut PowerSequencer<Rail>, settings: &PowerSettings, now_ms: u32) -> usize {
+    let mut targets = TARGETS;
+    power.update(settings, &mut targets, now_ms);
+    power.written(settings, now_ms);
+    released(&targets)
+}
+
+#[test]
+fn off_at_boot_with_pulses() {
+    let rail = Rail(Rc::new(Cell::new(true)));
+    let mut power = PowerSequencer::new(rail.clone());
+    assert!(!rail.is_on());
+    assert_eq!(step(&mut power, &PowerSettings::new(), 0), LEGS);
+    assert!(!rail.is_on());
+    assert_eq!(power.state(), PowerState::Off);
+}
+
+#[test]
+fn legs_are_staggered() {
+    let rail = Rail::default();
+    let mut power = PowerSequencer::new(rail.clone());
+    let settings = on(50);
+
+    // Held without pulses until that reached the controllers
+    let mut targets = TARGETS;
+    power.update(&settings, &mut targets, 0);
+    assert_eq!(released(&targets), 0);
+    assert_eq!(power.state(), PowerState::Arming);
+    assert!(!rail.is_on());
+    power.written(&settings, 0);
+    assert!(rail.is_on());
+
+    for legs in 1..LEGS {
+        let start = legs as u32 * 50 - 50;
+        assert_eq!(step(&mut power, &settings, start + 20), legs);
+        assert_eq!(power.state(), PowerState::Starting(legs));
+        assert_eq!(step(&mut power, &settings, start + 49), legs);
+    }
+    assert_eq!(step(&mut power, &settings, LEGS as u32 * 50), LEGS);
+    assert_eq!(power.state(), PowerState::On);
+}
+
+#[test]
+fn all_at_once_without_stagger() {
+    let rail = Rail::default();
+    let mut power = PowerSequencer::new(rail.clone());
+    assert_eq!(step(&mut power, &on(0), 0), LEGS);
+    assert_eq!(power.state(), PowerState::On);
+    assert!(rail.is_on());
+}
+
+#[test]
+fn rail_stays_off_until_written() {
+    let rail = Rail::default();
+    let mut power = PowerSequencer::new(rail.clone());
+    let mut targets = TARGETS;
+    for now in 0..10 {
+        power.update(&on(50), &mut targets, now * 20);
+    }
+    assert_eq!(power.state(), PowerState::Arming);
+    assert!(!rail.is_on());
+}
+
+#[test]
+fn off_cuts_the_rail_at_once() {
+    let rail = Rail::default();
+    let mut power = PowerSequencer::new(rail.clone());
+    step(&mut power, &on(50), 0);
+    step(&mut power, &on(50), 20);
+    assert!(rail.is_on());
+    assert_eq!(step(&mut power, &PowerSettings::new(), 40), LEGS);
+    assert_eq!(power.state(), PowerState::Off);
+    assert!(!rail.is_on());
+
+    // And starts over
+    step(&mut power, &on(50), 60);
+    assert_eq!(power.state(), PowerState::Starting(1));
+}
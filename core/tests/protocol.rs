@@ -0,0 +1,100 @@
+use ash_carrier_core::protocol::*;
+
+fn decode(frame: &[u8]) -> Vec<Result<Packet, FrameError>> {
+    let mut decoder = FrameDecoder::new();
+    frame.iter().filter_map(|b| decoder.push(*b)).collect()
+}
+
+#[test]
+fn crc_check_value() {
+    assert_eq!(crc16(b"123456789"), 0x29B1);
+}
+
+#[test]
+fn cobs_round_trip() {
+    let data = [0x11, 0x00, 0x00, 0x22, 0x33, 0x00];
+    let mut encoded = [0u8; 16];
+    let n = cobs_encode(&data, &mut encoded).unwrap();
+    assert!(!encoded[..n].contains(&0));
+
+    let mut decoded = [0u8; 16];
+    let m = cobs_decode(&encoded[..n], &mut decoded).unwrap();
+    assert_eq!(&decoded[..m], &data);
+}
+
+#[test]
+fn cobs_long_run() {
+    let data = [0xAAu8; 300];
+    let mut encoded = [0u8; 310];
+    let n = cobs_encode(&data, &mut encoded).unwrap();
+    let mut decoded = [0u8; 310];
+    let m = cobs_decode(&encoded[..n], &mut decoded).unwrap();
+    assert_eq!(&decoded[..m], &data[..]);
+}
+
+#[test]
+fn packets_round_trip() {
+    let mut targets = [1500u16; SERVOS];
+    targets[3] = 0;
+    let messages = [
+        Message::ServoTargets(targets),
+        Message::BodyPose {
+            rotation: [0.1, -0.2, 0.0],
+            translation: [0.0, 0.0, 0.05],
+        },
+        Message::ReadTelemetry,
+        Message::Scpi,
+        Message::Ack,
+        Message::Nack(NackReason::Corrupt),
+        Message::Telemetry {
+            targets,
+            estop: true,
+        },
+    ];
+    for (seq, message) in messages.iter().enumerate() {
+        let packet = Packet::new(seq as u8, *message);
+        let mut frame = [0u8; MAX_FRAME];
+        let n = packet.encode(&mut frame);
+        assert_eq!(frame[n - 1], 0);
+        assert_eq!(decode(&frame[..n]), vec![Ok(packet)]);
+    }
+}
+
+#[test]
+fn corruption_is_detected() {
+    let mut frame = [0u8; MAX_FRAME];
+    let n = Packet::new(7, Message::ReadTelemetry).encode(&mut frame);
+    frame[2] ^= 0x40;
+    assert_eq!(decode(&frame[..n]), vec![Err(FrameError::Crc)]);
+}
+
+#[test]
+fn wrong_length_keeps_sequence() {
+    let mut frame = [0u8; MAX_FRAME];
+    let n = encode_frame(9, 0x01, &[1, 2, 3], &mut frame);
+    let result = decode(&frame[..n]);
+    assert_eq!(result, vec![Err(FrameError::Length(9))]);
+    assert_eq!(FrameError::Length(9).seq(), 9);
+}
+
+#[test]
+fn overflow_is_reported_once() {
+    let mut input = vec![0x55u8; MAX_FRAME + 10];
+    input.push(0);
+    let mut frame = [0u8; MAX_FRAME];
+    let n = Packet::new(1, Message::Ack).encode(&mut frame);
+    input.extend_from_slice(&frame[..n]);
+    assert_eq!(
+        decode(&input),
+        vec![Err(FrameError::Overflow), Ok(Packet::new(1, Message::Ack))]
+    );
+}
+
+#[test]
+fn repeated_delimiters_are_ignored() {
+    let mut frame = [0u8; MAX_FRAME];
+    let n = Packet::new(2, Message::Scpi).encode(&mut frame);
+    let mut input = vec![0, 0, 0];
+    input.extend_from_slice(&frame[..n]);
+    assert_eq!(decode(&input), vec![Ok(Packet::new(2, Message::Scpi))]);
+}
@@ -0,0 +1,66 @@
+use ash_carrier_core::pwm::{PwmSettings, PwmTiming};
+
+fn timing(frequency: u16, trim: f32) -> PwmTiming {
+    PwmSettings { frequency, trim }.timing()
+}
+
+#[test]
+fn prescale_follows_the_oscillator() {
+    // 25 MHz / 4096 / 122
+    assert_eq!(timing(50, 1.0).prescale, 121);
+    assert_eq!(timing(200, 1.0).prescale, 30);
+    assert_eq!(timing(333, 1.0).prescale, 17);
+    // A 4 % fast oscillator divides by 4 % more
+    assert_eq!(timing(50, 1.04).prescale, 126);
+}
+
+#[test]
+fn prescale_is_limited() {
+    assert_eq!(timing(40, 1.1).prescale, 167);
+    let slowest = PwmSettings {
+        frequency: 1,
+        trim: 1.1,
+    };
+    assert_eq!(slowest.timing().prescale, 255);
+    let fastest = PwmSettings {
+        frequency: 5000,
+        trim: 0.9,
+    };
+    assert_eq!(fastest.timing().prescale, 3);
+}
+
+#[test]
+fn pulse_widths_keep_their_length() {
+    for &(frequency, trim) in &[(50, 1.0), (200, 1.0), (333, 0.95), (60, 1.1)] {
+        let timing = timing(frequency, trim);
+        for &us in &[500u16, 1500, 2500] {
+            let counts = timing.counts(us);
+            let actual = counts as f32 * timing.count_us;
+            assert!(
+                (actual - us as f32).abs() <= timing.count_us / 2.0,
+                "{} Hz trim {}: {} µs is {} counts",
+                frequency,
+                trim,
+                us,
+                counts
+            );
+        }
+    }
+}
+
+#[test]
+fn counts_stay_within_the_period() {
+    let timing = timing(333, 1.0);
+    assert_eq!(timing.counts(0), 0);
+    assert_eq!(timing.counts(1500), 2083);
+    assert_eq!(timing.counts(u16::MAX), 4095);
+}
+
+#[test]
+fn actual_frequency() {
+    assert!((timing(50, 1.0).frequency() - 50.03).abs() < 0.01);
+    // The prescaler can not hit 333 Hz exactly
+    assert!((timing(333, 1.0).frequency() - 339.08).abs() < 0.01);
+    // Close with a slow oscillator too
+    assert!((timing(40, 0.9).frequency() - 40.0).abs() < 0.2);
+}
@@ -0,0 +1,71 @@
+use ash_carrier_core::rosserial::*;
+
+fn frame(topic: u16, write: impl FnOnce(&mut Writer)) -> Vec<u8> {
+    let mut writer = Writer::new();
+    write(&mut writer);
+    let mut out = Vec::new();
+    writer.send(topic, |bytes| out.extend_from_slice(bytes));
+    out
+}
+
+fn received(input: &[u8]) -> Vec<(u16, Vec<u8>)> {
+    let mut decoder = Decoder::new();
+    input
+        .iter()
+        .filter_map(|b| decoder.push(*b).map(|(t, d)| (t, d.to_vec())))
+        .collect()
+}
+
+#[test]
+fn message_layout() {
+    // Time request as sent by rosserial_python
+    assert_eq!(
+        frame(ID_TIME, |_| {}),
+        [0xFF, 0xFE, 0x00, 0x00, 0xFF, 0x0A, 0x00, 0xF5]
+    );
+}
+
+#[test]
+fn round_trip() {
+    let input = frame(100, |w| {
+        w.u32(7).f64(1.5).string(b"cmd_vel");
+    });
+    let messages = received(&input);
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].0, 100);
+
+    let mut r = Reader::new(&messages[0].1);
+    assert_eq!(r.u32(), Some(7));
+    assert_eq!(r.f64(), Some(1.5));
+    assert_eq!(r.string(), Some(&b"cmd_vel"[..]));
+    assert_eq!(r.u32(), None);
+}
+
+#[test]
+fn resynchronizes_after_garbage() {
+    let mut input = vec![0x00, 0xFF, 0x12, 0xFF];
+    input.extend(frame(101, |w| {
+        w.u8(1);
+    }));
+    assert_eq!(received(&input), vec![(101, vec![1])]);
+}
+
+#[test]
+fn damaged_messages_are_dropped() {
+    let mut damaged = frame(100, |w| {
+        w.u32(0x1234);
+    });
+    damaged[8] ^= 1;
+    let mut input = damaged;
+    input.extend(frame(ID_TX_STOP, |_| {}));
+    assert_eq!(received(&input), vec![(ID_TX_STOP, vec![])]);
+}
+
+#[test]
+fn overflow_sends_nothing() {
+    let data = [0u8; MAX_MESSAGE + 1];
+    assert!(frame(100, |w| {
+        w.bytes(&data);
+    })
+    .is_empty());
+}
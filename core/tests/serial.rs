@@ -0,0 +1,17 @@
+use ash_carrier_core::serial::*;
+use std::sync::atomic::Ordering;
+
+#[test]
+fn errors_are_counted_by_kind() {
+    error(ERR_PARITY);
+    error(ERR_FRAMING);
+    assert_eq!(take_errors(), ERR_PARITY | ERR_FRAMING);
+    assert_eq!(take_errors(), 0);
+
+    // Unknown errors are no parity errors
+    error(0x80);
+    assert_eq!(take_errors(), ERR_OTHER);
+    assert_eq!(STATS.parity.load(Ordering::Relaxed), 1);
+    assert_eq!(STATS.other.load(Ordering::Relaxed), 1);
+    assert_eq!(STATS.errors(), 3);
+}
@@ -0,0 +1,48 @@
+use ash_carrier_core::pwm::PwmSettings;
+use ash_carrier_core::settings::*;
+
+const BAUD_MAX: u32 = 1_500_000;
+
+#[test]
+fn stored_form_round_trip() {
+    let mut settings = Settings::new();
+    settings.serial = SerialSettings {
+        baud: 115200,
+        parity: Parity::Even,
+        stop_bits: StopBits::Two,
+    };
+    settings.pwm = PwmSettings {
+        frequency: 333,
+        trim: 1.0234,
+    };
+    assert_eq!(Settings::from_words(&settings.to_words(), BAUD_MAX), Some(settings));
+}
+
+#[test]
+fn damaged_or_blank_is_rejected() {
+    let mut words = Settings::new().to_words();
+    words[1] ^= 1;
+    assert_eq!(Settings::from_words(&words, BAUD_MAX), None);
+    assert_eq!(Settings::from_words(&[0; WORDS], BAUD_MAX), None);
+}
+
+#[test]
+fn out_of_range_pwm_is_rejected() {
+    let mut settings = Settings::new();
+    settings.pwm.frequency = 1000;
+    assert_eq!(Settings::from_words(&settings.to_words(), BAUD_MAX), None);
+    settings.pwm = PwmSettings::new();
+    settings.pwm.trim = f32::NAN;
+    assert_eq!(Settings::from_words(&settings.to_words(), BAUD_MAX), None);
+}
+
+#[test]
+fn unreachable_baud_is_rejected() {
+    let mut settings = Settings::new();
+    settings.serial.baud = BAUD_MAX;
+    assert_eq!(Settings::from_words(&settings.to_words(), BAUD_MAX), Some(settings));
+    settings.serial.baud = BAUD_MAX + 1;
+    assert_eq!(Settings::from_words(&settings.to_words(), BAUD_MAX), None);
+    settings.serial.baud = 0;
+    assert_eq!(Settings::from_words(&settings.to_words(), BAUD_MAX), None);
+}
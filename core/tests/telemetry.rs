@@ -0,0 +1,135 @@
+//! Telemetry records, their scheduling and how a port hands them out.
+
+use ash_carrier_core::carrier_tree;
+use ash_carrier_core::crash::{CrashLog, CrashRecord};
+use ash_carrier_core::port::ScpiPort;
+use ash_carrier_core::protocol::{cobs_decode, MAX_FRAME};
+use ash_carrier_core::settings::{Parity, SerialSettings, StopBits};
+use ash_carrier_core::telemetry::*;
+use ash_carrier_core::tree::{CarrierDevice, Commands, State};
+use scpi::ieee488::commands::*;
+use scpi::prelude::*;
+use scpi::scpi::commands::*;
+
+struct NoCrashLog;
+
+impl CrashLog for NoCrashLog {
+    fn last(&self) -> Option<CrashRecord> {
+        None
+    }
+
+    fn clear(&self) {}
+}
+
+fn snapshot(time_ms: u32) -> Snapshot {
+    Snapshot {
+        time_ms,
+        battery_mv: 7400,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn gait_phase_field() {
+    let mut record = CsvRecord::new();
+    write_csv(&snapshot(0), FIELD_BATTERY | FIELD_GAIT, &mut record);
+    assert_eq!(record.as_str(), "TLM 0,7400,-1\n");
+
+    let walking = Snapshot {
+        gait_phase: Some(250),
+        ..snapshot(0)
+    };
+    let mut record = CsvRecord::new();
+    write_csv(&walking, FIELD_GAIT, &mut record);
+    assert_eq!(record.as_str(), "TLM 0,250\n");
+}
+
+#[test]
+fn binary_record_with_all_fields_fits_a_packet() {
+    let mut frame = [0u8; MAX_FRAME];
+    let n = write_binary(
+        &snapshot(0),
+        FIELD_ALL,
+        &mut Scheduler::default(),
+        &mut frame,
+    );
+    let mut packet = [0u8; MAX_FRAME];
+    let m = cobs_decode(&frame[..n - 1], &mut packet).unwrap();
+    // seq, kind, mask, time, 48 widths, battery, timing, errors, gait, CRC
+    assert_eq!(m, 2 + 1 + 4 + 96 + 4 + 8 + 9 + 2 + 2);
+    assert_eq!(&packet[m - 4..m - 2], &[0xFF, 0xFF]);
+}
+
+#[test]
+fn line_rate_bounds_the_record_rate() {
+    let config = TelemetryConfig {
+        rate: 100,
+        ..Default::default()
+    };
+    let serial = SerialSettings::new();
+    assert_eq!(serial.chars_per_second(), 960);
+
+    // A full record takes far longer than 10 ms at 9600 baud
+    let mut record = CsvRecord::new();
+    write_csv(&snapshot(0), FIELD_ALL, &mut record);
+    let busy_ms = (record.len() as u32 * 1000).div_ceil(960);
+    assert!(busy_ms > 10);
+
+    let mut scheduler = Scheduler::default();
+    assert!(scheduler.due(&config, 0));
+    scheduler.sent(0, record.len(), serial.chars_per_second());
+    assert!(!scheduler.due(&config, 10));
+    assert!(!scheduler.due(&config, busy_ms - 1));
+    assert!(scheduler.due(&config, busy_ms));
+
+    let fast = SerialSettings {
+        baud: 921_600,
+        ..serial
+    };
+    let mut scheduler = Scheduler::default();
+    assert!(scheduler.due(&config, 0));
+    scheduler.sent(0, record.len(), fast.chars_per_second());
+    assert!(scheduler.due(&config, 10));
+}
+
+#[test]
+fn framing_bits_count() {
+    let serial = SerialSettings {
+        baud: 12000,
+        parity: Parity::Even,
+        stop_bits: StopBits::Two,
+    };
+    assert_eq!(serial.chars_per_second(), 1000);
+}
+
+#[test]
+fn drops_are_counted_and_reported_once() {
+    let state = State::new(SerialSettings::new());
+    let commands = Commands::new(&state, &NoCrashLog, 115200);
+    let framed_handler = state.framed_handler();
+    let ros_bridge = state.ros_bridge();
+    let tree = carrier_tree!(commands, b"test");
+    let mut device = CarrierDevice;
+    let mut errors = ArrayErrorQueue::<[Error; 10]>::new();
+    let mut context = Context::new(&mut device, &mut errors, tree);
+    let mut port = ScpiPort::<256>::new();
+    for c in b"SYST:TEL:RATE 100;MASK 4\n".iter() {
+        port.push(
+            *c,
+            &mut context,
+            &state.session,
+            &framed_handler,
+            &ros_bridge,
+            0,
+            |_: &[u8]| {},
+        );
+    }
+
+    let queue_full = [false, true, true, true, false, true];
+    let mut reported = Vec::new();
+    for (i, full) in queue_full.iter().enumerate() {
+        reported.push(port.telemetry(&snapshot(i as u32 * 10), |_| !*full));
+    }
+    assert_eq!(reported, [false, true, false, false, false, true]);
+    assert_eq!(port.dropped(), 4);
+}
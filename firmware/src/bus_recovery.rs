@@ -0,0 +1,60 @@
+use cortex_m::asm;
+use stm32f4xx_hal::stm32::{GPIOB, I2C2};
+
+/// Half a SCL period at 100 kHz with a 48 MHz core clock.
+const HALF_PERIOD: u32 = 240;
+
+/// Free a stuck I2C2 bus (SCL = PB10, SDA = PB11) and reset the peripheral.
+///
+/// A slave that lost track of the transfer may hold SDA low forever. The pins
+/// are temporarily taken over as GPIO to clock out up to nine bits until SDA
+/// is released, followed by a STOP condition. The peripheral is then reset
+/// with its timing registers restored, so the HAL driver can keep using it.
+pub fn recover_i2c2() {
+    let gpiob = unsafe { &*GPIOB::ptr() };
+    let i2c = unsafe { &*I2C2::ptr() };
+
+    let cr2 = i2c.cr2.read().bits();
+    let ccr = i2c.ccr.read().bits();
+    let trise = i2c.trise.read().bits();
+    i2c.cr1.modify(|_, w| w.pe().clear_bit());
+
+    // Both lines are already open-drain, release them before switching mode
+    gpiob.bsrr.write(|w| w.bs10().set_bit().bs11().set_bit());
+    gpiob
+        .moder
+        .modify(|_, w| w.moder10().output().moder11().output());
+
+    for _ in 0..9 {
+        if gpiob.idr.read().idr11().bit_is_set() {
+            break;
+        }
+        gpiob.bsrr.write(|w| w.br10().set_bit());
+        asm::delay(HALF_PERIOD);
+        gpiob.bsrr.write(|w| w.bs10().set_bit());
+        asm::delay(HALF_PERIOD);
+    }
+
+    // STOP condition, SDA rising while SCL is high
+    gpiob.bsrr.write(|w| w.br10().set_bit());
+    asm::delay(HALF_PERIOD);
+    gpiob.bsrr.write(|w| w.br11().set_bit());
+    asm::delay(HALF_PERIOD);
+    gpiob.bsrr.write(|w| w.bs10().set_bit());
+    asm::delay(HALF_PERIOD);
+    gpiob.bsrr.write(|w| w.bs11().set_bit());
+    asm::delay(HALF_PERIOD);
+
+    gpiob
+        .moder
+        .modify(|_, w| w.moder10().alternate().moder11().alternate());
+
+    i2c.cr1.write(|w| w.swrst().set_bit());
+    i2c.cr1.write(|w| w.swrst().clear_bit());
+    unsafe {
+        i2c.cr2.write(|w| w.bits(cr2));
+        i2c.ccr.write(|w| w.bits(ccr));
+        i2c.trise.write(|w| w.bits(trise));
+    }
+    i2c.cr1.modify(|_, w| w.pe().set_bit());
+}
@@ -0,0 +1,54 @@
+use core::cell::Cell;
+use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::interrupt::{self, Mutex};
+use cortex_m::peripheral::{DCB, DWT};
+
+/// Cycle count at the last whole millisecond and the milliseconds up to it.
+#[derive(Copy, Clone)]
+struct Millis {
+    cycles: u32,
+    millis: u32,
+}
+
+static MILLIS: Mutex<Cell<Millis>> = Mutex::new(Cell::new(Millis {
+    cycles: 0,
+    millis: 0,
+}));
+static CYCLES_PER_US: AtomicU32 = AtomicU32::new(1);
+
+/// Start the DWT cycle counter, also the monotonic timer of the tasks.
+pub fn init(dcb: &mut DCB, dwt: &mut DWT, sysclk: u32) {
+    CYCLES_PER_US.store(sysclk / 1_000_000, Ordering::Relaxed);
+    dcb.enable_trace();
+    dwt.enable_cycle_counter();
+}
+
+/// Milliseconds since the cycle counter was started, from the monotonic
+/// timer of the tasks. Has to be called at least once per wrap of the
+/// counter, ~89 s at 48 MHz, which `servo_output` does.
+pub fn millis() -> u32 {
+    let cycles_per_ms = CYCLES_PER_US.load(Ordering::Relaxed) * 1000;
+    interrupt::free(|cs| {
+        let last = MILLIS.borrow(cs);
+        let mut now = last.get();
+        let elapsed = DWT::cycle_count().wrapping_sub(now.cycles) / cycles_per_ms;
+        now.cycles = now.cycles.wrapping_add(elapsed * cycles_per_ms);
+        now.millis = now.millis.wrapping_add(elapsed);
+        last.set(now);
+        now.millis
+    })
+}
+
+/// Microsecond stopwatch for short intervals, wraps after ~89 s at 48 MHz.
+#[derive(Copy, Clone)]
+pub struct Stopwatch(u32);
+
+impl Stopwatch {
+    pub fn start() -> Self {
+        Stopwatch(DWT::cycle_count())
+    }
+
+    pub fn elapsed_us(&self) -> u32 {
+        DWT::cycle_count().wrapping_sub(self.0) / CYCLES_PER_US.load(Ordering::Relaxed)
+    }
+}
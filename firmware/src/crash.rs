@@ -0,0 +1,93 @@
+use core::fmt::Write;
+use core::mem::MaybeUninit;
+use core::panic::PanicInfo;
+use core::ptr;
+
+use cortex_m::interrupt;
+use cortex_m::iprintln;
+use cortex_m::peripheral::{ITM, SCB};
+use cortex_m_rt::ExceptionFrame;
+
+use ash_carrier_core::crash::{CrashKind, CrashLog, CrashRecord};
+
+#[link_section = ".uninit.CRASH"]
+static mut CRASH: MaybeUninit<CrashRecord> = MaybeUninit::uninit();
+
+/// Crash record kept in RAM that is not initialized by the runtime.
+pub struct UninitCrashLog;
+
+impl CrashLog for UninitCrashLog {
+    fn last(&self) -> Option<CrashRecord> {
+        last()
+    }
+
+    fn clear(&self) {
+        clear()
+    }
+}
+
+/// Get the record left by the previous run, if any.
+pub fn last() -> Option<CrashRecord> {
+    let record = unsafe { ptr::read_volatile(ptr::addr_of!(CRASH).cast::<CrashRecord>()) };
+    if record.is_valid() {
+        Some(record)
+    } else {
+        None
+    }
+}
+
+/// Forget the record left by the previous run.
+pub fn clear() {
+    unsafe {
+        let mut record = ptr::read_volatile(ptr::addr_of!(CRASH).cast::<CrashRecord>());
+        record.invalidate();
+        ptr::write_volatile(ptr::addr_of_mut!(CRASH).cast::<CrashRecord>(), record);
+    }
+}
+
+unsafe fn record(kind: CrashKind) -> &'static mut CrashRecord {
+    let record = &mut *ptr::addr_of_mut!(CRASH).cast::<CrashRecord>();
+    record.begin(kind);
+    let scb = &*SCB::ptr();
+    record.cfsr = scb.cfsr.read();
+    record.hfsr = scb.hfsr.read();
+    record.mmfar = scb.mmfar.read();
+    record.bfar = scb.bfar.read();
+    record
+}
+
+/// Reset so the robot comes back up, or stop if a debugger is attached.
+fn restart() -> ! {
+    const DHCSR_C_DEBUGEN: u32 = 1;
+    let dhcsr = unsafe { ptr::read_volatile(0xE000_EDF0 as *const u32) };
+    if dhcsr & DHCSR_C_DEBUGEN != 0 {
+        cortex_m::asm::bkpt();
+    }
+    SCB::sys_reset()
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    interrupt::disable();
+
+    let record = unsafe { record(CrashKind::Panic) };
+    write!(record, "{}", info).ok();
+    record.commit();
+
+    let itm = unsafe { &mut *ITM::PTR };
+    iprintln!(&mut itm.stim[0], "{}", info);
+
+    restart()
+}
+
+/// Record a HardFault, to be called from the exception handler.
+pub fn hard_fault(ef: &ExceptionFrame) -> ! {
+    interrupt::disable();
+
+    let record = unsafe { record(CrashKind::HardFault) };
+    record.frame = [ef.r0, ef.r1, ef.r2, ef.r3, ef.r12, ef.lr, ef.pc, ef.xpsr];
+    write!(record, "HardFault at {:#010x}", ef.pc).ok();
+    record.commit();
+
+    restart()
+}
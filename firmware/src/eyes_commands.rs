@@ -1,5 +1,4 @@
 use core::cell::RefCell;
-use core::convert::{TryFrom, TryInto};
 use scpi::error::Result;
 use scpi::prelude::*;
 
@@ -18,11 +17,11 @@ struct BodyEyeLookCommand<'a> {
 }
 
 impl<'a> Command for BodyEyeLookCommand<'a> {
-    fn event(&self, context: &mut Context, args: &mut Tokenizer) -> Result<()> {
+    fn event(&self, _context: &mut Context, _args: &mut Tokenizer) -> Result<()> {
         unimplemented!()
     }
 
-    fn query(&self, context: &mut Context, args: &mut Tokenizer, response: &mut ResponseUnit) -> Result<()> {
+    fn query(&self, _context: &mut Context, _args: &mut Tokenizer, _response: &mut ResponseUnit) -> Result<()> {
         unimplemented!()
     }
 }
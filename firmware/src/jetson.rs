@@ -1,4 +1,4 @@
-use embedded_hal::{blocking::delay::DelayMs, digital::v2::OutputPin};
+use embedded_hal::digital::v2::OutputPin;
 
 struct Jetson<PWR, BATOC> {
     pwr: PWR,
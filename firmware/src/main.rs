@@ -0,0 +1,644 @@
+#![allow(unsafe_code)]
+// Raised by the code RTIC 0.5 generates for resources and tasks
+#![allow(static_mut_refs, non_local_definitions, unexpected_cfgs)]
+#![no_main]
+#![no_std]
+
+use cortex_m::asm;
+use cortex_m_rt::{exception, ExceptionFrame};
+use rtic::cyccnt::{Duration, U32Ext};
+
+// HAL
+use stm32f4xx_hal::stm32::{ADC1, I2C2 as I2C2_PERIPH};
+use stm32f4xx_hal::otg_fs::{UsbBus, UsbBusType, USB};
+use stm32f4xx_hal::watchdog::IndependentWatchdog;
+use usb_device::bus::UsbBusAllocator;
+use stm32f4xx_hal::adc::{
+    config::{AdcConfig, SampleTime},
+    Adc, Temperature,
+};
+use stm32f4xx_hal::{i2c, prelude::*, serial};
+
+// I2C Stuff
+use pwm_pca9685::{Pca9685, SlaveAddr};
+use shared_bus::{AtomicCheckMutex, I2cProxy};
+
+//Default commands
+use scpi::ieee488::commands::*;
+use scpi::prelude::*;
+use scpi::scpi::commands::*;
+
+// Git version
+use git_version::git_version;
+use stm32f4xx_hal::gpio::gpioa::{PA0, PA1, PA4};
+use stm32f4xx_hal::gpio::gpiob::{PB0, PB1, PB10, PB11};
+use stm32f4xx_hal::gpio::gpioc::PC;
+use stm32f4xx_hal::gpio::{AlternateOD, Analog, Edge, ExtiPin, Input, Output, PullUp, PushPull, AF4};
+
+const GIT_VERSION: &[u8] = git_version!().as_bytes();
+
+use ash_carrier_core::attitude::Attitude;
+use ash_carrier_core::contact::{self, Switch};
+use ash_carrier_core::level::{LevelSettings, Leveller};
+use ash_carrier_core::overload::{
+    self, CurrentSense, Protection, ProtectionLimits, ServoLoad, Thermometer,
+};
+use ash_carrier_core::imu::{AttitudeEstimator, Mpu6050, MPU6050_ADDRESS};
+use ash_carrier_core::port::ScpiPort;
+use ash_carrier_core::power::{PowerSequencer, PowerSettings, PowerState};
+use ash_carrier_core::pwm::PwmSettings;
+use ash_carrier_core::servo_commands::ServoControl;
+use ash_carrier_core::servo_bus::{self, BankStatus, BusHealth, ServoBank, ServoBanks};
+use ash_carrier_core::settings::{SerialSettings, Settings};
+use ash_carrier_core::tree::{CarrierDevice, Commands, State, BANKS, LEGS, SERVOS};
+use ash_carrier_core::telemetry::Snapshot;
+use ash_carrier_core::{battery::Battery, carrier_tree, estop, log};
+
+// Not wired up yet
+#[allow(dead_code)]
+mod jetson;
+mod clock;
+use clock::Stopwatch;
+// Not wired up yet
+#[allow(dead_code)]
+mod eyes_commands;
+mod bus_recovery;
+mod crash;
+use crash::UninitCrashLog;
+mod settings;
+mod uart;
+use uart::{DmaRx, DmaTx};
+mod usb;
+mod watchdog;
+
+use heapless::consts::U512;
+use heapless::spsc::{Consumer, Producer, Queue};
+
+use core::mem;
+
+//***********************************************************************************
+// # Tasks
+//
+// | Task             | Priority | Runs                                  |
+// |------------------|----------|---------------------------------------|
+// | `estop_input`    | 4        | EXTI0, emergency stop input changed   |
+// | `uart_idle`      | 3        | USART2 idle line or line error        |
+// | `uart_rx_dma`    | 3        | DMA1 stream 5, receive buffer wrapped |
+// | `control_loop`   | 2        | Every [CONTROL_PERIOD_MS]             |
+// | `servo_output`   | 1        | Spawned by `control_loop`             |
+// | `battery_monitor`| 1        | Every [BATTERY_PERIOD_MS]             |
+// | `imu_update`     | 1        | Every [IMU_PERIOD_MS]                 |
+// | `feed_watchdog`  | 1        | Every [watchdog::CHECK_MS]            |
+// | `idle`           | 0        | SCPI parsing on USART2 and USB        |
+//
+// The command tree and everything it borrows lives in `idle`. Other tasks
+// only see what is copied in and out of the shared resources.
+
+const SYSCLK_HZ: u32 = 48_000_000;
+/// Servo update rate, one update takes about 12 ms on a 100 kHz bus.
+const CONTROL_PERIOD_MS: u32 = 20;
+const BATTERY_PERIOD_MS: u32 = 10;
+const IMU_PERIOD_MS: u32 = 10;
+
+type I2c2 = i2c::I2c<I2C2_PERIPH, (PB10<AlternateOD<AF4>>, PB11<AlternateOD<AF4>>)>;
+type I2c2Proxy = I2cProxy<'static, AtomicCheckMutex<I2c2>>;
+
+/// `ms` in cycles of the monotonic timer.
+fn millis(ms: u32) -> Duration {
+    (ms * (SYSCLK_HZ / 1000)).cycles()
+}
+
+/// Move everything received on USART2 to the SCPI task.
+fn receive(rx: &mut DmaRx, queue: &mut Producer<'static, u8, U512>) {
+    rx.read(|c| {
+        if queue.enqueue(c).is_err() {
+            uart::error(uart::ERR_OVERFLOW);
+        }
+    });
+}
+
+#[rtic::app(device = stm32f4xx_hal::stm32, peripherals = true, monotonic = rtic::cyccnt::CYCCNT)]
+const APP: () = {
+    struct Resources {
+        /// Commanded pulse widths, from the SCPI task to the control loop.
+        targets: [u16; SERVOS],
+        /// Outputs, battery and timing, assembled for telemetry by the SCPI task.
+        snapshot: Snapshot,
+        /// Controllers that became unreachable or recovered since last reported.
+        #[init([BankStatus::Ok; BANKS])]
+        bus_status: [BankStatus; BANKS],
+        bus_health: [BusHealth; BANKS],
+        /// Measured attitude, from `imu_update` to the SCPI task.
+        #[init(None)]
+        attitude: Option<Attitude>,
+        /// Levelling settings and commanded roll and pitch, from the SCPI task
+        /// to `imu_update`.
+        #[init((LevelSettings::new(), Attitude { roll: 0.0, pitch: 0.0 }))]
+        level_input: (LevelSettings, Attitude),
+        /// Levelling correction, from `imu_update` to the SCPI task.
+        #[init(Attitude { roll: 0.0, pitch: 0.0 })]
+        level_correction: Attitude,
+        #[init(Leveller::new())]
+        leveller: Leveller,
+        /// Overload limits, from the SCPI task to `battery_monitor`.
+        #[init(ProtectionLimits::new())]
+        protection_limits: ProtectionLimits,
+        /// Servo currents and temperature, from `battery_monitor` to the SCPI task.
+        #[init(ServoLoad { milliamps: [0; BANKS], celsius: None })]
+        servo_load: ServoLoad,
+        #[init(Protection::new())]
+        protection: Protection,
+        /// Servo power request, from the SCPI task to `servo_output`.
+        #[init(PowerSettings::new())]
+        power_settings: PowerSettings,
+        /// Servo power sequence, from `servo_output` to the SCPI task.
+        #[init(PowerState::Off)]
+        power_state: PowerState,
+        /// PWM frequency and calibration, from the SCPI task to `servo_output`.
+        pwm_settings: PwmSettings,
+
+        estop_pin: PB0<Input<PullUp>>,
+        /// Foot contact switches of legs 1 to 8.
+        contacts: [Switch<PC<Input<PullUp>>>; LEGS],
+        rx_dma: DmaRx,
+        rx_queue: Producer<'static, u8, U512>,
+        servo_banks: ServoBanks<I2c2Proxy>,
+        power: PowerSequencer<PB1<Output<PushPull>>>,
+        attitude_estimator: AttitudeEstimator<I2c2Proxy>,
+        adc: Adc<ADC1>,
+        battery: Battery<PA0<Analog>>,
+        currents: (CurrentSense<PA1<Analog>>, CurrentSense<PA4<Analog>>),
+        thermometer: Thermometer<Temperature>,
+        iwdg: IndependentWatchdog,
+
+        rx: Consumer<'static, u8, U512>,
+        serial_tx: DmaTx,
+        usb_serial: usb::UsbSerial<'static>,
+        settings: Settings,
+        pclk1: u32,
+    }
+
+    #[init(schedule = [control_loop, battery_monitor, imu_update, feed_watchdog])]
+    fn init(cx: init::Context) -> init::LateResources {
+        static mut RX_QUEUE: Queue<u8, U512> = Queue(heapless::i::Queue::new());
+        static mut USB_EP_MEMORY: [u32; 1024] = [0; 1024];
+        static mut USB_BUS: Option<UsbBusAllocator<UsbBusType>> = None;
+
+        let dp = cx.device;
+        let mut core = cx.core;
+        // Before the watchdog check clears the reset flags
+        let button_reset = settings::reset_by_button();
+        if let Some(record) = crash::last() {
+            log::error("Reset after crash", record.kind() as u32);
+        }
+        if watchdog::caused_reset() {
+            log::error("Reset by watchdog", 0);
+        }
+        // Set up the system clock.
+        dp.RCC.apb2enr.modify(|_, w| w.syscfgen().enabled());
+        dp.RCC.ahb1enr.modify(|_, w| w.dma1en().enabled());
+        let rcc = dp.RCC.constrain();
+        let clocks = rcc.cfgr.sysclk(SYSCLK_HZ.hz()).require_pll48clk().freeze();
+        let gpioa = dp.GPIOA.split();
+        let gpiob = dp.GPIOB.split();
+        clock::init(&mut core.DCB, &mut core.DWT, clocks.sysclk().0);
+
+        /**************************************** Settings ****************************************/
+        // Pressing the reset button twice within 1.5 s restores the default line settings, in
+        // case the host can no longer talk to us
+        let mut settings = settings::load(uart::baud_max_at(clocks.pclk1().0));
+        let restore = settings::restore_requested(button_reset, || {
+            asm::delay(clocks.sysclk().0 / 2 * 3);
+        });
+        if restore {
+            settings.serial = SerialSettings::new();
+            settings::store(&settings);
+            log::warning("Serial settings restored to defaults", 0);
+        }
+
+        /**************************************** USART2 ****************************************/
+        let pa2 = gpioa.pa2.into_floating_input();
+        let pa3 = gpioa.pa3.into_push_pull_output();
+        let _usart2 = serial::Serial::usart2(
+            dp.USART2,
+            (pa2.into_alternate_af7(), pa3.into_alternate_af7()),
+            serial::config::Config::default().baudrate(9600.bps()),
+            clocks,
+        )
+        .unwrap();
+        uart::configure(clocks.pclk1().0, &settings.serial);
+        uart::init_dma();
+        let (rx_queue, rx) = RX_QUEUE.split();
+
+        /**************************************** ADC ****************************************/
+        let adc_config = AdcConfig::default().default_sample_time(SampleTime::Cycles_480);
+        let mut adc = Adc::adc1(dp.ADC1, true, adc_config);
+        adc.enable_temperature_and_vref();
+        let battery = Battery::new(gpioa.pa0.into_analog());
+        // Rail current of the even and odd servo bank
+        let currents = (
+            CurrentSense::new(gpioa.pa1.into_analog()),
+            CurrentSense::new(gpioa.pa4.into_analog()),
+        );
+        let thermometer = Thermometer::new(Temperature);
+
+        /**************************************** USB ****************************************/
+        let usb = USB {
+            usb_global: dp.OTG_FS_GLOBAL,
+            usb_device: dp.OTG_FS_DEVICE,
+            usb_pwrclk: dp.OTG_FS_PWRCLK,
+            pin_dm: gpioa.pa11.into_alternate_af10(),
+            pin_dp: gpioa.pa12.into_alternate_af10(),
+        };
+        *USB_BUS = Some(UsbBus::new(usb, USB_EP_MEMORY));
+        let usb_serial = usb::UsbSerial::new(USB_BUS.as_ref().unwrap());
+
+        /**************************************** E-stop ****************************************/
+        // PB0, active low
+        let mut syscfg = dp.SYSCFG;
+        let mut exti = dp.EXTI;
+        let mut estop_pin = gpiob.pb0.into_pull_up_input();
+        estop_pin.make_interrupt_source(&mut syscfg);
+        estop_pin.trigger_on_edge(&mut exti, Edge::RISING_FALLING);
+        estop_pin.enable_interrupt(&mut exti);
+        estop::set_input(estop_pin.is_low().unwrap());
+
+        /**************************************** Foot contacts ****************************************/
+        // PC0 to PC7, closing to ground while the foot is loaded
+        let gpioc = dp.GPIOC.split();
+        let contacts = [
+            Switch::new(gpioc.pc0.into_pull_up_input().downgrade()),
+            Switch::new(gpioc.pc1.into_pull_up_input().downgrade()),
+            Switch::new(gpioc.pc2.into_pull_up_input().downgrade()),
+            Switch::new(gpioc.pc3.into_pull_up_input().downgrade()),
+            Switch::new(gpioc.pc4.into_pull_up_input().downgrade()),
+            Switch::new(gpioc.pc5.into_pull_up_input().downgrade()),
+            Switch::new(gpioc.pc6.into_pull_up_input().downgrade()),
+            Switch::new(gpioc.pc7.into_pull_up_input().downgrade()),
+        ];
+
+        /**************************************** Servo power ****************************************/
+        // PB1, high switches the servo rail on
+        let power = PowerSequencer::new(gpiob.pb1.into_push_pull_output());
+
+        /**************************************** I2C2 ****************************************/
+        let scl = gpiob.pb10.into_alternate_af4().set_open_drain();
+        let sda = gpiob.pb11.into_alternate_af4().set_open_drain();
+        let i2c = i2c::I2c::i2c2(dp.I2C2, (scl, sda), 100.khz(), clocks);
+        // Used by servo_output and imu_update, which never preempt each other
+        let i2c_bus = shared_bus::new_atomic_check!(I2c2 = i2c).unwrap();
+        let servo_banks = ServoBanks::new(
+            ServoBank::new(
+                Pca9685::new(
+                    i2c_bus.acquire_i2c(),
+                    SlaveAddr::Alternative(false, false, false, true, true, false),
+                ),
+            ),
+            ServoBank::new(
+                Pca9685::new(
+                    i2c_bus.acquire_i2c(),
+                    SlaveAddr::Alternative(false, false, false, true, true, true),
+                ),
+            ),
+        );
+        let attitude_estimator =
+            AttitudeEstimator::new(Mpu6050::new(i2c_bus.acquire_i2c(), MPU6050_ADDRESS));
+
+        /**************************************** Watchdog ****************************************/
+        let mut iwdg = IndependentWatchdog::new(dp.IWDG);
+        iwdg.stop_on_debug(&dp.DBGMCU, true);
+        iwdg.start(watchdog::TIMEOUT_MS.ms());
+
+        cx.schedule
+            .control_loop(cx.start + millis(CONTROL_PERIOD_MS))
+            .unwrap();
+        cx.schedule
+            .battery_monitor(cx.start + millis(BATTERY_PERIOD_MS))
+            .unwrap();
+        cx.schedule
+            .imu_update(cx.start + millis(IMU_PERIOD_MS))
+            .unwrap();
+        cx.schedule
+            .feed_watchdog(cx.start + millis(watchdog::CHECK_MS))
+            .unwrap();
+
+        init::LateResources {
+            targets: [ServoControl::new().pulse_width; SERVOS],
+            snapshot: Snapshot::default(),
+            bus_health: [BusHealth::default(); BANKS],
+            pwm_settings: settings.pwm,
+            estop_pin,
+            contacts,
+            rx_dma: DmaRx::new(),
+            rx_queue,
+            servo_banks,
+            power,
+            attitude_estimator,
+            adc,
+            battery,
+            currents,
+            thermometer,
+            iwdg,
+            rx,
+            serial_tx: DmaTx::new(),
+            usb_serial,
+            settings,
+            pclk1: clocks.pclk1().0,
+        }
+    }
+
+    /// SCPI parsing, runs whenever no other task does.
+    #[idle(resources = [targets, snapshot, bus_status, bus_health, attitude, level_input, level_correction, protection_limits, servo_load, power_settings, power_state, pwm_settings, contacts, rx, serial_tx, usb_serial, settings, pclk1])]
+    fn idle(cx: idle::Context) -> ! {
+        let idle::Resources {
+            mut targets,
+            mut snapshot,
+            mut bus_status,
+            mut bus_health,
+            mut attitude,
+            mut level_input,
+            mut level_correction,
+            mut protection_limits,
+            mut servo_load,
+            mut power_settings,
+            mut power_state,
+            mut pwm_settings,
+            contacts,
+            rx,
+            serial_tx,
+            usb_serial,
+            settings,
+            pclk1,
+        } = cx.resources;
+
+        let state = State::new(settings.serial);
+        state.pwm.replace(settings.pwm);
+        let mut my_device = CarrierDevice;
+        let mut usb_device = CarrierDevice;
+
+        let commands = Commands::new(&state, &UninitCrashLog, uart::baud_max());
+        let framed_handler = state.framed_handler();
+        let ros_bridge = state.ros_bridge();
+
+        let tree = carrier_tree!(commands, GIT_VERSION);
+        let mut errors = ArrayErrorQueue::<[Error; 10]>::new();
+        let mut context = Context::new(&mut my_device, &mut errors, tree);
+        let mut port = ScpiPort::<256>::new();
+        port.set_line_rate(Some(settings.serial.chars_per_second()));
+
+        // USB gets its own context so responses and errors never mix with USART2
+        let mut usb_errors = ArrayErrorQueue::<[Error; 10]>::new();
+        let mut usb_context = Context::new(&mut usb_device, &mut usb_errors, tree);
+        let mut usb_port = ScpiPort::<256>::new();
+
+        loop {
+            watchdog::alive(watchdog::SCPI);
+
+            // Report lost or corrupted input
+            let errors = uart::take_errors();
+            if errors & (uart::ERR_OVERFLOW | uart::ERR_OVERRUN) != 0 {
+                context.push_error(ErrorCode::InputBufferOverrun.into());
+            }
+            if errors & uart::ERR_FRAMING != 0 {
+                context.push_error(ErrorCode::FramingErrorInProgramMessage.into());
+            }
+            if errors & uart::ERR_PARITY != 0 {
+                context.push_error(ErrorCode::ParityErrorInProgramMessage.into());
+            }
+            if errors & (uart::ERR_NOISE | uart::ERR_OTHER) != 0 {
+                context.push_error(ErrorCode::CommunicationError.into());
+            }
+
+            let fitted = *state.contacts_fitted.borrow();
+            state.contacts.replace(contact::read(contacts, fitted));
+
+            // SCPI communication
+            let now = clock::millis();
+            while let Some(c) = rx.dequeue() {
+                port.push(
+                    c,
+                    &mut context,
+                    &state.session,
+                    &framed_handler,
+                    &ros_bridge,
+                    now,
+                    |response| {
+                        if !serial_tx.write(response) {
+                            log::warning("UART TX queue full", response.len() as u32);
+                        }
+                    },
+                );
+            }
+            serial_tx.poll();
+            let mut usb_buf = [0u8; 64];
+            let n = usb_serial.read(&mut usb_buf);
+            for c in &usb_buf[..n] {
+                usb_port.push(
+                    *c,
+                    &mut usb_context,
+                    &state.session,
+                    &framed_handler,
+                    &ros_bridge,
+                    now,
+                    |response| {
+                        if !usb_serial.write(response) {
+                            log::warning("USB TX queue full", response.len() as u32);
+                        }
+                    },
+                );
+            }
+
+            // Apply new line settings once everything queued has been sent
+            let requested = *state.serial.borrow();
+            if requested != settings.serial && serial_tx.is_idle() {
+                uart::configure(*pclk1, &requested);
+                settings.serial = requested;
+                port.set_line_rate(Some(requested.chars_per_second()));
+                settings::store(settings);
+            }
+            let pwm = *state.pwm.borrow();
+            if pwm != settings.pwm {
+                pwm_settings.lock(|settings| *settings = pwm);
+                settings.pwm = pwm;
+                settings::store(settings);
+            }
+
+            // Exchange with the control loop
+            if estop::take_trip() {
+                state.stop();
+            }
+            let commanded = state.targets();
+            targets.lock(|targets| *targets = commanded);
+            let status = bus_status.lock(|status| mem::replace(status, [BankStatus::Ok; BANKS]));
+            servo_bus::report(&status, &mut context);
+            state.bus_health.replace(bus_health.lock(|health| *health));
+            state.attitude.replace(attitude.lock(|attitude| *attitude));
+            let (roll, pitch, _) = state.rotation.borrow().euler_angles();
+            let input = (*state.level.borrow(), Attitude { roll, pitch });
+            level_input.lock(|level_input| *level_input = input);
+            state
+                .level_correction
+                .replace(level_correction.lock(|correction| *correction));
+            let limits = *state.protection.borrow();
+            protection_limits.lock(|protection_limits| *protection_limits = limits);
+            state.servo_load.replace(servo_load.lock(|load| *load));
+            let power = *state.power.borrow();
+            power_settings.lock(|settings| *settings = power);
+            state.power_state.replace(power_state.lock(|state| *state));
+            overload::report(&mut context);
+            overload::report(&mut usb_context);
+
+            // Telemetry
+            let mut record = snapshot.lock(|snapshot| *snapshot);
+            record.time_ms = clock::millis();
+            record.targets = commanded;
+            record.serial_errors = uart::STATS.errors();
+            record.estop = estop::is_latched();
+            if port.telemetry(&record, |record| serial_tx.write(record)) {
+                log::warning("UART telemetry dropped", port.dropped());
+            }
+            if usb_port.telemetry(&record, |record| usb_serial.write(record)) {
+                log::warning("USB telemetry dropped", usb_port.dropped());
+            }
+        }
+    }
+
+    /// Emergency stop input, latches the emergency stop when active. The servo rail is cut
+    /// right here, the outputs are turned off with the next update.
+    #[task(binds = EXTI0, priority = 4, resources = [estop_pin, power])]
+    fn estop_input(cx: estop_input::Context) {
+        let pin = cx.resources.estop_pin;
+        pin.clear_interrupt_pending_bit();
+        estop::set_input(pin.is_low().unwrap_or(true));
+        if estop::is_latched() {
+            cx.resources.power.cut();
+        }
+    }
+
+    #[task(binds = USART2, priority = 3, resources = [rx_dma, rx_queue])]
+    fn uart_idle(cx: uart_idle::Context) {
+        uart::on_usart_interrupt();
+        receive(cx.resources.rx_dma, cx.resources.rx_queue);
+    }
+
+    #[task(binds = DMA1_STREAM5, priority = 3, resources = [rx_dma, rx_queue])]
+    fn uart_rx_dma(cx: uart_rx_dma::Context) {
+        uart::on_rx_dma_interrupt();
+        receive(cx.resources.rx_dma, cx.resources.rx_queue);
+    }
+
+    /// Fixed rate loop, hands the commanded pulse widths to `servo_output`.
+    #[task(priority = 2, resources = [targets], schedule = [control_loop], spawn = [servo_output])]
+    fn control_loop(cx: control_loop::Context) {
+        watchdog::alive(watchdog::CONTROL);
+
+        // Still busy with the previous update, e.g. recovering the bus
+        let _ = cx
+            .spawn
+            .servo_output(*cx.resources.targets, Stopwatch::start());
+
+        cx.schedule
+            .control_loop(cx.scheduled + millis(CONTROL_PERIOD_MS))
+            .unwrap();
+    }
+
+    /// Write pulse widths and sequence servo power. Banks are held fully off while the
+    /// emergency stop is latched or the overload protection tripped them, legs it
+    /// tripped are switched off.
+    #[task(resources = [servo_banks, power, power_settings, power_state, pwm_settings, snapshot, bus_status, bus_health])]
+    fn servo_output(cx: servo_output::Context, mut targets: [u16; SERVOS], started: Stopwatch) {
+        let servo_banks = cx.resources.servo_banks;
+        let mut power = cx.resources.power;
+        let power_settings = cx.resources.power_settings;
+        let now = clock::millis();
+        servo_banks.configure(cx.resources.pwm_settings);
+        power.lock(|power| power.update(power_settings, &mut targets, now));
+        overload::hold_off_legs(&mut targets);
+        let status = servo_banks.update(&targets, servo_bus::held_off(), bus_recovery::recover_i2c2);
+        if status.iter().all(|s| *s == BankStatus::Ok) {
+            power.lock(|power| power.written(power_settings, now));
+        }
+        *cx.resources.power_state = power.lock(|power| power.state());
+        for (pending, status) in cx.resources.bus_status.iter_mut().zip(status.iter()) {
+            if let BankStatus::Unreachable | BankStatus::Recovered = status {
+                *pending = *status;
+            }
+        }
+        *cx.resources.bus_health = servo_banks.health();
+
+        let snapshot = cx.resources.snapshot;
+        snapshot.outputs = servo_banks.outputs;
+        snapshot.i2c_errors = servo_banks.errors();
+        snapshot.loop_us = started.elapsed_us();
+        snapshot.loop_max_us = snapshot.loop_max_us.max(snapshot.loop_us);
+        watchdog::alive(watchdog::OUTPUT);
+    }
+
+    /// Sample the battery, servo currents and temperature, trips overloaded banks.
+    #[task(resources = [adc, battery, currents, thermometer, protection, protection_limits, servo_load, snapshot], schedule = [battery_monitor])]
+    fn battery_monitor(cx: battery_monitor::Context) {
+        let adc = cx.resources.adc;
+        let battery = cx.resources.battery;
+        battery.sample(adc);
+        cx.resources.snapshot.battery_mv = battery.millivolts();
+
+        let (first, second) = cx.resources.currents;
+        first.sample(adc);
+        second.sample(adc);
+        let thermometer = cx.resources.thermometer;
+        thermometer.sample(adc);
+        let load = ServoLoad {
+            milliamps: [first.milliamps(), second.milliamps()],
+            celsius: thermometer.celsius(),
+        };
+        cx.resources
+            .protection
+            .check(cx.resources.protection_limits, &load, BATTERY_PERIOD_MS);
+        *cx.resources.servo_load = load;
+
+        cx.schedule
+            .battery_monitor(cx.scheduled + millis(BATTERY_PERIOD_MS))
+            .unwrap();
+    }
+
+    /// Estimate the attitude and level the body on it.
+    #[task(resources = [attitude_estimator, attitude, leveller, level_input, level_correction], schedule = [imu_update])]
+    fn imu_update(cx: imu_update::Context) {
+        let dt = IMU_PERIOD_MS as f32 / 1000.0;
+        let measured = cx.resources.attitude_estimator.update(dt);
+        *cx.resources.attitude = measured;
+        let (settings, commanded) = cx.resources.level_input;
+        *cx.resources.level_correction =
+            cx.resources.leveller.update(settings, commanded, measured, dt);
+
+        cx.schedule
+            .imu_update(cx.scheduled + millis(IMU_PERIOD_MS))
+            .unwrap();
+    }
+
+    /// Feed the watchdog as long as every supervised task keeps running.
+    #[task(resources = [iwdg], schedule = [feed_watchdog])]
+    fn feed_watchdog(cx: feed_watchdog::Context) {
+        let stalled = watchdog::take_stalled();
+        if stalled == 0 {
+            cx.resources.iwdg.feed();
+        } else {
+            log::error("Task stalled", stalled);
+        }
+
+        cx.schedule
+            .feed_watchdog(cx.scheduled + millis(watchdog::CHECK_MS))
+            .unwrap();
+    }
+
+    // Dispatchers of the software tasks
+    extern "C" {
+        fn SPI1();
+        fn SPI2();
+    }
+};
+
+#[exception]
+fn HardFault(ef: &ExceptionFrame) -> ! {
+    crash::hard_fault(ef)
+}
@@ -0,0 +1,75 @@
+use core::ptr;
+use stm32f4xx_hal::stm32::{PWR, RCC};
+
+use ash_carrier_core::settings::{Settings, WORDS};
+
+/// Start of the 4 KiB backup SRAM.
+const BKPSRAM: *mut u32 = 0x4002_4000 as *mut u32;
+/// Word after the settings, holds [ARMED] for a moment after a reset by the
+/// reset button.
+const RESTORE_WORD: usize = WORDS;
+const ARMED: u32 = 0x5245_5354;
+
+/// Load stored settings, defaults if none are stored, they are corrupt or
+/// the baud rate is above `baud_max`.
+pub fn load(baud_max: u32) -> Settings {
+    enable_backup_sram();
+    let mut words = [0u32; WORDS];
+    for (i, w) in words.iter_mut().enumerate() {
+        *w = unsafe { ptr::read_volatile(BKPSRAM.add(i)) };
+    }
+    Settings::from_words(&words, baud_max).unwrap_or_else(Settings::new)
+}
+
+/// Reset by the reset button alone. Reads the reset flags, call before they
+/// are cleared.
+pub fn reset_by_button() -> bool {
+    let csr = unsafe { &*RCC::ptr() }.csr.read();
+    csr.padrstf().bit_is_set()
+        && csr.porrstf().bit_is_clear()
+        && csr.borrstf().bit_is_clear()
+        && csr.sftrstf().bit_is_clear()
+        && csr.wdgrstf().bit_is_clear()
+        && csr.wwdgrstf().bit_is_clear()
+        && csr.lpwrrstf().bit_is_clear()
+}
+
+/// True if the reset button was pressed a second time while `wait` ran after
+/// the previous press.
+///
+/// Holding the button keeps the MCU in reset, so a long press can not be
+/// timed. A second press is the closest we get.
+pub fn restore_requested<W: FnOnce()>(button: bool, wait: W) -> bool {
+    enable_backup_sram();
+    let word = unsafe { BKPSRAM.add(RESTORE_WORD) };
+    if !button {
+        unsafe { ptr::write_volatile(word, 0) };
+        return false;
+    }
+    if unsafe { ptr::read_volatile(word) } == ARMED {
+        unsafe { ptr::write_volatile(word, 0) };
+        return true;
+    }
+    unsafe { ptr::write_volatile(word, ARMED) };
+    wait();
+    unsafe { ptr::write_volatile(word, 0) };
+    false
+}
+
+pub fn store(settings: &Settings) {
+    enable_backup_sram();
+    for (i, w) in settings.to_words().iter().enumerate() {
+        unsafe { ptr::write_volatile(BKPSRAM.add(i), *w) };
+    }
+}
+
+/// Enable the backup SRAM clock, write access and backup regulator.
+fn enable_backup_sram() {
+    let rcc = unsafe { &*RCC::ptr() };
+    let pwr = unsafe { &*PWR::ptr() };
+    rcc.apb1enr.modify(|_, w| w.pwren().enabled());
+    pwr.cr.modify(|_, w| w.dbp().set_bit());
+    rcc.ahb1enr.modify(|_, w| w.bkpsramen().enabled());
+    pwr.csr.modify(|_, w| w.bre().set_bit());
+    while pwr.csr.read().brr().bit_is_clear() {}
+}
@@ -0,0 +1,270 @@
+use arraydeque::ArrayDeque;
+use core::ptr;
+use core::sync::atomic::{AtomicU32, Ordering};
+use stm32f4xx_hal::stm32::{DMA1, USART2};
+
+use ash_carrier_core::log;
+use ash_carrier_core::settings::{Parity, SerialSettings, StopBits};
+
+pub use ash_carrier_core::serial::{
+    error, take_errors, ERR_FRAMING, ERR_NOISE, ERR_OTHER, ERR_OVERFLOW, ERR_OVERRUN, ERR_PARITY,
+    STATS,
+};
+
+//***********************************************************************************
+// # Line settings
+
+/// APB1 clock feeding USART2.
+static PCLK: AtomicU32 = AtomicU32::new(0);
+
+/// Highest baud rate reachable with 16x oversampling.
+pub fn baud_max() -> u32 {
+    baud_max_at(PCLK.load(Ordering::Relaxed))
+}
+
+/// Highest baud rate with a peripheral clock of `pclk` Hz.
+pub fn baud_max_at(pclk: u32) -> u32 {
+    pclk / 16
+}
+
+/// Set baud rate, parity and stop bits.
+///
+/// Bytes being transmitted are garbled, wait until [DmaTx::is_idle].
+pub fn configure(pclk1: u32, settings: &SerialSettings) {
+    PCLK.store(pclk1, Ordering::Relaxed);
+    let usart = unsafe { &*USART2::ptr() };
+    let brr = (pclk1 + settings.baud / 2) / settings.baud;
+
+    usart.cr1.modify(|_, w| w.ue().disabled());
+    usart.brr.write(|w| unsafe { w.bits(brr) });
+    // Parity takes the place of the most significant bit, add a ninth one
+    usart.cr1.modify(|_, w| match settings.parity {
+        Parity::None => w.m().m8().pce().disabled(),
+        Parity::Even => w.m().m9().pce().enabled().ps().even(),
+        Parity::Odd => w.m().m9().pce().enabled().ps().odd(),
+    });
+    usart.cr2.modify(|_, w| match settings.stop_bits {
+        StopBits::One => w.stop().stop1(),
+        StopBits::Two => w.stop().stop2(),
+    });
+    usart.cr1.modify(|_, w| w.ue().enabled());
+}
+
+//***********************************************************************************
+// # DMA
+//
+// USART2 RX runs on DMA1 stream 5 and TX on stream 6, both on channel 4.
+
+const RX_LEN: usize = 256;
+const TX_LEN: usize = 256;
+const DMA_CHANNEL: u8 = 4;
+
+static mut RX_BUF: [u8; RX_LEN] = [0; RX_LEN];
+static mut TX_BUF: [u8; TX_LEN] = [0; TX_LEN];
+
+/// Number of times the receive buffer has wrapped.
+static RX_LAPS: AtomicU32 = AtomicU32::new(0);
+
+/// Start circular receive DMA, enable idle line and error interrupts.
+///
+/// The DMA1 clock must be enabled and USART2 configured beforehand.
+pub fn init_dma() {
+    let dma = unsafe { &*DMA1::ptr() };
+    let usart = unsafe { &*USART2::ptr() };
+    let dr = &usart.dr as *const _ as u32;
+
+    let rx = &dma.st[5];
+    rx.cr.modify(|_, w| w.en().disabled());
+    while rx.cr.read().en().is_enabled() {}
+    dma.hifcr.write(|w| {
+        w.ctcif5()
+            .set_bit()
+            .chtif5()
+            .set_bit()
+            .cteif5()
+            .set_bit()
+            .cdmeif5()
+            .set_bit()
+            .cfeif5()
+            .set_bit()
+    });
+    rx.par.write(|w| unsafe { w.bits(dr) });
+    rx.m0ar.write(|w| unsafe { w.bits(ptr::addr_of!(RX_BUF) as u32) });
+    rx.ndtr.write(|w| unsafe { w.bits(RX_LEN as u32) });
+    rx.cr.write(|w| {
+        w.chsel()
+            .bits(DMA_CHANNEL)
+            .dir()
+            .peripheral_to_memory()
+            .minc()
+            .incremented()
+            .circ()
+            .enabled()
+            .tcie()
+            .enabled()
+    });
+    rx.cr.modify(|_, w| w.en().enabled());
+
+    let tx = &dma.st[6];
+    tx.cr.modify(|_, w| w.en().disabled());
+    tx.par.write(|w| unsafe { w.bits(dr) });
+    tx.cr.write(|w| {
+        w.chsel()
+            .bits(DMA_CHANNEL)
+            .dir()
+            .memory_to_peripheral()
+            .minc()
+            .incremented()
+    });
+
+    usart
+        .cr3
+        .modify(|_, w| w.dmar().enabled().dmat().enabled().eie().enabled());
+    usart
+        .cr1
+        .modify(|_, w| w.idleie().enabled().peie().enabled());
+}
+
+/// USART2 interrupt, idle line and line errors.
+pub fn on_usart_interrupt() {
+    let usart = unsafe { &*USART2::ptr() };
+    let sr = usart.sr.read();
+    if sr.ore().bit_is_set() {
+        error(ERR_OVERRUN);
+    }
+    if sr.fe().bit_is_set() {
+        error(ERR_FRAMING);
+    }
+    if sr.nf().bit_is_set() {
+        error(ERR_NOISE);
+    }
+    if sr.pe().bit_is_set() {
+        error(ERR_PARITY);
+    }
+    // Reading DR after SR clears the idle and error flags
+    let _ = usart.dr.read();
+}
+
+/// DMA1 stream 5 interrupt, the receive buffer wrapped.
+pub fn on_rx_dma_interrupt() {
+    let dma = unsafe { &*DMA1::ptr() };
+    if dma.hisr.read().tcif5().bit_is_set() {
+        dma.hifcr.write(|w| w.ctcif5().set_bit());
+        RX_LAPS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Reader for the circular receive buffer.
+pub struct DmaRx {
+    /// Total number of bytes consumed, wraps with the lap counter.
+    read: u32,
+}
+
+impl DmaRx {
+    pub fn new() -> Self {
+        DmaRx { read: 0 }
+    }
+
+    fn written(&self) -> u32 {
+        let dma = unsafe { &*DMA1::ptr() };
+        let laps = RX_LAPS.load(Ordering::SeqCst);
+        let pos = RX_LEN as u32 - dma.st[5].ndtr.read().bits();
+        laps.wrapping_mul(RX_LEN as u32).wrapping_add(pos)
+    }
+
+    /// Pass every received byte to `f`, returns the number of bytes read.
+    ///
+    /// If the buffer wrapped over unread data, the lost bytes are skipped and
+    /// reported as an overflow.
+    pub fn read<F>(&mut self, mut f: F) -> usize
+    where
+        F: FnMut(u8),
+    {
+        let written = self.written();
+        let available = written.wrapping_sub(self.read);
+        // The stream wrapped but the lap counter is not updated yet
+        if available > u32::MAX / 2 {
+            return 0;
+        }
+        if available > RX_LEN as u32 {
+            error(ERR_OVERFLOW);
+            log::error("UART RX buffer overflow", available - RX_LEN as u32);
+            self.read = written.wrapping_sub(RX_LEN as u32);
+        }
+        let mut n = 0;
+        while self.read != written {
+            f(unsafe { RX_BUF[self.read as usize % RX_LEN] });
+            self.read = self.read.wrapping_add(1);
+            n += 1;
+        }
+        STATS.received.fetch_add(n as u32, Ordering::Relaxed);
+        n
+    }
+}
+
+/// Queued transmitter, the queue is fed to DMA one buffer at a time.
+pub struct DmaTx {
+    pending: ArrayDeque<[u8; 1024]>,
+}
+
+impl DmaTx {
+    pub fn new() -> Self {
+        DmaTx {
+            pending: ArrayDeque::new(),
+        }
+    }
+
+    /// Queue data for transmission, returns false if it did not fit.
+    pub fn write(&mut self, data: &[u8]) -> bool {
+        if self.pending.capacity() - self.pending.len() < data.len() {
+            return false;
+        }
+        self.pending.extend_back(data.iter().copied());
+        self.poll();
+        true
+    }
+
+    fn busy(&self) -> bool {
+        let dma = unsafe { &*DMA1::ptr() };
+        dma.st[6].cr.read().en().is_enabled()
+    }
+
+    /// True once everything has been handed to the UART.
+    pub fn is_idle(&self) -> bool {
+        let usart = unsafe { &*USART2::ptr() };
+        self.pending.is_empty() && !self.busy() && usart.sr.read().tc().bit_is_set()
+    }
+
+    /// Start the next transfer if the previous one is done.
+    pub fn poll(&mut self) {
+        if self.pending.is_empty() || self.busy() {
+            return;
+        }
+        let mut n = 0;
+        while let Some(c) = self.pending.pop_front() {
+            unsafe { TX_BUF[n] = c };
+            n += 1;
+            if n == TX_LEN {
+                break;
+            }
+        }
+
+        let dma = unsafe { &*DMA1::ptr() };
+        let tx = &dma.st[6];
+        dma.hifcr.write(|w| {
+            w.ctcif6()
+                .set_bit()
+                .chtif6()
+                .set_bit()
+                .cteif6()
+                .set_bit()
+                .cdmeif6()
+                .set_bit()
+                .cfeif6()
+                .set_bit()
+        });
+        tx.m0ar.write(|w| unsafe { w.bits(ptr::addr_of!(TX_BUF) as u32) });
+        tx.ndtr.write(|w| unsafe { w.bits(n as u32) });
+        tx.cr.modify(|_, w| w.en().enabled());
+    }
+}
@@ -0,0 +1,71 @@
+use arraydeque::ArrayDeque;
+use stm32f4xx_hal::otg_fs::{UsbBus, USB};
+use usb_device::bus::UsbBusAllocator;
+use usb_device::prelude::*;
+use usbd_serial::{SerialPort, USB_CLASS_CDC};
+
+type Bus = UsbBus<USB>;
+
+/// CDC-ACM virtual serial port.
+pub struct UsbSerial<'a> {
+    device: UsbDevice<'a, Bus>,
+    serial: SerialPort<'a, Bus>,
+    pending: ArrayDeque<[u8; 1024]>,
+}
+
+impl<'a> UsbSerial<'a> {
+    pub fn new(bus: &'a UsbBusAllocator<Bus>) -> Self {
+        let serial = SerialPort::new(bus);
+        let device = UsbDeviceBuilder::new(bus, UsbVidPid(0x16c0, 0x27dd))
+            .manufacturer("GPA-Robotics")
+            .product("ash-carrier")
+            .serial_number("0")
+            .device_class(USB_CLASS_CDC)
+            .build();
+        UsbSerial {
+            device,
+            serial,
+            pending: ArrayDeque::new(),
+        }
+    }
+
+    /// Service the bus and read received data into `buf`.
+    ///
+    /// Must be called often, the host gives up on enumeration otherwise.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        self.flush();
+        if !self.device.poll(&mut [&mut self.serial]) {
+            return 0;
+        }
+        self.serial.read(buf).unwrap_or(0)
+    }
+
+    /// Queue data for transmission, returns false if it did not fit.
+    ///
+    /// Data is dropped while no terminal has the port open.
+    pub fn write(&mut self, data: &[u8]) -> bool {
+        if !self.serial.dtr() {
+            return true;
+        }
+        if self.pending.capacity() - self.pending.len() < data.len() {
+            return false;
+        }
+        self.pending.extend_back(data.iter().copied());
+        self.flush();
+        true
+    }
+
+    fn flush(&mut self) {
+        while !self.pending.is_empty() {
+            let (front, _) = self.pending.as_slices();
+            match self.serial.write(front) {
+                Ok(n) if n > 0 => {
+                    for _ in 0..n {
+                        self.pending.pop_front();
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+use stm32f4xx_hal::stm32::RCC;
+
+/// Tasks which must keep running for the watchdog to be fed.
+pub const CONTROL: u32 = 1 << 0;
+pub const OUTPUT: u32 = 1 << 1;
+pub const SCPI: u32 = 1 << 2;
+const ALL: u32 = CONTROL | OUTPUT | SCPI;
+
+/// Independent watchdog timeout.
+pub const TIMEOUT_MS: u32 = 1000;
+/// How often the watchdog task checks in, several times per timeout.
+pub const CHECK_MS: u32 = 250;
+
+static ALIVE: AtomicU32 = AtomicU32::new(0);
+
+/// Called by `task` every time it runs.
+pub fn alive(task: u32) {
+    ALIVE.fetch_or(task, Ordering::Relaxed);
+}
+
+/// Tasks that have not run since the previous call, 0 if all of them did.
+pub fn take_stalled() -> u32 {
+    !ALIVE.swap(0, Ordering::Relaxed) & ALL
+}
+
+/// True if the last reset was caused by the independent watchdog, the reset
+/// flags are cleared.
+pub fn caused_reset() -> bool {
+    let rcc = unsafe { &*RCC::ptr() };
+    let caused = rcc.csr.read().wdgrstf().bit_is_set();
+    rcc.csr.modify(|_, w| w.rmvf().set_bit());
+    caused
+}
@@ -0,0 +1,148 @@
+#!/usr/bin/env python3
+"""Minimal stand-in for a rosserial agent, for testing the carrier's ROS
+bridge without a ROS installation.
+
+Switches the port to rosserial, requests the topics, answers time requests,
+prints received joint_states and battery_state and optionally publishes a
+cmd_vel and joint_command.
+
+    ./rosserial_agent.py /dev/ttyACM0 115200 --cmd-vel 0.1 0 0.2
+    ./rosserial_agent.py /dev/pts/5 9600 --joints 0.0 0.1 -0.1
+"""
+import argparse
+import struct
+import sys
+import time
+
+import serial
+
+ID_PUBLISHER = 0
+ID_SUBSCRIBER = 1
+ID_TIME = 10
+ID_TX_STOP = 11
+
+
+def checksum(data):
+    return 255 - sum(data) % 256
+
+
+def frame(topic, data=b""):
+    length = struct.pack("<H", len(data))
+    body = struct.pack("<H", topic) + data
+    return b"\xff\xfe" + length + bytes([checksum(length)]) + body + bytes([checksum(body)])
+
+
+def read_frames(port):
+    """Yield (topic, data) of every valid message."""
+    buf = b""
+    while True:
+        buf += port.read(port.in_waiting or 1)
+        while True:
+            start = buf.find(b"\xff\xfe")
+            if start < 0 or len(buf) < start + 7:
+                break
+            buf = buf[start:]
+            length = struct.unpack_from("<H", buf, 2)[0]
+            if buf[4] != checksum(buf[2:4]):
+                buf = buf[2:]
+                continue
+            if len(buf) < 8 + length:
+                break
+            body, sum_ = buf[5:7 + length], buf[7 + length]
+            buf = buf[8 + length:]
+            if sum_ == checksum(body):
+                yield struct.unpack_from("<H", body)[0], body[2:]
+
+
+class Reader:
+    def __init__(self, data):
+        self.data, self.pos = data, 0
+
+    def take(self, fmt):
+        values = struct.unpack_from("<" + fmt, self.data, self.pos)
+        self.pos += struct.calcsize("<" + fmt)
+        return values if len(values) > 1 else values[0]
+
+    def array(self, fmt):
+        n = self.take("I")
+        values = struct.unpack_from("<%d%s" % (n, fmt), self.data, self.pos)
+        self.pos += struct.calcsize("<%d%s" % (n, fmt))
+        return values
+
+    def string(self):
+        n = self.take("I")
+        s = self.data[self.pos:self.pos + n].decode()
+        self.pos += n
+        return s
+
+    def header(self):
+        seq, sec, nsec = self.take("III")
+        self.string()
+        return sec + nsec * 1e-9
+
+
+def string(s):
+    return struct.pack("<I", len(s)) + s.encode()
+
+
+def joint_command(positions):
+    data = struct.pack("<III", 0, 0, 0) + string("")
+    data += struct.pack("<I", 0)
+    data += struct.pack("<I%dd" % len(positions), len(positions), *positions)
+    return data + struct.pack("<II", 0, 0)
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("port")
+    parser.add_argument("baudrate", type=int)
+    parser.add_argument("--cmd-vel", nargs=3, type=float, metavar=("X", "Y", "YAW"))
+    parser.add_argument("--joints", nargs="+", type=float, metavar="RAD")
+    parser.add_argument("--duration", type=float, default=10.0)
+    args = parser.parse_args()
+
+    port = serial.Serial(args.port, args.baudrate, timeout=0.1)
+    port.write(b"\nSYST:COMM:PROT ROS\n")
+    time.sleep(0.1)
+    port.reset_input_buffer()
+    port.write(frame(ID_PUBLISHER))
+
+    topics = {}
+    end = time.time() + args.duration
+    try:
+        for topic, data in read_frames(port):
+            if time.time() > end:
+                break
+            if topic in (ID_PUBLISHER, ID_SUBSCRIBER):
+                r = Reader(data)
+                tid, name, kind = r.take("H"), r.string(), r.string()
+                role = "publishes" if topic == ID_PUBLISHER else "subscribes"
+                print("%s %s %s [%d]" % (role, name, kind, tid))
+                if topic == ID_PUBLISHER:
+                    topics[tid] = name
+                elif name == "cmd_vel" and args.cmd_vel:
+                    x, y, yaw = args.cmd_vel
+                    port.write(frame(tid, struct.pack("<6d", x, y, 0, 0, 0, yaw)))
+                elif name == "joint_command" and args.joints:
+                    port.write(frame(tid, joint_command(args.joints)))
+            elif topic == ID_TIME:
+                now = time.time()
+                port.write(frame(ID_TIME, struct.pack("<II", int(now), int(now % 1 * 1e9))))
+            elif topics.get(topic) == "joint_states":
+                r = Reader(data)
+                stamp = r.header()
+                names = [r.string() for _ in range(r.take("I"))]
+                positions = r.array("d")
+                print("%.3f joint_states %s" % (stamp, " ".join("%.3f" % p for p in positions)))
+            elif topics.get(topic) == "battery_state":
+                r = Reader(data)
+                stamp = r.header()
+                print("%.3f battery_state %.2f V" % (stamp, r.take("f")))
+    except KeyboardInterrupt:
+        pass
+    port.write(frame(ID_TX_STOP))
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -0,0 +1,262 @@
+//! Simulator running the carrier command tree on the host.
+//!
+//! [run] does the work of the firmware tasks in a single loop, with the servo
+//! controllers, the emergency stop input, the foot contact switches, the
+//! servo power switch and the battery, current and temperature ADCs replaced
+//! by the mocks in [mock]. The servo controllers are driven through the real
+//! PCA9685 driver and record their outputs, so tests can assert on what the
+//! servos would have done.
+
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use ash_carrier_core::attitude::Attitude;
+use ash_carrier_core::battery::Battery;
+use ash_carrier_core::contact::{self, Switch};
+use ash_carrier_core::crash::{CrashLog, CrashRecord};
+use ash_carrier_core::level::Leveller;
+use ash_carrier_core::overload::{self, CurrentSense, Protection, ServoLoad, Thermometer};
+use ash_carrier_core::port::ScpiPort;
+use ash_carrier_core::power::PowerSequencer;
+use ash_carrier_core::servo_bus::{self, BankStatus, ServoBank, ServoBanks};
+use ash_carrier_core::settings::SerialSettings;
+use ash_carrier_core::telemetry::Snapshot;
+use ash_carrier_core::tree::{CarrierDevice, Commands, State, BANKS, LEGS};
+use ash_carrier_core::{carrier_tree, estop};
+use embedded_hal::digital::v2::InputPin;
+use pwm_pca9685::{Pca9685, SlaveAddr};
+use scpi::ieee488::commands::*;
+use scpi::prelude::*;
+use scpi::scpi::commands::*;
+
+pub mod mock;
+
+use mock::{Adc, AdcPin, I2cBus, Pin};
+
+/// Addresses of the even and odd servo controllers.
+pub const BANK_ADDRESSES: [u8; 2] = [0x46, 0x47];
+/// Reported by `SYSTem:COMMunicate:SERial:BAUD` as the highest rate.
+pub const BAUD_MAX: u32 = 3_000_000;
+/// Longest wait for input before running the rest of the loop.
+const TICK: Duration = Duration::from_millis(1);
+
+const VERSION: &[u8] = concat!("sim-", env!("CARGO_PKG_VERSION")).as_bytes();
+
+/// Mock hardware of a carrier.
+#[derive(Clone)]
+pub struct Hardware {
+    /// I2C2 with the two servo controllers.
+    pub i2c: I2cBus,
+    /// Emergency stop input, active low.
+    pub estop: Pin,
+    /// Foot contact switches, low while the foot touches the ground.
+    pub contacts: [Pin; LEGS],
+    /// Servo power switch, high while the rail is on.
+    pub servo_power: Pin,
+    /// Battery voltage divider input.
+    pub battery: Adc,
+    /// Servo rail current sense amplifier of each bank.
+    pub currents: [Adc; BANKS],
+    /// Internal temperature sensor.
+    pub temperature: Adc,
+}
+
+impl Hardware {
+    pub fn new() -> Self {
+        let hardware = Hardware {
+            i2c: I2cBus::new(&BANK_ADDRESSES),
+            estop: Pin::new(),
+            contacts: Default::default(),
+            servo_power: Pin::new(),
+            battery: Adc::default(),
+            currents: Default::default(),
+            temperature: Adc::default(),
+        };
+        hardware.set_battery_mv(7400);
+        hardware.set_celsius(25.0);
+        hardware
+    }
+
+    /// Set the ADC input for a pack voltage of `mv`.
+    pub fn set_battery_mv(&self, mv: u32) {
+        self.battery.set((mv * 4095 / (3300 * 11)) as u16);
+    }
+
+    /// Set the current sense input of `bank` for a rail current of `ma`.
+    pub fn set_current_ma(&self, bank: usize, ma: u32) {
+        self.currents[bank].set((ma / 10 * 4095 / 3300) as u16);
+    }
+
+    /// Set the temperature sensor input for `celsius`.
+    pub fn set_celsius(&self, celsius: f32) {
+        let mv = 760.0 + (celsius - 25.0) * 2.5;
+        self.temperature.set((mv * 4095.0 / 3300.0).round() as u16);
+    }
+}
+
+impl Default for Hardware {
+    fn default() -> Self {
+        Hardware::new()
+    }
+}
+
+/// Nothing survives a restart of the simulator.
+struct NoCrashLog;
+
+impl CrashLog for NoCrashLog {
+    fn last(&self) -> Option<CrashRecord> {
+        None
+    }
+
+    fn clear(&self) {}
+}
+
+/// Run the carrier on `hardware` until `input` is disconnected.
+///
+/// Bytes received on `input` are handled like bytes received on the USB
+/// interface, responses and telemetry are passed to `write`.
+pub fn run<W>(hardware: &Hardware, input: Receiver<u8>, mut write: W)
+where
+    W: FnMut(&[u8]),
+{
+    let start = Instant::now();
+    let state = State::new(SerialSettings::new());
+    let commands = Commands::new(&state, &NoCrashLog, BAUD_MAX);
+    let framed_handler = state.framed_handler();
+    let ros_bridge = state.ros_bridge();
+
+    let tree = carrier_tree!(commands, VERSION);
+    let mut device = CarrierDevice;
+    let mut errors = ArrayErrorQueue::<[Error; 10]>::new();
+    let mut context = Context::new(&mut device, &mut errors, tree);
+    let mut port = ScpiPort::<256>::new();
+
+    let mut servo_banks = ServoBanks::new(
+        ServoBank::new(Pca9685::new(
+            hardware.i2c.clone(),
+            SlaveAddr::Alternative(false, false, false, true, true, false),
+        )),
+        ServoBank::new(Pca9685::new(
+            hardware.i2c.clone(),
+            SlaveAddr::Alternative(false, false, false, true, true, true),
+        )),
+    );
+    let mut adc = hardware.battery.clone();
+    let mut battery = Battery::new(AdcPin);
+    let mut current_adcs = hardware.currents.clone();
+    let mut currents = [CurrentSense::new(AdcPin), CurrentSense::new(AdcPin)];
+    let mut temperature_adc = hardware.temperature.clone();
+    let mut thermometer = Thermometer::new(AdcPin);
+    let mut protection = Protection::new();
+    let mut last_check = Instant::now();
+    let mut leveller = Leveller::new();
+    let mut power = PowerSequencer::new(hardware.servo_power.clone());
+    let mut contact_switches = hardware.contacts.clone().map(Switch::new);
+    let mut last_level = Instant::now();
+
+    let mut loop_us = 0;
+    let mut loop_max_us = 0;
+    loop {
+        let mut received = Vec::new();
+        let open = match input.recv_timeout(TICK) {
+            Ok(c) => {
+                received.push(c);
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => true,
+            Err(RecvTimeoutError::Disconnected) => false,
+        };
+        received.extend(input.try_iter());
+
+        // Time spent waiting for input does not count
+        let loop_start = Instant::now();
+        let now = start.elapsed().as_millis() as u32;
+        estop::set_input(hardware.estop.is_low().unwrap_or(true));
+        let fitted = *state.contacts_fitted.borrow();
+        state.contacts.replace(contact::read(&mut contact_switches, fitted));
+
+        for c in received {
+            port.push(
+                c,
+                &mut context,
+                &state.session,
+                &framed_handler,
+                &ros_bridge,
+                now,
+                &mut write,
+            );
+        }
+        if estop::take_trip() {
+            state.stop();
+        }
+
+        let targets = state.targets();
+        let settings = *state.power.borrow();
+        let mut released = targets;
+        power.update(&settings, &mut released, now);
+        overload::hold_off_legs(&mut released);
+        servo_banks.configure(&state.pwm.borrow());
+        // Nothing to recover on a mock bus
+        let status = servo_banks.update(&released, servo_bus::held_off(), || {});
+        if status.iter().all(|s| *s == BankStatus::Ok) {
+            power.written(&settings, now);
+        }
+        state.power_state.replace(power.state());
+        servo_bus::report(&status, &mut context);
+        overload::report(&mut context);
+        state.bus_health.replace(servo_banks.health());
+
+        // No IMU, the correction stays at zero
+        let (roll, pitch, _) = state.rotation.borrow().euler_angles();
+        let correction = leveller.update(
+            &state.level.borrow(),
+            &Attitude { roll, pitch },
+            *state.attitude.borrow(),
+            last_level.elapsed().as_secs_f32(),
+        );
+        last_level = Instant::now();
+        state.level_correction.replace(correction);
+
+        battery.sample(&mut adc);
+        let mut load = ServoLoad::default();
+        for ((sense, adc), milliamps) in currents
+            .iter_mut()
+            .zip(current_adcs.iter_mut())
+            .zip(load.milliamps.iter_mut())
+        {
+            sense.sample(adc);
+            *milliamps = sense.milliamps();
+        }
+        thermometer.sample(&mut temperature_adc);
+        load.celsius = thermometer.celsius();
+        protection.check(
+            &state.protection.borrow(),
+            &load,
+            last_check.elapsed().as_millis() as u32,
+        );
+        last_check = Instant::now();
+        state.servo_load.replace(load);
+        let snapshot = Snapshot {
+            time_ms: start.elapsed().as_millis() as u32,
+            targets,
+            outputs: servo_banks.outputs,
+            battery_mv: battery.millivolts(),
+            loop_us,
+            loop_max_us,
+            i2c_errors: servo_banks.errors(),
+            serial_errors: 0,
+            estop: estop::is_latched(),
+            gait_phase: None,
+        };
+        port.telemetry(&snapshot, |record: &[u8]| {
+            write(record);
+            true
+        });
+
+        if !open {
+            return;
+        }
+        loop_us = loop_start.elapsed().as_micros() as u32;
+        loop_max_us = loop_max_us.max(loop_us);
+    }
+}
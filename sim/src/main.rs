@@ -0,0 +1,108 @@
+//! Carrier simulator.
+//!
+//!     ash-carrier-sim [--pty] [--record <file>]
+//!
+//! Speaks SCPI on stdin/stdout, or on a pseudo-terminal whose path is
+//! printed to stderr with `--pty`. With `--record` the outputs of the servo
+//! controllers are written to `<file>` as CSV when the input ends.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+
+use ash_carrier_sim::{run, Hardware, BANK_ADDRESSES};
+use nix::pty::openpty;
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg};
+
+struct Options {
+    pty: bool,
+    record: Option<String>,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: ash-carrier-sim [--pty] [--record <file>]");
+    process::exit(2)
+}
+
+fn parse_options() -> Options {
+    let mut options = Options {
+        pty: false,
+        record: None,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pty" => options.pty = true,
+            "--record" => options.record = Some(args.next().unwrap_or_else(|| usage())),
+            _ => usage(),
+        }
+    }
+    options
+}
+
+/// Pass every byte read from `reader` to the simulator.
+fn forward<R: Read + Send + 'static>(mut reader: R) -> mpsc::Receiver<u8> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0u8; 256];
+        while let Ok(n) = reader.read(&mut buf) {
+            if n == 0 || buf[..n].iter().any(|b| tx.send(*b).is_err()) {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+fn write_recording(hardware: &Hardware, path: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "time_ms,address")?;
+    for channel in 0..16 {
+        write!(out, ",ch{}", channel)?;
+    }
+    writeln!(out)?;
+    for address in BANK_ADDRESSES.iter() {
+        for sample in hardware.i2c.recording(*address) {
+            write!(out, "{},0x{:02X}", sample.time_ms, address)?;
+            for output in sample.outputs.iter() {
+                write!(out, ",{}", output)?;
+            }
+            writeln!(out)?;
+        }
+    }
+    out.flush()
+}
+
+fn main() -> io::Result<()> {
+    let options = parse_options();
+    let hardware = Hardware::new();
+
+    if options.pty {
+        let pty = openpty(None, None)?;
+        let mut termios = tcgetattr(&pty.slave)?;
+        cfmakeraw(&mut termios);
+        tcsetattr(&pty.slave, SetArg::TCSANOW, &termios)?;
+        eprintln!("{}", nix::unistd::ttyname(&pty.slave)?.display());
+
+        // The slave stays open so the master never sees a hang up
+        let _slave = pty.slave;
+        let master = File::from(pty.master);
+        let mut output = master.try_clone()?;
+        run(&hardware, forward(master), |bytes| {
+            let _ = output.write_all(bytes);
+        });
+    } else {
+        let stdout = io::stdout();
+        run(&hardware, forward(io::stdin()), |bytes| {
+            let mut out = stdout.lock();
+            let _ = out.write_all(bytes).and_then(|_| out.flush());
+        });
+    }
+
+    if let Some(path) = options.record {
+        write_recording(&hardware, &path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,273 @@
+//! Mock hardware. Every mock is a cheap handle to shared state, so the
+//! simulator and whoever drives it can hold one each.
+
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use embedded_hal::adc::{Channel, OneShot};
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+
+const MODE1: usize = 0x00;
+const MODE1_SLEEP: u8 = 0x10;
+const MODE1_AUTO_INC: u8 = 0x20;
+const LED0_ON_L: usize = 0x06;
+const ALL_LED_ON_L: usize = 0xFA;
+const ALL_LED_OFF_H: usize = 0xFD;
+const PRE_SCALE: usize = 0xFE;
+/// Full on or full off bit in the high byte of a counter.
+const FULL: u8 = 0x10;
+
+/// Channel outputs of a controller from some point in time on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// Since the bus was created.
+    pub time_ms: u32,
+    /// High time of every channel in counts of 1/4096 period, 0 is off and
+    /// 4096 fully on.
+    pub outputs: [u16; 16],
+}
+
+/// Register model of a PCA9685.
+struct Pca9685Model {
+    address: u8,
+    registers: [u8; 256],
+    connected: bool,
+    recording: Vec<Sample>,
+}
+
+impl Pca9685Model {
+    fn new(address: u8) -> Self {
+        let mut registers = [0u8; 256];
+        // Power on state: sleeping with all channels fully off
+        registers[MODE1] = MODE1_SLEEP | 0x01;
+        registers[PRE_SCALE] = 0x1E;
+        for channel in 0..16 {
+            registers[LED0_ON_L + 4 * channel + 3] = FULL;
+        }
+        Pca9685Model {
+            address,
+            registers,
+            connected: true,
+            recording: Vec::new(),
+        }
+    }
+
+    fn write(&mut self, mut register: usize, data: &[u8]) {
+        for byte in data {
+            self.registers[register] = *byte;
+            // Writes to the ALL_LED registers go to every channel
+            if (ALL_LED_ON_L..=ALL_LED_OFF_H).contains(&register) {
+                for channel in 0..16 {
+                    self.registers[LED0_ON_L + 4 * channel + register - ALL_LED_ON_L] = *byte;
+                }
+            }
+            if self.registers[MODE1] & MODE1_AUTO_INC != 0 {
+                register = (register + 1) % 256;
+            }
+        }
+    }
+
+    fn outputs(&self) -> [u16; 16] {
+        let mut outputs = [0u16; 16];
+        if self.registers[MODE1] & MODE1_SLEEP != 0 {
+            return outputs;
+        }
+        for (channel, output) in outputs.iter_mut().enumerate() {
+            let r = &self.registers[LED0_ON_L + 4 * channel..LED0_ON_L + 4 * channel + 4];
+            let on = u16::from_le_bytes([r[0], r[1] & 0x0F]);
+            let off = u16::from_le_bytes([r[2], r[3] & 0x0F]);
+            *output = if r[3] & FULL != 0 {
+                0
+            } else if r[1] & FULL != 0 {
+                4096
+            } else {
+                off.wrapping_sub(on) & 0x0FFF
+            };
+        }
+        outputs
+    }
+}
+
+/// The controller did not acknowledge its address.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Nack;
+
+struct Bus {
+    start: Instant,
+    chips: Vec<Pca9685Model>,
+}
+
+/// An I2C bus with PCA9685 controllers on it, their outputs are recorded
+/// every time they change.
+#[derive(Clone)]
+pub struct I2cBus {
+    bus: Arc<Mutex<Bus>>,
+}
+
+impl I2cBus {
+    /// A bus with a controller at each of `addresses`.
+    pub fn new(addresses: &[u8]) -> Self {
+        I2cBus {
+            bus: Arc::new(Mutex::new(Bus {
+                start: Instant::now(),
+                chips: addresses.iter().map(|a| Pca9685Model::new(*a)).collect(),
+            })),
+        }
+    }
+
+    /// Outputs of the controller at `address` every time they changed.
+    pub fn recording(&self, address: u8) -> Vec<Sample> {
+        self.with_chip(address, |chip| chip.recording.clone())
+    }
+
+    /// Current outputs of the controller at `address`.
+    pub fn outputs(&self, address: u8) -> [u16; 16] {
+        self.with_chip(address, |chip| chip.outputs())
+    }
+
+    /// Prescale register of the controller at `address`.
+    pub fn prescale(&self, address: u8) -> u8 {
+        self.with_chip(address, |chip| chip.registers[PRE_SCALE])
+    }
+
+    /// Disconnect or reconnect a controller, a disconnected one does not
+    /// acknowledge any transfer.
+    pub fn set_connected(&self, address: u8, connected: bool) {
+        self.with_chip(address, |chip| chip.connected = connected)
+    }
+
+    fn with_chip<T, F: FnOnce(&mut Pca9685Model) -> T>(&self, address: u8, f: F) -> T {
+        let mut bus = self.bus.lock().unwrap();
+        let chip = bus
+            .chips
+            .iter_mut()
+            .find(|c| c.address == address)
+            .expect("no controller at address");
+        f(chip)
+    }
+}
+
+impl Write for I2cBus {
+    type Error = Nack;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Nack> {
+        let mut bus = self.bus.lock().unwrap();
+        let time_ms = bus.start.elapsed().as_millis() as u32;
+        let chip = bus
+            .chips
+            .iter_mut()
+            .find(|c| c.address == address && c.connected)
+            .ok_or(Nack)?;
+        if let Some((register, data)) = bytes.split_first() {
+            chip.write(*register as usize, data);
+        }
+        let outputs = chip.outputs();
+        if chip.recording.last().map(|s| s.outputs) != Some(outputs) {
+            chip.recording.push(Sample { time_ms, outputs });
+        }
+        Ok(())
+    }
+}
+
+impl WriteRead for I2cBus {
+    type Error = Nack;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Nack> {
+        let mut bus = self.bus.lock().unwrap();
+        let chip = bus
+            .chips
+            .iter_mut()
+            .find(|c| c.address == address && c.connected)
+            .ok_or(Nack)?;
+        let register = bytes.first().copied().unwrap_or(0) as usize;
+        for (i, b) in buffer.iter_mut().enumerate() {
+            *b = chip.registers[(register + i) % 256];
+        }
+        Ok(())
+    }
+}
+
+/// A digital pin, high until set otherwise. Also an output, for the
+/// simulator to drive and tests to read.
+#[derive(Clone)]
+pub struct Pin {
+    high: Arc<AtomicBool>,
+}
+
+impl Pin {
+    pub fn new() -> Self {
+        Pin {
+            high: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    pub fn set_high(&self, high: bool) {
+        self.high.store(high, Ordering::SeqCst);
+    }
+}
+
+impl Default for Pin {
+    fn default() -> Self {
+        Pin::new()
+    }
+}
+
+impl OutputPin for Pin {
+    type Error = ();
+
+    fn set_low(&mut self) -> Result<(), ()> {
+        self.high.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), ()> {
+        self.high.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl InputPin for Pin {
+    type Error = ();
+
+    fn is_high(&self) -> Result<bool, ()> {
+        Ok(self.high.load(Ordering::SeqCst))
+    }
+
+    fn is_low(&self) -> Result<bool, ()> {
+        Ok(!self.high.load(Ordering::SeqCst))
+    }
+}
+
+/// A 12 bit ADC with a single input, converting to a settable value.
+#[derive(Clone, Default)]
+pub struct Adc {
+    value: Arc<AtomicU16>,
+}
+
+/// The input of [Adc].
+pub struct AdcPin;
+
+impl Channel<Adc> for AdcPin {
+    type ID = u8;
+
+    fn channel() -> u8 {
+        0
+    }
+}
+
+impl Adc {
+    /// Set the raw conversion result, 0 to 4095.
+    pub fn set(&self, value: u16) {
+        self.value.store(value.min(4095), Ordering::SeqCst);
+    }
+}
+
+impl OneShot<Adc, u16, AdcPin> for Adc {
+    type Error = ();
+
+    fn read(&mut self, _pin: &mut AdcPin) -> nb::Result<u16, ()> {
+        Ok(self.value.load(Ordering::SeqCst))
+    }
+}
@@ -0,0 +1,304 @@
+//! The simulator driven line by line, asserting on responses and on what the
+//! servo controllers were told.
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use ash_carrier_sim::{run, Hardware, BANK_ADDRESSES};
+use embedded_hal::digital::v2::InputPin;
+
+/// The emergency stop latch is global, tests must not overlap.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+struct Sim {
+    hardware: Hardware,
+    input: Option<Sender<u8>>,
+    output: Receiver<u8>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Sim {
+    fn start(hardware: Hardware) -> Self {
+        let (input, rx) = mpsc::channel();
+        let (tx, output) = mpsc::channel();
+        let hw = hardware.clone();
+        let thread = thread::spawn(move || {
+            run(&hw, rx, |bytes| {
+                for b in bytes {
+                    let _ = tx.send(*b);
+                }
+            })
+        });
+        Sim {
+            hardware,
+            input: Some(input),
+            output,
+            thread: Some(thread),
+        }
+    }
+
+    fn read_line(&self) -> String {
+        let mut line = Vec::new();
+        loop {
+            let b = self
+                .output
+                .recv_timeout(Duration::from_secs(2))
+                .expect("no response");
+            match b {
+                b'\n' => return String::from_utf8(line).unwrap(),
+                b'\r' => {}
+                b => line.push(b),
+            }
+        }
+    }
+
+    fn send(&self, line: &str) {
+        let input = self.input.as_ref().unwrap();
+        for b in line.bytes().chain(Some(b'\n')) {
+            input.send(b).unwrap();
+        }
+    }
+
+    fn query(&self, line: &str) -> String {
+        self.send(line);
+        self.read_line()
+    }
+
+    /// Execute a command, once this returns the servos have been updated
+    /// at least once after it.
+    fn command(&self, line: &str) {
+        assert_eq!(self.query(&format!("{};*OPC?", line)), "1");
+        assert_eq!(self.query("*OPC?"), "1");
+    }
+
+    fn outputs(&self, bank: usize) -> [u16; 16] {
+        self.hardware.i2c.outputs(BANK_ADDRESSES[bank])
+    }
+
+    /// Stop the simulator and return its hardware.
+    fn stop(mut self) -> Hardware {
+        self.input.take();
+        self.thread.take().unwrap().join().unwrap();
+        self.hardware.clone()
+    }
+}
+
+/// 1500 µs in counts at the default 50 Hz.
+const CENTER: u16 = 307;
+
+fn lock() -> std::sync::MutexGuard<'static, ()> {
+    SERIAL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[test]
+fn identifies_as_simulator() {
+    let _lock = lock();
+    let sim = Sim::start(Hardware::new());
+    assert!(sim.query("*IDN?").starts_with("GPA-Robotics,ash-carrier,0,sim-"));
+    assert_eq!(sim.query("SYST:ERR?"), "0,\"No error\"");
+    sim.stop();
+}
+
+#[test]
+fn pulse_widths_reach_the_controllers() {
+    let _lock = lock();
+    let sim = Sim::start(Hardware::new());
+    sim.command("*CLS");
+    sim.command("BODY:SERV:PWID:SET 1,1200;SET 4,1800");
+    // Even servos on the first controller, odd ones on the second, in
+    // counts of 4.88 µs at 50 Hz
+    assert_eq!(sim.outputs(0)[0], 246);
+    assert_eq!(sim.outputs(1)[1], 369);
+    assert_eq!(sim.outputs(1)[0], CENTER);
+
+    sim.command("BODY:SERV:PWID:SET 1,1300");
+    let hardware = sim.stop();
+    let recording = hardware.i2c.recording(BANK_ADDRESSES[0]);
+    let channel: Vec<u16> = recording.iter().map(|s| s.outputs[0]).collect();
+    assert_eq!(channel, vec![0, CENTER, 246, 266]);
+    assert!(recording.windows(2).all(|w| w[0].time_ms <= w[1].time_ms));
+}
+
+#[test]
+fn emergency_stop_turns_outputs_off() {
+    let _lock = lock();
+    let hardware = Hardware::new();
+    let sim = Sim::start(hardware.clone());
+    sim.command("BODY:SERV:PWID:SET 1,1200");
+    assert_eq!(sim.outputs(0)[0], 246);
+
+    hardware.estop.set_high(false);
+    sim.command("*CLS");
+    assert_eq!(sim.query("SYST:EST?"), "1");
+    assert_eq!(sim.outputs(0), [0; 16]);
+    assert_eq!(sim.outputs(1), [0; 16]);
+
+    // Can not be released while the input is active
+    sim.send("SYST:EST:RES");
+    assert!(sim.query("SYST:ERR?").starts_with("-2"));
+    hardware.estop.set_high(true);
+    sim.command("SYST:EST:RES");
+    // Nothing moves back to where it was before the stop
+    assert_eq!(sim.query("BODY:SERV:PWID:SET? 1"), "0");
+    assert_eq!(sim.outputs(0)[..12], [0; 12]);
+    sim.command("BODY:SERV:PWID:SET 1,1200");
+    assert_eq!(sim.outputs(0)[0], 246);
+    sim.stop();
+}
+
+#[test]
+fn foot_contacts_are_measured() {
+    let _lock = lock();
+    let hardware = Hardware::new();
+    let sim = Sim::start(hardware.clone());
+    hardware.contacts[0].set_high(false);
+    // Nothing fitted at boot
+    assert_eq!(sim.query("MEAS:LEG:CONT?"), "-1,-1,-1,-1,-1,-1,-1,-1");
+    sim.command("SYST:CONT:FITT 63");
+    assert_eq!(sim.query("MEAS:LEG:CONT?"), "1,0,0,0,0,0,-1,-1");
+    hardware.contacts[5].set_high(false);
+    sim.command("*CLS");
+    assert_eq!(sim.query("MEAS:LEG:CONT?"), "1,0,0,0,0,1,-1,-1");
+    sim.stop();
+}
+
+#[test]
+fn jammed_bank_is_turned_off() {
+    let _lock = lock();
+    let hardware = Hardware::new();
+    let sim = Sim::start(hardware.clone());
+    sim.command("*CLS;STAT:PRES;:STAT:QUES:PTR 32767");
+    let celsius: f32 = sim.query("MEAS:TEMP?").parse().unwrap();
+    assert!((celsius - 25.0).abs() < 0.5, "{}", celsius);
+
+    hardware.set_current_ma(1, 20_000);
+    // Every leg is probed before the bank trips, the current doesn't follow
+    // the legs in the simulator
+    let mut tripped = String::new();
+    for _ in 0..200 {
+        tripped = sim.query("SYST:SERV:PROT:TRIP?");
+        if tripped != "0,0" {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    assert_eq!(tripped, "0,1");
+    assert_eq!(sim.query("SYST:SERV:PROT:TRIP:LEG?"), "0,0,0,0,0,0,0,0");
+    let amps: Vec<f32> = sim
+        .query("MEAS:SERV:CURR?")
+        .split(',')
+        .map(|a| a.parse().unwrap())
+        .collect();
+    assert!(amps[0] < 0.1 && (amps[1] - 20.0).abs() < 0.1, "{:?}", amps);
+    assert_eq!(sim.query("STAT:QUES:COND?"), "2");
+    sim.command("*CLS");
+    assert_eq!(sim.outputs(0), [CENTER; 16]);
+    assert_eq!(sim.outputs(1), [0; 16]);
+
+    hardware.set_current_ma(1, 0);
+    sim.command("SYST:SERV:PROT:CLE");
+    assert_eq!(sim.query("SYST:SERV:PROT:TRIP?"), "0,0");
+    assert_eq!(sim.query("STAT:QUES:COND?"), "0");
+    assert_eq!(sim.outputs(1), [CENTER; 16]);
+    sim.stop();
+}
+
+#[test]
+fn servo_power_soft_start() {
+    let _lock = lock();
+    let hardware = Hardware::new();
+    let sim = Sim::start(hardware.clone());
+    sim.command("*CLS");
+    assert!(hardware.servo_power.is_low().unwrap());
+    // Safe pulse widths are written with the rail off
+    assert_eq!(sim.outputs(0), [CENTER; 16]);
+
+    sim.command("SYST:SERV:POW:STAG 10");
+    sim.command("SYST:SERV:POW ON");
+    let mut state = String::new();
+    for _ in 0..100 {
+        state = sim.query("SYST:SERV:POW:STAT?");
+        if state == "ON" {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    }
+    assert_eq!(state, "ON");
+    assert!(hardware.servo_power.is_high().unwrap());
+    sim.command("*CLS");
+    assert_eq!(sim.outputs(1), [CENTER; 16]);
+
+    sim.command("SYST:SERV:POW OFF");
+    assert!(hardware.servo_power.is_low().unwrap());
+
+    // The emergency stop cuts the rail until released
+    sim.command("SYST:SERV:POW ON");
+    sim.command("SYST:EST");
+    assert!(hardware.servo_power.is_low().unwrap());
+    assert_eq!(sim.query("SYST:SERV:POW:STAT?"), "OFF");
+    sim.command("SYST:EST:RES");
+    assert_ne!(sim.query("SYST:SERV:POW:STAT?"), "OFF");
+    sim.stop();
+}
+
+#[test]
+fn pwm_frequency_and_trim() {
+    let _lock = lock();
+    let hardware = Hardware::new();
+    let sim = Sim::start(hardware.clone());
+    sim.command("*CLS");
+    assert_eq!(hardware.i2c.prescale(BANK_ADDRESSES[0]), 121);
+
+    // Pulse widths keep their length at a faster rate
+    sim.command("DIAG:PWM:FREQ 200");
+    for address in BANK_ADDRESSES.iter() {
+        assert_eq!(hardware.i2c.prescale(*address), 30);
+    }
+    assert_eq!(sim.outputs(0), [1210; 16]);
+
+    // A fast oscillator takes more counts
+    sim.command("DIAG:PWM:TRIM 1.05");
+    assert_eq!(hardware.i2c.prescale(BANK_ADDRESSES[1]), 31);
+    assert_eq!(sim.outputs(1), [1230; 16]);
+    assert_eq!(sim.query("SYST:ERR?"), "0,\"No error\"");
+    sim.stop();
+}
+
+#[test]
+fn unreachable_controller_is_reported() {
+    let _lock = lock();
+    let hardware = Hardware::new();
+    let sim = Sim::start(hardware.clone());
+    hardware.i2c.set_connected(BANK_ADDRESSES[1], false);
+    let mut health = String::new();
+    for _ in 0..50 {
+        health = sim.query("DIAG:I2C:ERR? 2");
+        if health.ends_with(",1") {
+            break;
+        }
+    }
+    assert!(health.ends_with(",1"), "{}", health);
+    assert_eq!(sim.query("DIAG:I2C:ERR? 1"), "0,0,0,0");
+    assert!(sim.query("SYST:ERR?").starts_with("-300,"));
+    sim.stop();
+}
+
+#[test]
+fn battery_telemetry() {
+    let _lock = lock();
+    let hardware = Hardware::new();
+    hardware.set_battery_mv(8000);
+    let sim = Sim::start(hardware);
+    sim.query("SYST:TEL:FORM CSV;MASK 4;RATE 100;*OPC?");
+    let record = loop {
+        let line = sim.read_line();
+        if let Some(record) = line.strip_prefix("TLM ") {
+            break record.to_string();
+        }
+    };
+    let mv: u32 = record.split(',').nth(1).unwrap().parse().unwrap();
+    assert!((7950..=8000).contains(&mv), "{}", mv);
+    sim.stop();
+}